@@ -0,0 +1,30 @@
+use common::descriptions::DefinitionsJSON;
+use common::saveload::Encoder;
+use std::io;
+
+pub struct Definitions {
+    pub definitions: DefinitionsJSON,
+    /// Problems found by [`DefinitionsJSON::validate`], the file is not saved while there are any
+    pub errors: Vec<String>,
+    pub changed: bool,
+}
+
+impl Definitions {
+    pub fn new() -> io::Result<Self> {
+        let djson = common::saveload::load_raw("assets/definitions.json")?;
+        let definitions: DefinitionsJSON = common::saveload::JSONPretty::decode(&djson)?;
+        Ok(Self {
+            errors: definitions.validate(),
+            definitions,
+            changed: false,
+        })
+    }
+
+    pub fn validate(&mut self) {
+        self.errors = self.definitions.validate();
+    }
+
+    pub fn save(&self) {
+        common::saveload::JSONPretty::save(&self.definitions, "definitions");
+    }
+}
@@ -11,6 +11,7 @@ use engine::{Drawable, GfxContext, Mesh, SpriteBatch};
 use geom::{Matrix4, Vec2};
 
 use crate::companies::Companies;
+use crate::definitions::Definitions;
 use crate::State;
 
 #[derive(Copy, Clone, Debug)]
@@ -25,6 +26,7 @@ pub enum Tab {
 pub enum Inspected {
     None,
     Company(usize),
+    Definitions,
 }
 
 #[derive(Clone)]
@@ -38,6 +40,7 @@ pub enum Shown {
 pub struct Gui {
     pub tree: Option<DockState<Tab>>,
     pub companies: Companies,
+    pub definitions: Definitions,
     pub inspected: Inspected,
     pub shown: Shown,
 }
@@ -59,6 +62,7 @@ impl Gui {
         Self {
             tree: Some(tree),
             companies: Companies::new().expect("could not load companies.json"),
+            definitions: Definitions::new().expect("could not load definitions.json"),
             inspected: Inspected::None,
             shown: Shown::None,
         }
@@ -83,6 +87,19 @@ fn explorer(state: &mut State, ui: &mut Ui) {
             state.gui.companies.save();
         }
     }
+    if state.gui.definitions.changed && state.gui.definitions.errors.is_empty() {
+        if ui.button("Save definitions").clicked() {
+            state.gui.definitions.save();
+            state.gui.definitions.changed = false;
+        }
+    }
+    let r = ui.add_sized(
+        [ui.available_width(), 40.0],
+        egui::Button::new("Vehicles, buildings and costs"),
+    );
+    if r.clicked() {
+        state.gui.inspected = Inspected::Definitions;
+    }
     for (i, comp) in state.gui.companies.companies.iter().enumerate() {
         let r = ui.add_sized([ui.available_width(), 40.0], egui::Button::new(&comp.name));
         if r.clicked() {
@@ -204,6 +221,68 @@ fn properties(state: &mut State, ui: &mut Ui) {
                 });
             });
         }
+        Inspected::Definitions => definitions_properties(&mut state.gui.definitions, ui),
+    }
+}
+
+fn definitions_properties(defs: &mut Definitions, ui: &mut Ui) {
+    for e in &defs.errors {
+        ui.colored_label(Color32::RED, e);
+    }
+
+    let d = &mut defs.definitions;
+    let mut changed = false;
+
+    for (name, v) in d.vehicles.iter_mut() {
+        ui.label(name);
+        ui.indent(name, |ui| {
+            changed |= inspect(ui, "width", &mut v.width);
+            changed |= inspect(ui, "acceleration", &mut v.acceleration);
+            changed |= inspect(ui, "deceleration", &mut v.deceleration);
+            changed |= inspect(ui, "min_turning_radius", &mut v.min_turning_radius);
+            changed |= inspect(ui, "speed_factor", &mut v.speed_factor);
+            changed |= inspect(ui, "ang_acc", &mut v.ang_acc);
+        });
+    }
+
+    ui.add_space(5.0);
+    ui.label("locomotive");
+    ui.indent("locomotive", |ui| {
+        changed |= inspect(ui, "max_speed", &mut d.locomotive.max_speed);
+        changed |= inspect(ui, "acc_force", &mut d.locomotive.acc_force);
+        changed |= inspect(ui, "dec_force", &mut d.locomotive.dec_force);
+        changed |= inspect(ui, "price", &mut d.locomotive.price);
+    });
+    for (name, w) in d.wagons.iter_mut() {
+        ui.label(format!("{name} wagon"));
+        ui.indent(name, |ui| {
+            changed |= inspect(ui, "price", &mut w.price);
+        });
+    }
+
+    ui.add_space(5.0);
+    for (name, b) in d.buildings.iter_mut() {
+        ui.label(name);
+        ui.indent(name, |ui| {
+            changed |= inspect(ui, "price", &mut b.price);
+            changed |= inspect(ui, "power_consumption", &mut b.power_consumption);
+        });
+    }
+
+    ui.add_space(5.0);
+    ui.label("roads");
+    ui.indent("roads", |ui| {
+        changed |= inspect(ui, "base_price", &mut d.roads.base_price);
+        changed |= inspect(
+            ui,
+            "price_per_lane_meter",
+            &mut d.roads.price_per_lane_meter,
+        );
+    });
+
+    if changed {
+        defs.changed = true;
+        defs.validate();
     }
 }
 
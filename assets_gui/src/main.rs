@@ -11,6 +11,7 @@ use crate::gui::{Gui, Inspected, Shown};
 use crate::orbit_camera::OrbitCamera;
 
 mod companies;
+mod definitions;
 mod gui;
 mod orbit_camera;
 
@@ -98,7 +99,7 @@ impl engine::framework::State for State {
 
 fn create_shown(gfx: &mut GfxContext, state: &State, inspected: Inspected) -> Shown {
     match inspected {
-        Inspected::None => Shown::None,
+        Inspected::None | Inspected::Definitions => Shown::None,
         Inspected::Company(i) => {
             let comp = &state.gui.companies.companies[i];
             let p = Path::new(&comp.asset_location);
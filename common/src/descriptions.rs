@@ -1,6 +1,7 @@
 use egui_inspect::debug_inspect_impl;
 use geom::Vec2;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 #[derive(Serialize, Deserialize)]
 pub struct RecipeDescription {
@@ -49,6 +50,9 @@ pub struct GoodsCompanyDescriptionJSON {
     pub price: i64,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub zone: Option<Box<ZoneDescription>>,
+    /// Electricity drawn from the grid, in kW
+    #[serde(default)]
+    pub power_consumption: i32,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -72,3 +76,178 @@ impl Default for ZoneDescription {
         }
     }
 }
+
+/// Names of the road vehicles in [`DefinitionsJSON::vehicles`], in the order of `VehicleKind`
+pub const VEHICLE_NAMES: [&str; 3] = ["car", "truck", "bus"];
+/// Names of the wagons in [`DefinitionsJSON::wagons`], in the order of `RailWagonKind`
+pub const WAGON_NAMES: [&str; 2] = ["passenger", "freight"];
+/// Names of the buildings that are not companies in [`DefinitionsJSON::buildings`]
+pub const SPECIAL_BUILDING_NAMES: [&str; 4] = [
+    "house",
+    "rail_freight_station",
+    "train_station",
+    "external_trading",
+];
+
+/// Everything that is not a company but can be tweaked without recompiling,
+/// loaded from `assets/definitions.json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DefinitionsJSON {
+    pub vehicles: BTreeMap<String, VehicleDescription>,
+    pub locomotive: LocomotiveDescription,
+    pub wagons: BTreeMap<String, WagonDescription>,
+    pub buildings: BTreeMap<String, SpecialBuildingDescription>,
+    pub roads: RoadCostDescription,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VehicleDescription {
+    /// Size of the vehicle along its direction, in m
+    pub width: f32,
+    /// In m/s²
+    pub acceleration: f32,
+    /// In m/s²
+    pub deceleration: f32,
+    /// In m
+    pub min_turning_radius: f32,
+    /// Multiplier of the speed limit
+    pub speed_factor: f32,
+    /// Angular acceleration, in rad/s²
+    pub ang_acc: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LocomotiveDescription {
+    /// In m/s
+    pub max_speed: f32,
+    pub acc_force: f32,
+    pub dec_force: f32,
+    pub price: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WagonDescription {
+    pub price: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SpecialBuildingDescription {
+    pub price: i64,
+    /// Electricity drawn from the grid, in kW
+    pub power_consumption: i32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RoadCostDescription {
+    /// Price of any road
+    pub base_price: i64,
+    /// Price per lane for each meter of road
+    pub price_per_lane_meter: f32,
+}
+
+impl DefinitionsJSON {
+    /// Every problem found in the definitions, they can be used if there is none
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = vec![];
+
+        check_names(&mut errors, "vehicles", &VEHICLE_NAMES, &self.vehicles);
+        check_names(&mut errors, "wagons", &WAGON_NAMES, &self.wagons);
+        check_names(
+            &mut errors,
+            "buildings",
+            &SPECIAL_BUILDING_NAMES,
+            &self.buildings,
+        );
+
+        for (name, v) in &self.vehicles {
+            for (field, value) in [
+                ("width", v.width),
+                ("acceleration", v.acceleration),
+                ("deceleration", v.deceleration),
+                ("min_turning_radius", v.min_turning_radius),
+                ("speed_factor", v.speed_factor),
+                ("ang_acc", v.ang_acc),
+            ] {
+                if value <= 0.0 {
+                    errors.push(format!(
+                        "vehicle {name}: {field} must be positive, got {value}"
+                    ));
+                }
+            }
+        }
+
+        let loco = &self.locomotive;
+        for (field, value) in [
+            ("max_speed", loco.max_speed),
+            ("acc_force", loco.acc_force),
+            ("dec_force", loco.dec_force),
+        ] {
+            if value <= 0.0 {
+                errors.push(format!("locomotive: {field} must be positive, got {value}"));
+            }
+        }
+
+        let prices = std::iter::once(("locomotive".to_string(), loco.price))
+            .chain(
+                self.wagons
+                    .iter()
+                    .map(|(n, w)| (format!("wagon {n}"), w.price)),
+            )
+            .chain(
+                self.buildings
+                    .iter()
+                    .map(|(n, b)| (format!("building {n}"), b.price)),
+            )
+            .chain(std::iter::once((
+                "roads".to_string(),
+                self.roads.base_price,
+            )));
+        for (what, price) in prices {
+            if price < 0 {
+                errors.push(format!("{what}: price cannot be negative, got {price}"));
+            }
+        }
+        for (name, b) in &self.buildings {
+            if b.power_consumption < 0 {
+                errors.push(format!(
+                    "building {name}: power_consumption cannot be negative, got {}",
+                    b.power_consumption
+                ));
+            }
+        }
+        if self.roads.price_per_lane_meter < 0.0 {
+            errors.push(format!(
+                "roads: price_per_lane_meter cannot be negative, got {}",
+                self.roads.price_per_lane_meter
+            ));
+        }
+
+        errors
+    }
+}
+
+fn check_names<T>(
+    errors: &mut Vec<String>,
+    section: &str,
+    expected: &[&str],
+    m: &BTreeMap<String, T>,
+) {
+    for name in expected {
+        if !m.contains_key(*name) {
+            errors.push(format!("{section}: missing {name}"));
+        }
+    }
+    for name in m.keys() {
+        if !expected.contains(&&**name) {
+            errors.push(format!(
+                "{section}: unknown {name}, expected one of {expected:?}"
+            ));
+        }
+    }
+}
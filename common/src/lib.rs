@@ -204,12 +204,15 @@ mod chunkid;
 pub mod descriptions;
 pub mod history;
 pub mod logger;
+pub mod mods;
 pub mod rand;
+mod role;
 pub mod saveload;
 pub mod scroll;
 pub mod timestep;
 
 pub use chunkid::*;
+pub use role::*;
 
 pub use inline_tweak as tw;
 use rustc_hash::FxHasher;
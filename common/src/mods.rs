@@ -0,0 +1,403 @@
+//! Mods are packages in the `mods/` directory that add or override the game data and assets.
+//!
+//! A mod is a directory with a `mod.json` manifest, its files mirror the layout of `assets/`:
+//! - `items.json`, `companies.json`, `definitions.json` and `config.json` are merged into the
+//!   base files, see [`merge_json`]. Entries with the same name replace the base ones.
+//! - Any other file, like `models/x.glb`, `sprites/x.png` or `sounds/x.ogg`, replaces or adds
+//!   the file at the same path, see [`resolve`].
+//!
+//! `mods/load_order.json` lists the directories of the active mods, later mods win.
+//! Without it no mod is active.
+//!
+//! The simulation records the active mods in its options and the peers refuse to connect if their
+//! mods differ, see [`mismatch`].
+
+use crate::saveload::{Encoder, JSON};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt::{Display, Formatter};
+use std::hash::Hasher;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{RwLock, RwLockReadGuard};
+
+pub const MODS_DIR: &str = "mods";
+pub const LOAD_ORDER_FILE: &str = "load_order.json";
+pub const MANIFEST_FILE: &str = "mod.json";
+
+/// Identifies the exact content of a mod
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ModInfo {
+    pub name: String,
+    pub version: String,
+    /// Hash of every file of the mod, see [`hash_dir`]
+    pub hash: u64,
+}
+
+impl Display for ModInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} ({:016x})", self.name, self.version, self.hash)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ModManifest {
+    pub name: String,
+    #[serde(default)]
+    pub version: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub authors: Vec<String>,
+}
+
+pub struct Mod {
+    pub manifest: ModManifest,
+    pub info: ModInfo,
+    pub dir: PathBuf,
+}
+
+/// The mods in load order
+#[derive(Default)]
+pub struct ModSet {
+    mods: Vec<Mod>,
+}
+
+static ACTIVE: RwLock<ModSet> = RwLock::new(ModSet::empty());
+
+/// Replaces the mods used by [`resolve`] and [`load_json`], it should be done once at startup
+/// before anything is loaded
+pub fn set_active(mods: ModSet) {
+    for m in &mods.mods {
+        log::info!("mod active: {}", m.info);
+    }
+    *ACTIVE.write().unwrap() = mods;
+}
+
+pub fn active() -> RwLockReadGuard<'static, ModSet> {
+    ACTIVE.read().unwrap()
+}
+
+pub fn active_infos() -> Vec<ModInfo> {
+    active().infos()
+}
+
+/// Path of the asset taking the active mods into account
+pub fn resolve(path: impl AsRef<Path>) -> PathBuf {
+    active().resolve(path)
+}
+
+/// Content of the json file merged with the active mods
+pub fn load_json(path: impl AsRef<Path>) -> Result<String, String> {
+    active().load_json(path)
+}
+
+impl ModSet {
+    pub const fn empty() -> Self {
+        Self { mods: Vec::new() }
+    }
+
+    /// Loads the mods listed in the load order of `dir`, every problem found is returned
+    pub fn load(dir: impl AsRef<Path>) -> Result<Self, Vec<String>> {
+        let dir = dir.as_ref();
+        let order_path = dir.join(LOAD_ORDER_FILE);
+        let Ok(order) = std::fs::read(&order_path) else {
+            return Ok(Self::empty());
+        };
+        let order: Vec<String> =
+            JSON::decode(&order).map_err(|e| vec![format!("{}: {}", order_path.display(), e)])?;
+
+        let mut errors = vec![];
+        let mut mods: Vec<Mod> = vec![];
+        for name in order {
+            let mod_dir = dir.join(&name);
+            let manifest_path = mod_dir.join(MANIFEST_FILE);
+            let manifest: ModManifest = match std::fs::read(&manifest_path)
+                .map_err(|e| e.to_string())
+                .and_then(|x| JSON::decode(&x).map_err(|e| e.to_string()))
+            {
+                Ok(x) => x,
+                Err(e) => {
+                    errors.push(format!("{}: {}", manifest_path.display(), e));
+                    continue;
+                }
+            };
+            if mods.iter().any(|m| m.manifest.name == manifest.name) {
+                errors.push(format!("mod {} is loaded twice", manifest.name));
+                continue;
+            }
+            mods.push(Mod {
+                info: ModInfo {
+                    name: manifest.name.clone(),
+                    version: manifest.version.clone(),
+                    hash: hash_dir(&mod_dir),
+                },
+                manifest,
+                dir: mod_dir,
+            });
+        }
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        Ok(Self { mods })
+    }
+
+    pub fn mods(&self) -> &[Mod] {
+        &self.mods
+    }
+
+    pub fn infos(&self) -> Vec<ModInfo> {
+        self.mods.iter().map(|m| m.info.clone()).collect()
+    }
+
+    /// Path of the file overriding the asset, the asset itself if no mod overrides it.
+    /// The asset is located by the part of its path after the `assets` directory.
+    pub fn resolve(&self, path: impl AsRef<Path>) -> PathBuf {
+        let path = path.as_ref();
+        let Some(rel) = asset_relative(path) else {
+            return path.to_path_buf();
+        };
+        self.mods
+            .iter()
+            .rev()
+            .map(|m| m.dir.join(&rel))
+            .find(|p| p.is_file())
+            .unwrap_or_else(|| path.to_path_buf())
+    }
+
+    /// Content of the base json file with the same file of every mod merged into it in order
+    pub fn load_json(&self, path: impl AsRef<Path>) -> Result<String, String> {
+        let path = path.as_ref();
+        let base =
+            std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let Some(rel) = asset_relative(path) else {
+            return Ok(base);
+        };
+
+        let overlays = self
+            .mods
+            .iter()
+            .map(|m| m.dir.join(&rel))
+            .filter(|p| p.is_file())
+            .collect::<Vec<_>>();
+        if overlays.is_empty() {
+            return Ok(base);
+        }
+
+        let mut merged: Value =
+            serde_json::from_str(&base).map_err(|e| format!("{}: {}", path.display(), e))?;
+        for p in overlays {
+            let overlay: Value = std::fs::read_to_string(&p)
+                .map_err(|e| e.to_string())
+                .and_then(|x| serde_json::from_str(&x).map_err(|e| e.to_string()))
+                .map_err(|e| format!("{}: {}", p.display(), e))?;
+            merge_json(&mut merged, overlay);
+        }
+        Ok(merged.to_string())
+    }
+}
+
+/// Merges the overlay into the base:
+/// - objects are merged key by key
+/// - the entries of arrays of named objects replace the base entry with the same name, or are
+///   appended if there is none
+/// - anything else is replaced
+pub fn merge_json(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (k, v) in overlay {
+                match base.get_mut(&k) {
+                    Some(b) => merge_json(b, v),
+                    None => {
+                        base.insert(k, v);
+                    }
+                }
+            }
+        }
+        (Value::Array(base), Value::Array(overlay)) if overlay.iter().all(entry_name_is_some) => {
+            for v in overlay {
+                match base.iter_mut().find(|b| entry_name(b) == entry_name(&v)) {
+                    Some(b) => *b = v,
+                    None => base.push(v),
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+fn entry_name(v: &Value) -> Option<&str> {
+    v.get("name")?.as_str()
+}
+
+fn entry_name_is_some(v: &Value) -> bool {
+    entry_name(v).is_some()
+}
+
+/// Describes the difference between two sets of mods, None if they are the same
+pub fn mismatch(ours: &[ModInfo], theirs: &[ModInfo]) -> Option<String> {
+    if ours == theirs {
+        return None;
+    }
+    let list = |mods: &[ModInfo]| {
+        if mods.is_empty() {
+            return "no mods".to_string();
+        }
+        mods.iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    Some(format!(
+        "different mods: [{}] vs [{}]",
+        list(ours),
+        list(theirs)
+    ))
+}
+
+/// Hash of the relative paths and contents of every file in the directory, in path order so that
+/// it is the same on every machine
+pub fn hash_dir(dir: &Path) -> u64 {
+    let mut files = crate::saveload::walkdir(dir)
+        .filter_map(|p| {
+            let rel = p.strip_prefix(dir).ok()?;
+            let rel = rel
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            Some((rel, p))
+        })
+        .collect::<Vec<_>>();
+    files.sort();
+
+    // every component is prefixed by its length so that different trees cannot hash the same
+    let mut hasher = rustc_hash::FxHasher::default();
+    for (rel, p) in files {
+        let content = std::fs::read(p).unwrap_or_default();
+        hasher.write_usize(rel.len());
+        hasher.write(rel.as_bytes());
+        hasher.write_usize(content.len());
+        hasher.write(&content);
+    }
+    hasher.finish()
+}
+
+/// Part of the path after the `assets` directory
+fn asset_relative(path: &Path) -> Option<PathBuf> {
+    let mut components = path.components();
+    components.find(|c| matches!(c, Component::Normal(x) if *x == "assets"))?;
+    let rel = components.as_path();
+    if rel.as_os_str().is_empty() {
+        return None;
+    }
+    Some(rel.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(p: &Path, content: &str) {
+        std::fs::create_dir_all(p.parent().unwrap()).unwrap();
+        std::fs::write(p, content).unwrap();
+    }
+
+    #[test]
+    fn merge_named_entries() {
+        let mut base: Value = serde_json::from_str(
+            r#"{"list": [{"name": "a", "v": 1}, {"name": "b", "v": 2}], "obj": {"x": 1, "y": 2}}"#,
+        )
+        .unwrap();
+        let overlay: Value = serde_json::from_str(
+            r#"{"list": [{"name": "b", "v": 3}, {"name": "c", "v": 4}], "obj": {"y": 5}}"#,
+        )
+        .unwrap();
+        merge_json(&mut base, overlay);
+
+        let expected: Value = serde_json::from_str(
+            r#"{"list": [{"name": "a", "v": 1}, {"name": "b", "v": 3}, {"name": "c", "v": 4}], "obj": {"x": 1, "y": 5}}"#,
+        )
+        .unwrap();
+        assert_eq!(base, expected);
+    }
+
+    #[test]
+    fn hash_dir_separates_paths_and_contents() {
+        let root = std::env::temp_dir().join(format!("goria_hash_test_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let (a, b) = (root.join("a"), root.join("b"));
+        write(&a.join("ab"), "c");
+        write(&b.join("a"), "bc");
+
+        assert_ne!(hash_dir(&a), hash_dir(&b));
+        assert_eq!(hash_dir(&a), hash_dir(&a));
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn load_order_and_overrides() {
+        let root = std::env::temp_dir().join(format!("goria_mods_test_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let assets = root.join("assets");
+        let mods = root.join("mods");
+
+        write(
+            &assets.join("items.json"),
+            r#"[{"name": "a", "label": "A"}]"#,
+        );
+        write(&assets.join("models/house.glb"), "base");
+        write(&mods.join("first/mod.json"), r#"{"name": "first"}"#);
+        write(
+            &mods.join("first/items.json"),
+            r#"[{"name": "a", "label": "First"}]"#,
+        );
+        write(&mods.join("first/models/house.glb"), "first");
+        write(
+            &mods.join("second/mod.json"),
+            r#"{"name": "second", "version": "1.0"}"#,
+        );
+        write(
+            &mods.join("second/items.json"),
+            r#"[{"name": "b", "label": "B"}]"#,
+        );
+        write(&mods.join("load_order.json"), r#"["first", "second"]"#);
+
+        let set = ModSet::load(&mods).unwrap();
+        assert_eq!(set.mods().len(), 2);
+        assert_eq!(set.infos()[1].version, "1.0");
+
+        let items: Value =
+            serde_json::from_str(&set.load_json(assets.join("items.json")).unwrap()).unwrap();
+        let expected: Value = serde_json::from_str(
+            r#"[{"name": "a", "label": "First"}, {"name": "b", "label": "B"}]"#,
+        )
+        .unwrap();
+        assert_eq!(items, expected);
+
+        assert_eq!(
+            set.resolve(assets.join("models/house.glb")),
+            mods.join("first/models/house.glb")
+        );
+        assert_eq!(
+            set.resolve(assets.join("models/other.glb")),
+            assets.join("models/other.glb")
+        );
+
+        let before = set.infos();
+        write(
+            &mods.join("second/items.json"),
+            r#"[{"name": "b", "label": "C"}]"#,
+        );
+        let after = ModSet::load(&mods).unwrap().infos();
+        assert!(mismatch(&before, &after).is_some());
+        assert!(mismatch(&before, &before).is_none());
+
+        write(&mods.join("load_order.json"), r#"["first", "missing"]"#);
+        assert_eq!(ModSet::load(&mods).err().unwrap().len(), 1);
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
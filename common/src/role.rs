@@ -0,0 +1,39 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// What a player is allowed to do in multiplayer, ordered from the least to the most permissive
+#[derive(Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Hash, Debug)]
+pub enum Role {
+    /// Can only watch and chat
+    Viewer,
+    /// Can build and destroy
+    Builder,
+    /// Can do everything, including replacing the map
+    Admin,
+}
+
+impl Display for Role {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Role::Viewer => "viewer",
+            Role::Builder => "builder",
+            Role::Admin => "admin",
+        })
+    }
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "viewer" => Ok(Role::Viewer),
+            "builder" => Ok(Role::Builder),
+            "admin" => Ok(Role::Admin),
+            _ => Err(format!(
+                "unknown role {s:?}, expected viewer, builder or admin"
+            )),
+        }
+    }
+}
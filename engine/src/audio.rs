@@ -112,7 +112,7 @@ impl AudioContext {
         if name.starts_with("music") {
             return None;
         }
-        let p = format!("assets/sounds/{name}.ogg");
+        let p = common::mods::resolve(format!("assets/sounds/{name}.ogg"));
         let t = Instant::now();
         let buf = match common::saveload::load_raw(&p) {
             Ok(x) => x,
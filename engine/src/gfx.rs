@@ -472,7 +472,7 @@ impl GfxContext {
         }
 
         let tex = Arc::new(
-            TextureBuilder::try_from_path(&p)?
+            TextureBuilder::try_from_path(common::mods::resolve(&p))?
                 .with_label(label)
                 .with_mipmaps(self.mipmap_module())
                 .build(&self.device, &self.queue),
@@ -248,6 +248,7 @@ pub fn load_mesh_with_properties(
     let mut path = PathBuf::new();
     path.push("assets/models/");
     path.push(asset_name);
+    let path = common::mods::resolve(path);
 
     let t = Instant::now();
 
@@ -0,0 +1,267 @@
+//! Admin console on stdin.
+//!
+//! Lines are read in a background thread and parsed into [`ConsoleCommand`]s, which are executed
+//! by the main loop between two ticks. Type `help` to list the commands.
+
+use common::saveload::{Encoder, JSON};
+use geom::Color;
+use networking::{Frame, Role, Server};
+use simulation::multiplayer::chat::{Message, MessageKind};
+use simulation::utils::time::GameTime;
+use simulation::world_command::{WorldCommand, WorldCommands};
+use simulation::Simulation;
+use std::io::BufRead;
+use std::sync::mpsc::{channel, Receiver};
+
+const HELP: &str = "commands:
+  help                 show this message
+  players              list the players with their lag in frames
+  save [name]          save the world now (default: world)
+  load [name]          load a world, kicking everyone (default: world)
+  kick <name>          disconnect a player
+  ban <name>           disconnect a player and refuse it from now on
+  unban <name>         accept a banned player again
+  role <name> <role>   set the role of a player: viewer, builder or admin
+  pause                stop the simulation
+  resume               resume the simulation
+  warp <n>             run n ticks per timestep
+  say <message>        broadcast an info message in the chat";
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ConsoleCommand {
+    Help,
+    Players,
+    Save(String),
+    Load(String),
+    Kick(String),
+    Ban(String),
+    Unban(String),
+    Role(String, Role),
+    Pause,
+    Resume,
+    Warp(u32),
+    Say(String),
+}
+
+impl ConsoleCommand {
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let line = line.trim();
+        let (cmd, arg) = line.split_once(' ').unwrap_or((line, ""));
+        let arg = arg.trim();
+
+        let required = |what: &str| {
+            if arg.is_empty() {
+                return Err(format!("usage: {cmd} <{what}>"));
+            }
+            Ok(arg.to_string())
+        };
+        let save_name = || {
+            if arg.is_empty() {
+                "world".to_string()
+            } else {
+                arg.to_string()
+            }
+        };
+
+        Ok(match cmd {
+            "help" | "?" => Self::Help,
+            "players" | "list" => Self::Players,
+            "save" => Self::Save(save_name()),
+            "load" => Self::Load(save_name()),
+            "kick" => Self::Kick(required("name")?),
+            "ban" => Self::Ban(required("name")?),
+            "unban" => Self::Unban(required("name")?),
+            "role" => {
+                let usage = || "usage: role <name> <viewer|builder|admin>".to_string();
+                let (name, role) = arg.rsplit_once(' ').ok_or_else(usage)?;
+                Self::Role(name.trim().to_string(), role.parse()?)
+            }
+            "pause" => Self::Pause,
+            "resume" => Self::Resume,
+            "warp" => Self::Warp(
+                required("n")?
+                    .parse()
+                    .map_err(|_| "usage: warp <n>, n being a positive integer".to_string())?,
+            ),
+            "say" => Self::Say(required("message")?),
+            _ => return Err(format!("unknown command {cmd:?}, type help")),
+        })
+    }
+
+    pub fn execute(self, w: &mut Simulation, server: &mut Server<Simulation, WorldCommands>) {
+        match self {
+            Self::Help => println!("{HELP}"),
+            Self::Players => {
+                let players = server.players();
+                println!("{} players", players.len());
+                for p in players {
+                    let role = if p.spectator {
+                        "spectator".to_string()
+                    } else {
+                        p.role.to_string()
+                    };
+                    match p.lag {
+                        Some(lag) => println!("  {} ({role}): {lag} frames behind", p.name),
+                        None => println!("  {} ({role}): too late", p.name),
+                    }
+                }
+            }
+            Self::Save(name) => {
+                w.save_to_disk(&name);
+                JSON::save_silent(server.identities(), "identities");
+                println!("saved {name}");
+            }
+            Self::Load(name) => {
+                let Some(loaded) = Simulation::load_from_disk(&name) else {
+                    println!("could not load {name}");
+                    return;
+                };
+                *w = loaded;
+                server.reset(Frame(w.get_tick()));
+                println!("loaded {name} at tick {}", w.get_tick());
+            }
+            Self::Kick(name) => {
+                if !server.kick(&name) {
+                    println!("no player named {name}");
+                }
+            }
+            Self::Ban(name) => {
+                server.ban(&name);
+                JSON::save_silent(server.identities(), "identities");
+                println!("banned {name}");
+            }
+            Self::Unban(name) => {
+                if !server.unban(&name) {
+                    println!("{name} is not banned");
+                }
+                JSON::save_silent(server.identities(), "identities");
+            }
+            Self::Role(name, role) => {
+                server.set_role(&name, role);
+                JSON::save_silent(server.identities(), "identities");
+                println!("{name} is now {role}");
+            }
+            Self::Pause => server.set_paused(true),
+            Self::Resume => server.set_paused(false),
+            Self::Warp(warp) => {
+                server.set_time_warp(warp);
+                println!("time warp is now {}", server.time_warp());
+            }
+            Self::Say(text) => {
+                let mut commands = WorldCommands::default();
+                commands.push(WorldCommand::SendMessage {
+                    message: Message {
+                        name: "server".to_string(),
+                        text,
+                        sent_at: w.read::<GameTime>().instant(),
+                        color: Color::WHITE,
+                        kind: MessageKind::Info,
+                    },
+                });
+                server.push_input(&commands);
+            }
+        }
+    }
+}
+
+/// Reads stdin in a background thread, commands that cannot be parsed are reported right away
+pub fn spawn() -> Receiver<ConsoleCommand> {
+    let (send, recv) = channel();
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lock().lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            match ConsoleCommand::parse(&line) {
+                Ok(cmd) => {
+                    if send.send(cmd).is_err() {
+                        break;
+                    }
+                }
+                Err(e) => println!("{e}"),
+            }
+        }
+    });
+    recv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ConsoleCommand::{self, *};
+
+    fn parse(line: &str) -> Result<ConsoleCommand, String> {
+        ConsoleCommand::parse(line)
+    }
+
+    #[test]
+    fn parses_commands() {
+        assert_eq!(parse("help"), Ok(Help));
+        assert_eq!(parse("?"), Ok(Help));
+        assert_eq!(parse("list"), Ok(Players));
+        assert_eq!(parse("  players  \n"), Ok(Players));
+        assert_eq!(parse("save"), Ok(Save("world".to_string())));
+        assert_eq!(parse("save  backup "), Ok(Save("backup".to_string())));
+        assert_eq!(parse("load old"), Ok(Load("old".to_string())));
+        assert_eq!(parse("kick bob"), Ok(Kick("bob".to_string())));
+        assert_eq!(parse("ban bob"), Ok(Ban("bob".to_string())));
+        assert_eq!(parse("unban bob"), Ok(Unban("bob".to_string())));
+        assert_eq!(parse("pause"), Ok(Pause));
+        assert_eq!(parse("resume"), Ok(Resume));
+        assert_eq!(parse("warp 8"), Ok(Warp(8)));
+        assert_eq!(
+            parse("say hello  everyone"),
+            Ok(Say("hello  everyone".to_string()))
+        );
+    }
+
+    #[test]
+    fn role_name_can_contain_spaces() {
+        assert_eq!(
+            parse("role bob the builder builder"),
+            Ok(Role(
+                "bob the builder".to_string(),
+                networking::Role::Builder
+            ))
+        );
+        assert_eq!(
+            parse("role alice admin"),
+            Ok(Role("alice".to_string(), networking::Role::Admin))
+        );
+    }
+
+    #[test]
+    fn refuses_malformed_input() {
+        for line in [
+            "",
+            "   ",
+            "HELP",
+            "teleport bob",
+            "kick",
+            "ban  ",
+            "unban",
+            "say",
+            "warp",
+            "warp fast",
+            "warp -1",
+            "warp 1.5",
+            "role",
+            "role bob",
+            "role bob king",
+            "role bob Admin",
+        ] {
+            assert!(parse(line).is_err(), "{line:?} was accepted");
+        }
+
+        assert_eq!(parse("kick"), Err("usage: kick <name>".to_string()));
+        assert_eq!(
+            parse("role bob king"),
+            Err("unknown role \"king\", expected viewer, builder or admin".to_string())
+        );
+        assert!(parse("teleport")
+            .unwrap_err()
+            .starts_with("unknown command"));
+    }
+}
@@ -1,11 +1,24 @@
+use crate::query::QueryServer;
 use common::logger::MyLog;
+use common::mods::ModSet;
+use common::saveload::{Encoder, JSON};
 use common::unwrap_or;
-use networking::{Frame, Server, ServerConfiguration, ServerPollResult};
-use simulation::world_command::WorldCommands;
-use simulation::Simulation;
+use networking::{Frame, Role, Server, ServerConfiguration, ServerPollResult};
+use simulation::map::procgen::heightmap::{Biome, WorldGenOptions};
+use simulation::map::procgen::OsmData;
+use simulation::map::{export_map, MapExportFormat};
+use simulation::souls::goods_company::GoodsCompanyRegistry;
+use simulation::world_command::{WorldCommand, WorldCommands};
+use simulation::{Simulation, SimulationOptions};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
 use std::time::{Duration, Instant};
 use structopt::StructOpt;
 
+mod console;
+mod metrics;
+mod query;
+
 const VERSION: &str = include_str!("../../VERSION");
 
 #[derive(StructOpt, Debug)]
@@ -27,19 +40,171 @@ struct Opt {
     /// i.e. 20ms = 50FPS
     #[structopt(long, default_value = "20")]
     timestep: u64,
+
+    /// Start a new world from an OpenStreetMap extract (.osm or .osm.pbf) instead of the savegame
+    #[structopt(long, parse(from_os_str))]
+    osm: Option<PathBuf>,
+
+    /// Export the map of the world to this file and exit
+    #[structopt(long, parse(from_os_str))]
+    export: Option<PathBuf>,
+
+    /// Format of the map export: geojson or graph
+    #[structopt(long, default_value = "geojson")]
+    export_format: MapExportFormat,
+
+    /// Seed of the world generation when starting a new world
+    #[structopt(long)]
+    seed: Option<u64>,
+
+    /// Biome preset of the world generation when starting a new world:
+    /// temperate, islands, continent, highlands or arid
+    #[structopt(long, default_value = "temperate")]
+    biome: Biome,
+
+    /// Serve prometheus metrics over http on this port of localhost
+    #[structopt(long)]
+    metrics_port: Option<u16>,
+
+    /// Dump the prometheus metrics to this file every second
+    #[structopt(long, parse(from_os_str))]
+    metrics_file: Option<PathBuf>,
+
+    /// Serve the read-only json query api over http on this port of localhost
+    #[structopt(long)]
+    query_port: Option<u16>,
+
+    /// Role of the players that were not given one with the console:
+    /// viewer, builder or admin
+    #[structopt(long, default_value = "builder")]
+    default_role: Role,
+
+    /// Players must give this password to join
+    #[structopt(long)]
+    password: Option<String>,
+
+    /// Stream the replay of this save to spectators instead of running a game,
+    /// everyone joins as a spectator
+    #[structopt(long)]
+    replay: Option<String>,
+
+    /// Number of replay ticks per timestep
+    #[structopt(long, default_value = "1")]
+    replay_speed: u32,
+
+    /// Check the items, companies and definitions, print the production chains and exit
+    #[structopt(long)]
+    check_assets: bool,
+
+    /// Directory of the mods, the active ones are listed in its load_order.json
+    #[structopt(long, parse(from_os_str), default_value = "mods")]
+    mods: PathBuf,
+}
+
+/// A new empty world, the simulation is initialized once for all the tests.
+/// The assets are found from the root of the repository, like when running the server
+#[cfg(test)]
+fn test_world() -> Simulation {
+    static INIT: std::sync::Once = std::sync::Once::new();
+    INIT.call_once(|| {
+        std::env::set_current_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/..")).unwrap();
+        simulation::init::init();
+    });
+    Simulation::new(false)
+}
+
+fn new_world(opt: &Opt) -> Simulation {
+    let mut worldgen = WorldGenOptions::preset(opt.biome);
+    if let Some(seed) = opt.seed {
+        worldgen.seed = seed;
+    }
+    Simulation::new_with_options(SimulationOptions {
+        worldgen,
+        ..Default::default()
+    })
 }
 
 fn main() {
     let opt: Opt = Opt::from_args();
     MyLog::init();
+
+    match ModSet::load(&opt.mods) {
+        Ok(mods) => common::mods::set_active(mods),
+        Err(errors) => {
+            for e in errors {
+                log::error!("could not load mods: {}", e);
+            }
+            return;
+        }
+    }
+
+    if opt.check_assets {
+        let report = simulation::economy::check_assets();
+        print!("{report}");
+        if !report.is_ok() {
+            std::process::exit(1);
+        }
+        return;
+    }
+
     simulation::init::init();
 
     log::info!("starting server with version: {}", VERSION);
 
-    let mut w = unwrap_or!(Simulation::load_from_disk("world"), {
-        log::info!("savegame not found defaulting to empty");
-        Simulation::new(true)
-    });
+    let mut replay = None;
+
+    let mut w = match (&opt.replay, &opt.osm) {
+        (Some(name), _) => {
+            let Some(r) = Simulation::load_replay_from_disk(name) else {
+                log::error!("could not load the replay of {}", name);
+                return;
+            };
+            log::info!("streaming replay {} of {} commands", name, r.commands.len());
+            let (w, loader) = Simulation::from_replay(r);
+            replay = Some(loader.replay);
+            w
+        }
+        (None, Some(path)) => {
+            let data = match OsmData::from_file(path) {
+                Ok(x) => x,
+                Err(e) => {
+                    log::error!("could not read osm extract {:?}: {}", path, e);
+                    return;
+                }
+            };
+            log::info!(
+                "importing {} ways from osm extract {:?}",
+                data.ways.len(),
+                path
+            );
+            let mut w = new_world(&opt);
+            let center = w.map().environment.bounds().center();
+            WorldCommand::MapLoadOSM {
+                data: Box::new(data),
+                center,
+            }
+            .apply(&mut w);
+            w
+        }
+        (None, None) => unwrap_or!(Simulation::load_from_disk("world"), {
+            log::info!("savegame not found defaulting to empty");
+            new_world(&opt)
+        }),
+    };
+
+    if let Some(ref path) = opt.export {
+        let exported = export_map(
+            &w.map(),
+            &w.read::<GoodsCompanyRegistry>(),
+            opt.export_format,
+        );
+        match exported.map(|x| std::fs::write(path, x)) {
+            Ok(Ok(())) => log::info!("exported map to {:?}", path),
+            Ok(Err(e)) => log::error!("could not write map export to {:?}: {}", path, e),
+            Err(e) => log::error!("could not export map: {}", e),
+        }
+        return;
+    }
 
     let mut sched = Simulation::schedule();
 
@@ -49,7 +214,11 @@ fn main() {
         port: opt.port,
         virtual_client: None,
         version: VERSION.to_string(),
+        mods: common::mods::active_infos(),
         always_run: opt.always_run,
+        default_role: opt.default_role,
+        password: opt.password.clone(),
+        spectators_only: replay.is_some(),
     }) {
         Ok(x) => x,
         Err(e) => {
@@ -57,9 +226,47 @@ fn main() {
             return;
         }
     };
+    server.set_input_filter(WorldCommands::retain_permitted);
+    server.set_world_parts(Simulation::parts);
+    if let Ok(identities) = JSON::load("identities") {
+        server.set_identities(identities);
+    }
+
+    if let Some(ref replay) = replay {
+        // a command recorded at tick t is applied by the frame t+1
+        let mut frames: BTreeMap<u64, WorldCommands> = BTreeMap::new();
+        for (tick, command) in &replay.commands {
+            frames.entry(tick.0 + 1).or_default().push(command.clone());
+        }
+        for (frame, commands) in frames {
+            server.push_input_at(Frame(frame), &commands);
+        }
+        server.set_time_warp(opt.replay_speed);
+    }
     log::info!("server started!");
 
+    let metrics = match opt.metrics_port.map(metrics::serve) {
+        Some(Err(e)) => {
+            log::error!("could not serve metrics: {}", e);
+            return;
+        }
+        Some(Ok(x)) => Some(x),
+        None => None,
+    };
+
+    let query = match opt.query_port.map(QueryServer::start) {
+        Some(Err(e)) => {
+            log::error!("could not serve the query api: {}", e);
+            return;
+        }
+        Some(Ok(x)) => Some(x),
+        None => None,
+    };
+
+    let console = console::spawn();
+
     let mut last_saved = Instant::now();
+    let mut last_metrics = (Instant::now(), w.get_tick());
 
     loop {
         if let ServerPollResult::Input(inputs) = server.poll(&w, Frame(w.get_tick()), None) {
@@ -70,11 +277,38 @@ fn main() {
             }
         }
 
-        if last_saved.elapsed().as_secs() > opt.autosave {
+        while let Ok(cmd) = console.try_recv() {
+            cmd.execute(&mut w, &mut server);
+        }
+
+        if replay.is_none() && last_saved.elapsed().as_secs() > opt.autosave {
             w.save_to_disk("world");
+            JSON::save_silent(server.identities(), "identities");
             last_saved = Instant::now();
         }
 
+        if let Some(ref query) = query {
+            query.answer(&w);
+        }
+
+        if (metrics.is_some() || opt.metrics_file.is_some())
+            && last_metrics.0.elapsed() > Duration::from_secs(1)
+        {
+            let (t, tick) = last_metrics;
+            // the console can load an older world
+            let tick_rate = w.get_tick().saturating_sub(tick) as f64 / t.elapsed().as_secs_f64();
+            let rendered = metrics::render(&w, &sched, tick_rate, server.n_players());
+            if let Some(ref path) = opt.metrics_file {
+                if let Err(e) = std::fs::write(path, &rendered) {
+                    log::error!("could not write metrics to {:?}: {}", path, e);
+                }
+            }
+            if let Some(ref metrics) = metrics {
+                *metrics.lock().unwrap() = rendered;
+            }
+            last_metrics = (Instant::now(), w.get_tick());
+        }
+
         std::thread::sleep(Duration::from_millis(1));
     }
 }
@@ -0,0 +1,258 @@
+//! Prometheus metrics of the running simulation.
+//!
+//! The metrics are rendered in the text exposition format once per second by the main loop and
+//! served to anyone connecting to the metrics port on localhost, or dumped to a file.
+
+use simulation::economy::{EcoStats, Government, ItemHistories, ItemRegistry, Market};
+use simulation::utils::scheduler::SeqSchedule;
+use simulation::Simulation;
+use std::fmt::Write;
+use std::io::{Read, Write as IoWrite};
+use std::net::{Ipv4Addr, TcpListener};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Latest rendered metrics, shared with the http thread
+pub type SharedMetrics = Arc<Mutex<String>>;
+
+/// Serves the metrics over http on localhost, in a background thread
+pub fn serve(port: u16) -> std::io::Result<SharedMetrics> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+    let metrics = SharedMetrics::default();
+    let shared = metrics.clone();
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else {
+                continue;
+            };
+            let _ = stream.set_read_timeout(Some(Duration::from_secs(1)));
+            // the only thing served are the metrics, the request doesn't matter
+            let mut buf = [0; 1024];
+            let _ = stream.read(&mut buf);
+
+            let body = shared.lock().unwrap().clone();
+            let _ = write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+        }
+    });
+
+    log::info!("serving metrics on http://localhost:{}/metrics", port);
+    Ok(metrics)
+}
+
+struct Metrics(String);
+
+impl Metrics {
+    fn header(&mut self, name: &str, kind: &str, help: &str) {
+        let _ = writeln!(self.0, "# HELP egregoria_{name} {help}");
+        let _ = writeln!(self.0, "# TYPE egregoria_{name} {kind}");
+    }
+
+    fn value(&mut self, name: &str, labels: &[(&str, &str)], v: impl std::fmt::Display) {
+        let _ = write!(self.0, "egregoria_{name}");
+        if !labels.is_empty() {
+            let labels: Vec<String> = labels
+                .iter()
+                .map(|(k, v)| format!("{k}=\"{}\"", v.replace('\\', "\\\\").replace('"', "\\\"")))
+                .collect();
+            let _ = write!(self.0, "{{{}}}", labels.join(","));
+        }
+        let _ = writeln!(self.0, " {v}");
+    }
+
+    fn single(&mut self, name: &str, kind: &str, help: &str, v: impl std::fmt::Display) {
+        self.header(name, kind, help);
+        self.value(name, &[], v);
+    }
+}
+
+/// Renders the metrics in the prometheus text format
+pub fn render(sim: &Simulation, sched: &SeqSchedule, tick_rate: f64, n_players: usize) -> String {
+    let mut m = Metrics(String::new());
+
+    m.single(
+        "tick",
+        "counter",
+        "Current tick of the simulation",
+        sim.get_tick(),
+    );
+    m.single(
+        "tick_rate",
+        "gauge",
+        "Ticks simulated per second",
+        format!("{tick_rate:.2}"),
+    );
+    m.single(
+        "players",
+        "gauge",
+        "Number of players connected and in game",
+        n_players,
+    );
+
+    m.header(
+        "system_time_ms",
+        "gauge",
+        "Average time spent in each system per tick, in milliseconds",
+    );
+    for (name, t) in sched.times() {
+        m.value("system_time_ms", &[("system", &name)], t);
+    }
+
+    let world = sim.world();
+    m.header("entities", "gauge", "Number of entities per kind");
+    for (kind, n) in [
+        ("vehicles", world.vehicles.len()),
+        ("humans", world.humans.len()),
+        ("trains", world.trains.len()),
+        ("wagons", world.wagons.len()),
+        ("freight_stations", world.freight_stations.len()),
+        ("companies", world.companies.len()),
+    ] {
+        m.value("entities", &[("kind", kind)], n);
+    }
+
+    let map = sim.map();
+    m.header("map_objects", "gauge", "Number of map objects per kind");
+    for (kind, n) in [
+        ("roads", map.roads().len()),
+        ("intersections", map.intersections().len()),
+        ("buildings", map.buildings().len()),
+        ("lots", map.lots().len()),
+    ] {
+        m.value("map_objects", &[("kind", kind)], n);
+    }
+    drop(map);
+
+    m.single(
+        "government_money",
+        "gauge",
+        "Money of the government",
+        sim.read::<Government>().money.cents() as f64 / 100.0,
+    );
+
+    let registry = sim.read::<ItemRegistry>();
+    let market = sim.read::<Market>();
+    // samples of a metric must be grouped together, so collect the orders first
+    let mut orders = vec![];
+    for (&item, single) in market.iter() {
+        let name = &*registry[item].name;
+        let buy_qty: u32 = single.buy_orders().values().map(|o| o.qty).sum();
+        let sell_qty: u32 = single.sell_orders().values().map(|o| o.qty).sum();
+        orders.push((name, "buy", single.buy_orders().len(), buy_qty));
+        orders.push((name, "sell", single.sell_orders().len(), sell_qty));
+    }
+    m.header("market_orders", "gauge", "Number of orders in the market");
+    for &(item, side, n, _) in &orders {
+        m.value("market_orders", &[("item", item), ("side", side)], n);
+    }
+    m.header(
+        "market_order_quantity",
+        "gauge",
+        "Total quantity of the orders in the market",
+    );
+    for &(item, side, _, qty) in &orders {
+        m.value(
+            "market_order_quantity",
+            &[("item", item), ("side", side)],
+            qty,
+        );
+    }
+
+    let stats = sim.read::<EcoStats>();
+    let mut trades = vec![];
+    for (flow, histories) in [
+        ("exports", &stats.exports),
+        ("imports", &stats.imports),
+        ("internal", &stats.internal_trade),
+    ] {
+        trade_totals(&mut trades, &registry, flow, histories);
+    }
+    m.header(
+        "trade_quantity",
+        "gauge",
+        "Quantity of items traded over the shortest history window",
+    );
+    for &(item, flow, quantity, _) in &trades {
+        m.value(
+            "trade_quantity",
+            &[("item", item), ("flow", flow)],
+            quantity,
+        );
+    }
+    m.header(
+        "trade_money",
+        "gauge",
+        "Money exchanged with the outside over the shortest history window",
+    );
+    for &(item, flow, _, money) in &trades {
+        m.value("trade_money", &[("item", item), ("flow", flow)], money);
+    }
+
+    m.0
+}
+
+/// Sums the quantity and money of the trades of every item over the shortest history level
+fn trade_totals<'a>(
+    out: &mut Vec<(&'a str, &'static str, i64, f64)>,
+    registry: &'a ItemRegistry,
+    flow: &'static str,
+    histories: &ItemHistories,
+) {
+    for (item, level) in histories.iter_histories(0) {
+        let quantity: i64 = level.past_ring_items.iter().sum();
+        let cents: i64 = level.past_ring_money.iter().map(|x| x.cents()).sum();
+        out.push((&registry[item].name, flow, quantity, cents as f64 / 100.0));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render, Metrics};
+    use simulation::Simulation;
+
+    #[test]
+    fn labels_are_escaped() {
+        let mut m = Metrics(String::new());
+        m.single("players", "gauge", "Number of players", 3);
+        m.value("orders", &[("item", r#"a "b" \c"#), ("side", "buy")], 2);
+
+        assert_eq!(
+            m.0,
+            "# HELP egregoria_players Number of players\n\
+             # TYPE egregoria_players gauge\n\
+             egregoria_players 3\n\
+             egregoria_orders{item=\"a \\\"b\\\" \\\\c\",side=\"buy\"} 2\n"
+        );
+    }
+
+    #[test]
+    fn render_is_valid_exposition_format() {
+        let sim = crate::test_world();
+        let sched = Simulation::schedule();
+        let text = render(&sim, &sched, 50.0, 2);
+
+        assert!(text.contains("\negregoria_tick_rate 50.00\n"));
+        assert!(text.contains("\negregoria_players 2\n"));
+
+        // every sample comes after the type of its metric, with a numeric value
+        let mut current = None;
+        for line in text.lines() {
+            if let Some(rest) = line.strip_prefix("# TYPE ") {
+                current = rest.split(' ').next();
+                continue;
+            }
+            if line.starts_with('#') {
+                continue;
+            }
+            let (series, value) = line.rsplit_once(' ').unwrap();
+            assert!(value.parse::<f64>().is_ok(), "{line}");
+            let name = series.split('{').next().unwrap();
+            assert_eq!(Some(name), current, "{line}");
+        }
+    }
+}
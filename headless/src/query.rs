@@ -0,0 +1,473 @@
+//! Read-only JSON query API over http on localhost.
+//!
+//! Connections are accepted in a background thread and handled by a small pool of workers, the
+//! requests are answered by the main loop between two ticks so that they see a consistent
+//! simulation.
+//!
+//! Ids are written like in the logs: `<index>v<version>`.
+//!
+//! - `GET /entities/<kind>`: ids of the entities of a kind
+//!   (vehicle, human, train, wagon, freight_station, company)
+//! - `GET /entity/<kind>/<id>`: an entity with its position
+//! - `GET /buildings`: ids and kinds of the buildings
+//! - `GET /building/<id>`: a building with its owner and the souls inside
+//! - `GET /project?x=<x>&y=<y>`: what is on the map at this position
+//! - `GET /market` and `GET /market/<item>`: the order books of the market
+
+use serde::Serialize;
+use serde_json::{json, Value};
+use simulation::economy::{ItemRegistry, Market, SingleMarket};
+use simulation::map::{BuildingID, ProjectFilter, ProjectKind};
+use simulation::map_dynamic::BuildingInfos;
+use simulation::{
+    AnyEntity, CompanyID, FreightStationID, HumanID, Simulation, SoulID, TrainID, VehicleID,
+    WagonID,
+};
+use slotmapd::{Key, KeyData};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::sync::mpsc::{channel, sync_channel, Receiver, Sender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+struct Request {
+    path: String,
+    respond: Sender<(u16, String)>,
+}
+
+pub struct QueryServer {
+    requests: Receiver<Request>,
+}
+
+/// Number of threads handling the connections
+const WORKERS: usize = 4;
+/// Connections waiting for a worker, the next ones are refused until one is free
+const BACKLOG: usize = 16;
+
+impl QueryServer {
+    /// Accepts connections on localhost in a background thread
+    pub fn start(port: u16) -> std::io::Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+        log::info!("serving the query api on http://localhost:{}", port);
+        Ok(Self::serve(listener))
+    }
+
+    /// Hands the accepted connections to a fixed pool of workers, so that a flood of
+    /// connections cannot spawn an unbounded number of threads
+    fn serve(listener: TcpListener) -> Self {
+        let (send, requests) = channel();
+        let (queue, streams) = sync_channel::<TcpStream>(BACKLOG);
+        let streams = Arc::new(Mutex::new(streams));
+
+        for _ in 0..WORKERS {
+            let streams = streams.clone();
+            let send = send.clone();
+            std::thread::spawn(move || loop {
+                let Ok(stream) = streams.lock().unwrap().recv() else {
+                    return;
+                };
+                handle_connection(stream, send.clone());
+            });
+        }
+
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else {
+                    continue;
+                };
+                if let Err(TrySendError::Full(stream)) = queue.try_send(stream) {
+                    respond(
+                        stream,
+                        503,
+                        json!({ "error": "too many connections" }).to_string(),
+                    );
+                }
+            }
+        });
+
+        Self { requests }
+    }
+
+    /// Answers the pending requests
+    pub fn answer(&self, sim: &Simulation) {
+        while let Ok(req) = self.requests.try_recv() {
+            let response = match answer(sim, &req.path) {
+                Ok(v) => (200, v.to_string()),
+                Err((status, msg)) => (status, json!({ "error": msg }).to_string()),
+            };
+            let _ = req.respond.send(response);
+        }
+    }
+}
+
+fn handle_connection(stream: TcpStream, requests: Sender<Request>) {
+    let _ = stream.set_read_timeout(Some(Duration::from_secs(5)));
+    let mut line = String::new();
+    if BufReader::new(&stream).read_line(&mut line).is_err() {
+        return;
+    }
+
+    let mut parts = line.split_whitespace();
+    let (status, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some(path)) => {
+            let (respond, response) = channel();
+            let req = Request {
+                path: path.to_string(),
+                respond,
+            };
+            if requests.send(req).is_err() {
+                return;
+            }
+            match response.recv_timeout(Duration::from_secs(10)) {
+                Ok(x) => x,
+                Err(_) => (503, json!({ "error": "simulation is busy" }).to_string()),
+            }
+        }
+        _ => (405, json!({ "error": "only GET is supported" }).to_string()),
+    };
+    respond(stream, status, body);
+}
+
+fn respond(mut stream: TcpStream, status: u16, body: String) {
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        500 => "Internal Server Error",
+        _ => "Service Unavailable",
+    };
+    let _ = write!(
+        stream,
+        "HTTP/1.1 {status} {reason}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        body.len(),
+        body
+    );
+}
+
+type QueryResult = Result<Value, (u16, String)>;
+
+fn not_found(what: impl std::fmt::Display) -> (u16, String) {
+    (404, format!("{what} not found"))
+}
+
+fn bad_request(what: impl std::fmt::Display) -> (u16, String) {
+    (400, what.to_string())
+}
+
+fn answer(sim: &Simulation, path: &str) -> QueryResult {
+    let (path, query) = path.split_once('?').unwrap_or((path, ""));
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+
+    match segments[..] {
+        ["entities", kind] => entities(sim, kind),
+        ["entity", kind, id] => entity(sim, parse_entity(kind, id)?),
+        ["buildings"] => Ok(buildings(sim)),
+        ["building", id] => building(sim, BuildingID::from(parse_key(id)?)),
+        ["project"] => project(sim, query),
+        ["market"] => Ok(market(sim, None)),
+        ["market", item] => {
+            let id = sim
+                .read::<ItemRegistry>()
+                .try_id(item)
+                .ok_or_else(|| not_found(format!("item {item}")))?;
+            Ok(market(sim, Some(id)))
+        }
+        _ => Err(not_found(format!("endpoint {path}"))),
+    }
+}
+
+fn fmt_key(k: impl Key) -> String {
+    format!("{:?}", k.data())
+}
+
+fn parse_key(s: &str) -> Result<KeyData, (u16, String)> {
+    let parse = || {
+        let (idx, version) = s.split_once('v')?;
+        let idx: u32 = idx.parse().ok()?;
+        let version: u32 = version.parse().ok()?;
+        Some(KeyData::from_ffi(
+            (u64::from(version) << 32) | u64::from(idx),
+        ))
+    };
+    parse().ok_or_else(|| bad_request(format!("invalid id {s}, expected <index>v<version>")))
+}
+
+fn parse_entity(kind: &str, id: &str) -> Result<AnyEntity, (u16, String)> {
+    let k = parse_key(id)?;
+    Ok(match kind {
+        "vehicle" => AnyEntity::VehicleID(VehicleID::from(k)),
+        "human" => AnyEntity::HumanID(HumanID::from(k)),
+        "train" => AnyEntity::TrainID(TrainID::from(k)),
+        "wagon" => AnyEntity::WagonID(WagonID::from(k)),
+        "freight_station" => AnyEntity::FreightStationID(FreightStationID::from(k)),
+        "company" => AnyEntity::CompanyID(CompanyID::from(k)),
+        _ => return Err(not_found(format!("entity kind {kind}"))),
+    })
+}
+
+/// Kind and id of an entity, as used in the urls
+fn entity_ref(e: AnyEntity) -> Value {
+    let (kind, id) = match e {
+        AnyEntity::VehicleID(id) => ("vehicle", fmt_key(id)),
+        AnyEntity::HumanID(id) => ("human", fmt_key(id)),
+        AnyEntity::TrainID(id) => ("train", fmt_key(id)),
+        AnyEntity::WagonID(id) => ("wagon", fmt_key(id)),
+        AnyEntity::FreightStationID(id) => ("freight_station", fmt_key(id)),
+        AnyEntity::CompanyID(id) => ("company", fmt_key(id)),
+    };
+    json!({ "kind": kind, "id": id })
+}
+
+fn soul_ref(soul: SoulID) -> Value {
+    entity_ref(soul.into())
+}
+
+fn entities(sim: &Simulation, kind: &str) -> QueryResult {
+    let w = sim.world();
+    let ids: Vec<String> = match kind {
+        "vehicle" => w.vehicles.keys().map(fmt_key).collect(),
+        "human" => w.humans.keys().map(fmt_key).collect(),
+        "train" => w.trains.keys().map(fmt_key).collect(),
+        "wagon" => w.wagons.keys().map(fmt_key).collect(),
+        "freight_station" => w.freight_stations.keys().map(fmt_key).collect(),
+        "company" => w.companies.keys().map(fmt_key).collect(),
+        _ => return Err(not_found(format!("entity kind {kind}"))),
+    };
+    Ok(json!(ids))
+}
+
+fn entity(sim: &Simulation, e: AnyEntity) -> QueryResult {
+    fn to_json(x: Option<&impl Serialize>) -> Option<Result<Value, (u16, String)>> {
+        x.map(|x| to_json_lossy(x).map_err(|e| (500, e)))
+    }
+
+    let w = sim.world();
+    let data = match e {
+        AnyEntity::VehicleID(id) => to_json(w.get(id)),
+        AnyEntity::HumanID(id) => to_json(w.get(id)),
+        AnyEntity::TrainID(id) => to_json(w.get(id)),
+        AnyEntity::WagonID(id) => to_json(w.get(id)),
+        AnyEntity::FreightStationID(id) => to_json(w.get(id)),
+        AnyEntity::CompanyID(id) => to_json(w.get(id)),
+    }
+    .ok_or_else(|| not_found(format!("{e:?}")))??;
+
+    let mut v = entity_ref(e);
+    v["pos"] = json!(w.pos_any(e).map(|p| [p.x, p.y, p.z]));
+    v["data"] = data;
+    Ok(v)
+}
+
+/// Serializes to json like serde_json, except that map keys that are not strings (like ids)
+/// are written as their json representation instead of failing
+fn to_json_lossy(x: &impl Serialize) -> Result<Value, String> {
+    fn convert(v: ciborium::Value) -> Value {
+        use ciborium::Value as C;
+        match v {
+            C::Integer(i) => {
+                let i = i128::from(i);
+                i64::try_from(i).map_or_else(|_| json!(i as f64), |i| json!(i))
+            }
+            C::Bytes(b) => json!(b),
+            C::Float(f) => json!(f),
+            C::Text(s) => Value::String(s),
+            C::Bool(b) => Value::Bool(b),
+            C::Null => Value::Null,
+            C::Tag(_, v) => convert(*v),
+            C::Array(a) => Value::Array(a.into_iter().map(convert).collect()),
+            C::Map(m) => Value::Object(
+                m.into_iter()
+                    .map(|(k, v)| {
+                        let k = match k {
+                            C::Text(s) => s,
+                            k => convert(k).to_string(),
+                        };
+                        (k, convert(v))
+                    })
+                    .collect(),
+            ),
+            _ => Value::Null,
+        }
+    }
+
+    ciborium::Value::serialized(x)
+        .map(convert)
+        .map_err(|e| e.to_string())
+}
+
+fn buildings(sim: &Simulation) -> Value {
+    let map = sim.map();
+    let list: Vec<Value> = map
+        .buildings()
+        .values()
+        .map(|b| json!({ "id": fmt_key(b.id), "kind": format!("{:?}", b.kind) }))
+        .collect();
+    json!(list)
+}
+
+fn building(sim: &Simulation, id: BuildingID) -> QueryResult {
+    let map = sim.map();
+    let b = map
+        .buildings()
+        .get(id)
+        .ok_or_else(|| not_found(format!("building {}", fmt_key(id))))?;
+    let infos = sim.read::<BuildingInfos>();
+    let info = infos.get(id);
+
+    Ok(json!({
+        "id": fmt_key(id),
+        "kind": format!("{:?}", b.kind),
+        "door_pos": [b.door_pos.x, b.door_pos.y, b.door_pos.z],
+        "corners": b.obb.corners.map(|c| [c.x, c.y]),
+        "height": b.height,
+        "zone_area": b.zone.as_ref().map(|z| z.area),
+        "owner": info.and_then(|x| x.owner).map(soul_ref),
+        "inside": info.map_or(vec![], |x| x.inside.iter().copied().map(soul_ref).collect()),
+        "flooded": info.is_some_and(|x| x.flooded),
+    }))
+}
+
+fn project(sim: &Simulation, query: &str) -> QueryResult {
+    let mut x = None;
+    let mut y = None;
+    for param in query.split('&') {
+        match param.split_once('=') {
+            Some(("x", v)) => x = v.parse::<f32>().ok(),
+            Some(("y", v)) => y = v.parse::<f32>().ok(),
+            _ => {}
+        }
+    }
+    let (Some(x), Some(y)) = (x, y) else {
+        return Err(bad_request("expected /project?x=<x>&y=<y>"));
+    };
+
+    let map = sim.map();
+    let z = map.environment.height(geom::vec2(x, y)).unwrap_or(0.0);
+    let proj = map.project(geom::vec3(x, y, z), 0.0, ProjectFilter::ALL);
+    let (kind, id) = match proj.kind {
+        ProjectKind::Inter(id) => ("intersection", Some(fmt_key(id))),
+        ProjectKind::Road(id) => ("road", Some(fmt_key(id))),
+        ProjectKind::Building(id) => ("building", Some(fmt_key(id))),
+        ProjectKind::Lot(id) => ("lot", Some(fmt_key(id))),
+        ProjectKind::Ground => ("ground", None),
+    };
+    Ok(json!({
+        "kind": kind,
+        "id": id,
+        "pos": [proj.pos.x, proj.pos.y, proj.pos.z],
+    }))
+}
+
+fn market(sim: &Simulation, only: Option<simulation::economy::ItemID>) -> Value {
+    let registry = sim.read::<ItemRegistry>();
+    let market = sim.read::<Market>();
+
+    let books: serde_json::Map<String, Value> = market
+        .iter()
+        .filter(|(&item, _)| only.is_none_or(|x| x == item))
+        .map(|(&item, m)| (registry[item].name.clone(), order_book(m)))
+        .collect();
+    Value::Object(books)
+}
+
+fn order_book(m: &SingleMarket) -> Value {
+    let buy: Vec<Value> = m
+        .buy_orders()
+        .iter()
+        .map(
+            |(&soul, o)| json!({ "soul": soul_ref(soul), "pos": [o.pos.x, o.pos.y], "qty": o.qty }),
+        )
+        .collect();
+    let sell: Vec<Value> = m
+        .sell_orders()
+        .iter()
+        .map(|(&soul, o)| {
+            json!({
+                "soul": soul_ref(soul),
+                "pos": [o.pos.x, o.pos.y],
+                "qty": o.qty,
+                "stock": o.stock,
+            })
+        })
+        .collect();
+    json!({
+        "ext_value": m.ext_value.cents() as f64 / 100.0,
+        "buy_orders": buy,
+        "sell_orders": sell,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{answer, parse_key, QueryServer};
+    use serde_json::json;
+    use std::io::{Read, Write};
+    use std::net::{Ipv4Addr, TcpListener, TcpStream};
+
+    #[test]
+    fn keys_are_parsed_like_they_are_logged() {
+        let k = parse_key("3v7").unwrap();
+        assert_eq!(format!("{k:?}"), "3v7");
+
+        for bad in ["", "3", "v7", "3v", "-1v2", "3v7v1", "av1"] {
+            assert_eq!(parse_key(bad).unwrap_err().0, 400, "{bad}");
+        }
+    }
+
+    #[test]
+    fn answers_on_an_empty_world() {
+        let sim = crate::test_world();
+
+        assert_eq!(answer(&sim, "/entities/vehicle").unwrap(), json!([]));
+        assert_eq!(answer(&sim, "/buildings").unwrap(), json!([]));
+        assert_eq!(
+            answer(&sim, "/project?x=10&y=20").unwrap()["kind"],
+            json!("ground")
+        );
+
+        let status = |path| answer(&sim, path).unwrap_err().0;
+        assert_eq!(status("/"), 404);
+        assert_eq!(status("/entities/dragon"), 404);
+        assert_eq!(status("/entity/human/1v1"), 404);
+        assert_eq!(status("/entity/human/oops"), 400);
+        assert_eq!(status("/building/1v1"), 404);
+        assert_eq!(status("/project?x=10"), 400);
+        assert_eq!(status("/market/unobtainium"), 404);
+    }
+
+    #[test]
+    fn serves_over_http() {
+        let sim = crate::test_world();
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = QueryServer::serve(listener);
+
+        let get = |request: &'static str| {
+            std::thread::spawn(move || {
+                let mut stream = TcpStream::connect(addr).unwrap();
+                stream.write_all(request.as_bytes()).unwrap();
+                let mut response = String::new();
+                stream.read_to_string(&mut response).unwrap();
+                response
+            })
+        };
+        let clients = [
+            get("GET /entities/human HTTP/1.1\r\n\r\n"),
+            get("GET /nothing HTTP/1.1\r\n\r\n"),
+            get("POST /buildings HTTP/1.1\r\n\r\n"),
+        ];
+
+        // the requests are answered by the "main loop" until every client got its response
+        while !clients.iter().all(|c| c.is_finished()) {
+            server.answer(&sim);
+            std::thread::yield_now();
+        }
+        let [ok, not_found, post] = clients.map(|c| c.join().unwrap());
+
+        assert!(ok.starts_with("HTTP/1.1 200 OK\r\n"), "{ok}");
+        assert!(ok.ends_with("\r\n\r\n[]"), "{ok}");
+        assert!(not_found.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        assert!(post.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"));
+    }
+}
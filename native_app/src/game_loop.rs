@@ -3,6 +3,7 @@ use std::sync::{Arc, RwLock};
 use std::time::{Duration, Instant};
 
 use crate::rendering::immediate::{ImmediateDraw, ImmediateSound};
+use common::mods::ModSet;
 use common::History;
 use engine::{Context, FrameContext, Tesselator};
 use geom::{vec2, vec3, Camera, LinearColor};
@@ -10,6 +11,7 @@ use simulation::utils::time::GameTime;
 use simulation::Simulation;
 
 use crate::audio::GameAudio;
+use crate::gui::overlays::Overlay;
 use crate::gui::terraforming::TerraformingResource;
 use crate::gui::windows::debug::DebugObjs;
 use crate::gui::windows::settings::{manage_settings, Settings};
@@ -38,6 +40,15 @@ pub struct State {
 
 impl engine::framework::State for State {
     fn new(ctx: &mut Context) -> Self {
+        match ModSet::load(common::mods::MODS_DIR) {
+            Ok(mods) => common::mods::set_active(mods),
+            Err(errors) => {
+                for e in errors {
+                    log::error!("could not load mods, starting without them: {}", e);
+                }
+            }
+        }
+
         let camera = OrbitCamera::load((ctx.gfx.size.0, ctx.gfx.size.1));
 
         Gui::set_style(&ctx.egui.egui);
@@ -313,6 +324,13 @@ impl State {
             }
         }
 
+        {
+            profiling::scope!("overlay");
+            let sim = self.sim.read().unwrap();
+            let overlay = *self.uiw.read::<Overlay>();
+            overlay.draw(&mut self.immtess, &sim, &self.uiw);
+        }
+
         {
             let sim = self.sim.read().unwrap();
             let immediate = &mut *self.uiw.write::<ImmediateDraw>();
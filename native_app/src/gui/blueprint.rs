@@ -0,0 +1,114 @@
+use super::Tool;
+use crate::gui::{ErrorTooltip, PotentialCommands};
+use crate::inputmap::{InputAction, InputMap};
+use crate::rendering::immediate::{ImmediateDraw, ImmediateSound};
+use crate::uiworld::UiWorld;
+use common::AudioKind;
+use geom::{Degrees, Vec2, Vec3, AABB};
+use simulation::blueprint::Blueprint;
+use simulation::souls::goods_company::GoodsCompanyRegistry;
+use simulation::world_command::WorldCommand;
+use simulation::Simulation;
+use std::borrow::Cow;
+
+#[derive(Default)]
+pub struct BlueprintResource {
+    /// The blueprint being pasted, None while selecting the area to copy
+    pub blueprint: Option<Blueprint>,
+    pub rotation: Degrees,
+    /// Name the blueprint is saved under
+    pub name: String,
+    /// Names of the saved blueprints, None when they need to be listed again
+    pub saved: Option<Vec<String>>,
+    select_start: Option<Vec2>,
+}
+
+/// Blueprint tool
+/// Drag to copy the roads and buildings of an area, then click to paste them elsewhere
+pub fn blueprint(sim: &Simulation, uiworld: &mut UiWorld) {
+    profiling::scope!("gui::blueprint");
+    let mut state = uiworld.write::<BlueprintResource>();
+    let tool = *uiworld.read::<Tool>();
+
+    if !matches!(tool, Tool::Blueprint) {
+        state.select_start = None;
+        return;
+    }
+
+    let inp = uiworld.read::<InputMap>();
+    let mut draw = uiworld.write::<ImmediateDraw>();
+    let map = sim.map();
+    let registry = sim.read::<GoodsCompanyRegistry>();
+
+    if inp.act.contains(&InputAction::Rotate) {
+        state.rotation += Degrees(inp.wheel);
+        state.rotation.normalize();
+    }
+
+    let mpos = unwrap_ret!(inp.unprojected);
+
+    let Some(ref bp) = state.blueprint else {
+        if inp.just_act.contains(&InputAction::Select) {
+            state.select_start = Some(mpos.xy());
+        }
+        let Some(start) = state.select_start else {
+            draw.circle(mpos.up(0.5), 2.0)
+                .color(simulation::config().gui_primary);
+            return;
+        };
+
+        let area = AABB::new(start.min(mpos.xy()), start.max(mpos.xy()));
+        draw.aabb(area, mpos.z + 0.5)
+            .color(simulation::config().gui_primary.a(0.3));
+
+        if !inp.act.contains(&InputAction::Select) {
+            state.select_start = None;
+            let bp = Blueprint::capture(&map, &registry, area);
+            if bp.is_empty() {
+                *uiworld.write::<ErrorTooltip>() =
+                    ErrorTooltip::new(Cow::Borrowed("Nothing to copy"));
+                return;
+            }
+            state.blueprint = Some(bp);
+            state.rotation = Degrees(0.0);
+        }
+        return;
+    };
+
+    let cmds = bp.commands(&map, &registry, mpos.xy(), state.rotation.vec2());
+
+    let col = simulation::config().gui_primary;
+    for cmd in &cmds {
+        match cmd {
+            WorldCommand::MapMakeMultipleConnections(projects, links) => {
+                for (src, dst, interpoint, pat) in links {
+                    let (from, to) = (projects[*src].pos, projects[*dst].pos);
+                    let points: Vec<Vec3> = match interpoint {
+                        Some(elbow) => (0..=16)
+                            .map(|i| {
+                                let t = i as f32 / 16.0;
+                                let p = from.xy().lerp(*elbow, t).lerp(elbow.lerp(to.xy(), t), t);
+                                p.z(from.z + (to.z - from.z) * t + 0.5)
+                            })
+                            .collect(),
+                        None => vec![from.up(0.5), to.up(0.5)],
+                    };
+                    draw.polyline(points, pat.width(), false).color(col.a(0.5));
+                }
+            }
+            WorldCommand::MapBuildSpecialBuilding { pos, .. } => {
+                draw.obb(*pos, mpos.z + 0.5).color(col.a(0.5));
+            }
+            _ => {}
+        }
+    }
+
+    if inp.just_act.contains(&InputAction::Select) {
+        uiworld.commands().extend(cmds);
+        uiworld
+            .write::<ImmediateSound>()
+            .play("road_lay", AudioKind::Ui);
+    } else {
+        uiworld.write::<PotentialCommands>().0 = cmds;
+    }
+}
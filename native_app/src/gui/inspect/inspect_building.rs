@@ -1,11 +1,12 @@
 use crate::uiworld::UiWorld;
-use egui::{Context, Ui, Widget};
-use simulation::economy::{ItemRegistry, Market};
+use egui::{Color32, Context, Ui, Widget};
+use simulation::economy::{ItemRegistry, Market, PowerGrid};
 use simulation::world_command::WorldCommand;
 use simulation::{Simulation, SoulID};
 
 use crate::gui::inspect::entity_link;
 use crate::gui::item_icon;
+use crate::gui::windows::economy::format_power;
 use egui_inspect::{Inspect, InspectArgs, InspectVec2Rotation};
 use simulation::map::{Building, BuildingID, BuildingKind, Zone, MAX_ZONE_AREA};
 use simulation::map_dynamic::BuildingInfos;
@@ -151,7 +152,8 @@ fn render_goodscompany(ui: &mut Ui, uiworld: &mut UiWorld, sim: &Simulation, b:
             entity_link(uiworld, sim, ui, driver);
         });
     }
-    let productivity = goods.productivity(workers.0.len(), b.zone.as_ref());
+    let grid = sim.read::<PowerGrid>();
+    let productivity = goods.productivity(workers.0.len(), b.zone.as_ref(), &grid);
     let productivity = (productivity * 100.0).round();
     if productivity < 100.0 {
         egui::ProgressBar::new(productivity)
@@ -159,6 +161,22 @@ fn render_goodscompany(ui: &mut Ui, uiworld: &mut UiWorld, sim: &Simulation, b:
             .desired_width(200.0)
             .ui(ui);
     }
+    if goods.power_production > 0 {
+        ui.label(format!(
+            "Delivers up to {} to the grid",
+            format_power(goods.power_production as i64)
+        ));
+    }
+    if goods.power_consumption > 0 {
+        ui.label(format!(
+            "Draws {} from the grid",
+            format_power(goods.power_consumption as i64)
+        ));
+        if grid.is_brownout() {
+            ui.colored_label(Color32::YELLOW, "Brownout: not enough electricity");
+        }
+    }
+    drop(grid);
 
     render_recipe(ui, uiworld, sim, &goods.recipe);
 
@@ -13,12 +13,15 @@ use simulation::world_command::WorldCommand;
 use simulation::{AnyEntity, Simulation};
 
 pub mod addtrain;
+pub mod blueprint;
 pub mod bulldozer;
 pub mod chat;
 pub mod follow;
 pub mod inspect;
 pub mod inspected_aura;
 pub mod lotbrush;
+pub mod notifications;
+pub mod overlays;
 pub mod roadbuild;
 pub mod roadeditor;
 pub mod selectable;
@@ -40,6 +43,7 @@ pub fn run_ui_systems(sim: &Simulation, uiworld: &mut UiWorld) {
     roadeditor::roadeditor(sim, uiworld);
     specialbuilding::specialbuilding(sim, uiworld);
     addtrain::addtrain(sim, uiworld);
+    blueprint::blueprint(sim, uiworld);
     zoneedit::zoneedit(sim, uiworld);
     terraforming::terraforming(sim, uiworld);
 
@@ -117,6 +121,7 @@ pub enum Tool {
     SpecialBuilding,
     Train,
     Terraforming,
+    Blueprint,
 }
 
 impl Tool {
@@ -0,0 +1,102 @@
+use egui::{Align2, Color32, RichText, Sense};
+
+use simulation::economy::ItemRegistry;
+use simulation::events::{Event, EventKind, Events};
+use simulation::map::{BuildingID, BuildingKind};
+use simulation::souls::goods_company::GoodsCompanyRegistry;
+use simulation::utils::time::{GameInstant, GameTime, SECONDS_PER_REALTIME_SECOND};
+use simulation::Simulation;
+
+use crate::gui::InspectedBuilding;
+use crate::uiworld::UiWorld;
+
+#[derive(Default)]
+pub struct NotificationsState {
+    /// Events emitted before this are not shown anymore
+    cleared: Option<GameInstant>,
+}
+
+/// Shows the recent simulation events, clicking one moves the camera to its source
+pub fn notifications(ui: &egui::Context, uiw: &mut UiWorld, sim: &Simulation) {
+    const MAX_SHOWN: usize = 8;
+    let time = sim.read::<GameTime>();
+    let mut since = GameInstant {
+        timestamp: time.timestamp - 120.0 * SECONDS_PER_REALTIME_SECOND as f64,
+    };
+    if let Some(cleared) = uiw.read::<NotificationsState>().cleared {
+        if cleared > since {
+            since = cleared;
+        }
+    }
+
+    let events = sim.read::<Events>();
+    let shown: Vec<&Event> = events.since(since).take(MAX_SHOWN).collect();
+    if shown.is_empty() {
+        return;
+    }
+
+    egui::Window::new("Notifications")
+        .title_bar(false)
+        .resizable(false)
+        .auto_sized()
+        .anchor(Align2::RIGHT_TOP, (-5.0, 30.0))
+        .show(ui, |ui| {
+            for event in shown {
+                let mut text = describe(sim, &event.kind);
+                if event.count > 1 {
+                    text += &format!(" (x{})", event.count);
+                }
+                let color = match event.kind {
+                    EventKind::NegativeBudget => Color32::from_rgb(255, 100, 100),
+                    _ => Color32::from_rgb(255, 190, 90),
+                };
+
+                let mut resp = ui
+                    .add(egui::Label::new(RichText::new(text).color(color)).sense(Sense::click()));
+                let Some(pos) = event.pos else {
+                    continue;
+                };
+                resp = resp.on_hover_text("Click to go there");
+                if resp.clicked() {
+                    uiw.camera_mut().targetpos = pos;
+                    if let Some(b) = source_building(&event.kind) {
+                        uiw.write::<InspectedBuilding>().e = Some(b);
+                    }
+                }
+            }
+
+            if ui.small_button("Clear").clicked() {
+                uiw.write::<NotificationsState>().cleared = Some(time.instant());
+            }
+        });
+}
+
+fn source_building(kind: &EventKind) -> Option<BuildingID> {
+    match *kind {
+        EventKind::MissingInput { building, .. } | EventKind::NoFreightTrain(building) => {
+            Some(building)
+        }
+        EventKind::Gridlock(_) | EventKind::NegativeBudget => None,
+    }
+}
+
+fn describe(sim: &Simulation, kind: &EventKind) -> String {
+    match *kind {
+        EventKind::Gridlock(_) => "Gridlock at an intersection".to_string(),
+        EventKind::MissingInput { building, item, .. } => {
+            let map = sim.map();
+            let gregistry = sim.read::<GoodsCompanyRegistry>();
+            let name = match map.buildings().get(building).map(|b| b.kind) {
+                Some(BuildingKind::GoodsCompany(id)) => gregistry.descriptions[id].name.as_str(),
+                _ => "A company",
+            };
+            let registry = sim.read::<ItemRegistry>();
+            let item = registry.get(item).map_or("an input", |i| i.label.as_str());
+            format!("{name} lacks {item}")
+        }
+        EventKind::NoFreightTrain(_) => {
+            "A rail freight station has goods waiting but no freight train".to_string()
+        }
+        EventKind::NegativeBudget => "The budget is negative".to_string(),
+    }
+}
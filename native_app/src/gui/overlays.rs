@@ -0,0 +1,275 @@
+use crate::uiworld::UiWorld;
+use engine::Tesselator;
+use geom::{Camera, LinearColor, Vec2, Vec3};
+use simulation::map::{BuildingKind, LaneID, LotKind, ProjectFilter, ProjectKind, TraverseKind};
+use simulation::map_dynamic::BuildingInfos;
+use simulation::utils::time::GameTime;
+use simulation::Simulation;
+use std::collections::BTreeMap;
+
+/// Only the objects this close to the camera are drawn
+const OVERLAY_RADIUS: f32 = 3000.0;
+/// Road length taken by one vehicle in a jammed lane
+const VEHICLE_SPACING: f32 = 8.0;
+/// Souls inside a building for it to be shown as full
+const FULL_OCCUPANCY: f32 = 20.0;
+/// Companies closer than this to a rail freight station are well served
+const FREIGHT_NEAR: f32 = 1000.0;
+/// Companies further than this from a rail freight station are not served
+const FREIGHT_FAR: f32 = 4000.0;
+
+/// Player-facing overlay drawn over the map, chosen from the menu bar
+#[derive(Default, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Overlay {
+    #[default]
+    None,
+    Traffic,
+    Occupancy,
+    Unemployment,
+    FoodAccess,
+    FreightCoverage,
+    Zoning,
+}
+
+impl Overlay {
+    pub const ALL: [Overlay; 7] = [
+        Overlay::None,
+        Overlay::Traffic,
+        Overlay::Occupancy,
+        Overlay::Unemployment,
+        Overlay::FoodAccess,
+        Overlay::FreightCoverage,
+        Overlay::Zoning,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Overlay::None => "None",
+            Overlay::Traffic => "Traffic",
+            Overlay::Occupancy => "Occupancy",
+            Overlay::Unemployment => "Unemployment",
+            Overlay::FoodAccess => "Food access",
+            Overlay::FreightCoverage => "Freight coverage",
+            Overlay::Zoning => "Zoning",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Overlay::None => "No overlay",
+            Overlay::Traffic => "Vehicles per lane, from free (green) to jammed (red)",
+            Overlay::Occupancy => "People inside each building, from empty (green) to full (red)",
+            Overlay::Unemployment => "Residents of each house without a job, from none (green) to all (red)",
+            Overlay::FoodAccess => "Residents of each house that did not eat today, from none (green) to all (red)",
+            Overlay::FreightCoverage => "Distance from the companies to the nearest rail freight station, the external market is reached from there",
+            Overlay::Zoning => "Residential lots in green, unassigned lots in gray",
+        }
+    }
+
+    pub fn draw(self, tess: &mut Tesselator<true>, sim: &Simulation, uiworld: &UiWorld) {
+        let center = uiworld.read::<Camera>().pos.xy();
+        match self {
+            Overlay::None => {}
+            Overlay::Traffic => {
+                draw_traffic(tess, sim, center, &mut uiworld.write::<TrafficCounts>())
+            }
+            Overlay::Occupancy => draw_occupancy(tess, sim, center),
+            Overlay::Unemployment => draw_residents(tess, sim, center, |h, _| h.work.is_none()),
+            Overlay::FoodAccess => draw_residents(tess, sim, center, |h, time| {
+                h.food.last_ate.elapsed(time) > GameTime::DAY as f64
+            }),
+            Overlay::FreightCoverage => draw_freight_coverage(tess, sim, center),
+            Overlay::Zoning => draw_zoning(tess, sim, center),
+        }
+    }
+}
+
+/// Green for 0, yellow for 0.5 and red for 1
+fn heat(t: f32) -> LinearColor {
+    let t = t.clamp(0.0, 1.0);
+    LinearColor::new((2.0 * t).min(1.0), (2.0 - 2.0 * t).min(1.0), 0.0, 0.6)
+}
+
+/// Vehicles on each lane, counted once per tick for the traffic overlay
+#[derive(Default)]
+pub struct TrafficCounts {
+    counted_at: Option<u64>,
+    vehicles: BTreeMap<LaneID, u32>,
+}
+
+impl TrafficCounts {
+    fn update(&mut self, sim: &Simulation) {
+        let tick = sim.get_tick();
+        if self.counted_at == Some(tick) {
+            return;
+        }
+        self.counted_at = Some(tick);
+        self.vehicles.clear();
+        for v in sim.world().vehicles.values() {
+            if let Some(TraverseKind::Lane(id)) = v.it.get_travers().map(|t| t.kind) {
+                *self.vehicles.entry(id).or_default() += 1;
+            }
+        }
+    }
+}
+
+fn draw_traffic(
+    tess: &mut Tesselator<true>,
+    sim: &Simulation,
+    center: Vec2,
+    counts: &mut TrafficCounts,
+) {
+    counts.update(sim);
+    let map = sim.map();
+
+    for kind in map
+        .spatial_map()
+        .query_around(center, OVERLAY_RADIUS, ProjectFilter::ROAD)
+    {
+        let ProjectKind::Road(id) = kind else {
+            continue;
+        };
+        let Some(road) = map.roads().get(id) else {
+            continue;
+        };
+        for (lane_id, lane_kind) in road.lanes_iter() {
+            if !lane_kind.vehicles() {
+                continue;
+            }
+            let Some(lane) = map.lanes().get(lane_id) else {
+                continue;
+            };
+            let n_vehicles = counts.vehicles.get(&lane_id).copied().unwrap_or(0);
+            let length = lane.points.length().max(VEHICLE_SPACING);
+            tess.set_color(heat(n_vehicles as f32 * VEHICLE_SPACING / length));
+            tess.draw_polyline(lane.points.as_slice(), lane_kind.width() * 0.5, false);
+        }
+    }
+}
+
+fn draw_occupancy(tess: &mut Tesselator<true>, sim: &Simulation, center: Vec2) {
+    let map = sim.map();
+    let binfos = sim.read::<BuildingInfos>();
+
+    for kind in map
+        .spatial_map()
+        .query_around(center, OVERLAY_RADIUS, ProjectFilter::BUILDING)
+    {
+        let ProjectKind::Building(id) = kind else {
+            continue;
+        };
+        let (Some(b), Some(info)) = (map.buildings().get(id), binfos.get(id)) else {
+            continue;
+        };
+        tess.set_color(heat(info.inside.len() as f32 / FULL_OCCUPANCY));
+        tess.draw_filled_polygon(&b.obb.corners, b.door_pos.z + 0.5);
+    }
+}
+
+/// Colors each house by the share of its residents matching `bad`
+fn draw_residents(
+    tess: &mut Tesselator<true>,
+    sim: &Simulation,
+    center: Vec2,
+    bad: impl Fn(&simulation::HumanEnt, &GameTime) -> bool,
+) {
+    let map = sim.map();
+    let time = sim.read::<GameTime>();
+
+    // (residents, matching)
+    let mut houses = BTreeMap::new();
+    for h in sim.world().humans.values() {
+        let v: &mut (u32, u32) = houses.entry(h.home.house).or_default();
+        v.0 += 1;
+        v.1 += bad(h, &time) as u32;
+    }
+
+    for (id, (residents, matching)) in houses {
+        let Some(b) = map.buildings().get(id) else {
+            continue;
+        };
+        if b.door_pos.xy().distance(center) > OVERLAY_RADIUS {
+            continue;
+        }
+        tess.set_color(heat(matching as f32 / residents as f32));
+        tess.draw_filled_polygon(&b.obb.corners, b.door_pos.z + 0.5);
+    }
+}
+
+fn draw_freight_coverage(tess: &mut Tesselator<true>, sim: &Simulation, center: Vec2) {
+    let map = sim.map();
+    let buildings = map.buildings();
+
+    let stations: Vec<Vec3> = buildings
+        .values()
+        .filter(|b| matches!(b.kind, BuildingKind::RailFreightStation))
+        .map(|b| b.door_pos)
+        .collect();
+
+    for b in buildings.values() {
+        let BuildingKind::GoodsCompany(_) = b.kind else {
+            continue;
+        };
+        let pos = b.door_pos.xy();
+        if pos.distance(center) > OVERLAY_RADIUS {
+            continue;
+        }
+        let nearest = stations
+            .iter()
+            .map(|s| s.xy().distance(pos))
+            .fold(f32::INFINITY, f32::min);
+        tess.set_color(heat(
+            (nearest - FREIGHT_NEAR) / (FREIGHT_FAR - FREIGHT_NEAR),
+        ));
+        tess.draw_filled_polygon(&b.obb.corners, b.door_pos.z + 0.5);
+    }
+
+    for &station in &stations {
+        tess.set_color(heat(0.0));
+        tess.draw_stroke_circle(station.up(0.5), FREIGHT_NEAR, 5.0);
+        tess.set_color(heat(1.0));
+        tess.draw_stroke_circle(station.up(0.5), FREIGHT_FAR, 5.0);
+    }
+}
+
+fn draw_zoning(tess: &mut Tesselator<true>, sim: &Simulation, center: Vec2) {
+    let map = sim.map();
+
+    for kind in map
+        .spatial_map()
+        .query_around(center, OVERLAY_RADIUS, ProjectFilter::LOT)
+    {
+        let ProjectKind::Lot(id) = kind else {
+            continue;
+        };
+        let Some(lot) = map.lots().get(id) else {
+            continue;
+        };
+        tess.set_color(match lot.kind {
+            LotKind::Residential => LinearColor::new(0.2, 0.8, 0.2, 0.5),
+            LotKind::Unassigned => LinearColor::new(0.5, 0.5, 0.5, 0.5),
+        });
+        tess.draw_filled_polygon(&lot.shape.corners, lot.height + 0.3);
+    }
+}
+
+/// Overlay selection in the menu bar
+pub fn overlay_menu(ui: &mut egui::Ui, uiworld: &UiWorld) {
+    let mut overlay = uiworld.write::<Overlay>();
+    let title = match *overlay {
+        Overlay::None => "Overlay".to_string(),
+        o => format!("Overlay: {}", o.name()),
+    };
+    ui.menu_button(title, |ui| {
+        for o in Overlay::ALL {
+            if ui
+                .selectable_label(*overlay == o, o.name())
+                .on_hover_text(o.description())
+                .clicked()
+            {
+                *overlay = o;
+                ui.close_menu();
+            }
+        }
+    });
+}
@@ -1,15 +1,16 @@
-use crate::gui::{PotentialCommands, Tool};
+use crate::gui::{ErrorTooltip, PotentialCommands, Tool};
 use crate::inputmap::{InputAction, InputMap};
 use crate::rendering::immediate::{ImmediateDraw, ImmediateSound};
 use crate::uiworld::UiWorld;
 use common::AudioKind;
 use geom::{BoldLine, BoldSpline, Camera, PolyLine, ShapeEnum, Spline};
-use geom::{PolyLine3, Spline3, Vec2, Vec3};
+use geom::{PolyLine3, Radians, Spline3, Vec2, Vec3};
 use simulation::map::{
     Intersection, LanePatternBuilder, Map, MapProject, ProjectFilter, ProjectKind, PylonPosition,
 };
 use simulation::world_command::{WorldCommand, WorldCommands};
 use simulation::Simulation;
+use std::borrow::Cow;
 use BuildState::{Hover, Interpolation, Start};
 use ProjectKind::{Building, Ground, Inter, Road};
 
@@ -21,12 +22,59 @@ pub enum BuildState {
     Interpolation(Vec2, MapProject),
 }
 
-#[derive(Default)]
+/// Angle step used when snapping relative to the connected roads
+const SNAP_ANGLE: f32 = 15.0 * std::f32::consts::PI / 180.0;
+/// Arcs sweeping more than twice this angle cannot be built in one piece
+const MAX_ARC_HALF_ANGLE: f32 = 80.0 * std::f32::consts::PI / 180.0;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum RoadBuildMode {
+    /// Straight or curved depending on the tool
+    #[default]
+    Free,
+    /// Circular arc continuing the road it starts from
+    Arc,
+    /// Copy of the hovered road, `parallel_offset` meters away
+    Parallel,
+}
+
+/// Measures of the road being built, shown next to the cursor
+#[derive(Copy, Clone, Debug)]
+pub struct RoadReadout {
+    pub length: f32,
+    pub grade: f32,
+    pub radius: Option<f32>,
+}
+
 pub struct RoadBuildResource {
     pub build_state: BuildState,
     pub pattern_builder: LanePatternBuilder,
     pub snap_to_grid: bool,
     pub height_offset: f32,
+    pub mode: RoadBuildMode,
+    /// Snap the direction of new roads to multiples of 15° relative to the connected roads
+    pub snap_angle: bool,
+    /// Radius of the arcs, None to follow the mouse
+    pub fixed_radius: Option<f32>,
+    /// Distance between the centers of the hovered road and its parallel, the side follows the mouse
+    pub parallel_offset: f32,
+    pub readout: Option<RoadReadout>,
+}
+
+impl Default for RoadBuildResource {
+    fn default() -> Self {
+        Self {
+            build_state: Hover,
+            pattern_builder: LanePatternBuilder::default(),
+            snap_to_grid: false,
+            height_offset: 0.0,
+            mode: RoadBuildMode::Free,
+            snap_angle: false,
+            fixed_radius: None,
+            parallel_offset: 30.0,
+            readout: None,
+        }
+    }
 }
 
 /// Road building tool
@@ -43,6 +91,7 @@ pub fn roadbuild(sim: &Simulation, uiworld: &mut UiWorld) {
     let commands: &mut WorldCommands = &mut uiworld.commands();
     let cam = &*uiworld.read::<Camera>();
 
+    state.readout = None;
     if !tool.is_roadbuild() {
         state.build_state = Hover;
         state.height_offset = 0.0;
@@ -54,7 +103,7 @@ pub fn roadbuild(sim: &Simulation, uiworld: &mut UiWorld) {
     // Prepare mousepos depending on snap to grid
     let unproj = unwrap_ret!(inp.unprojected);
     let grid_size = 20.0;
-    let mousepos = if state.snap_to_grid {
+    let mut mousepos = if state.snap_to_grid {
         let v = unproj.xy().snap(grid_size, grid_size);
         v.z(unwrap_ret!(map.environment.height(v)) + 0.3 + state.height_offset)
     } else {
@@ -103,6 +152,10 @@ pub fn roadbuild(sim: &Simulation, uiworld: &mut UiWorld) {
         }
     }
 
+    if state.mode == RoadBuildMode::Parallel {
+        state.build_state = Hover;
+    }
+
     if inp.just_act.contains(&InputAction::Close) && !matches!(state.build_state, Hover) {
         inp.just_act.remove(&InputAction::Close);
         state.build_state = Hover;
@@ -118,6 +171,18 @@ pub fn roadbuild(sim: &Simulation, uiworld: &mut UiWorld) {
         state.height_offset = state.height_offset.max(0.0);
     }
 
+    if let Start(selected_proj) = state.build_state {
+        if state.snap_angle && !nosnapping {
+            let start = selected_proj.pos.xy();
+            let d = mousepos.xy() - start;
+            if let Some(reference) = reference_dir(map, selected_proj, mousepos.xy()) {
+                let angle = (reference.angle(d) / SNAP_ANGLE).round() * SNAP_ANGLE;
+                let v = start + reference.rotated_by_angle(Radians(angle)) * d.mag();
+                mousepos = v.z(unwrap_ret!(map.environment.height(v)) + 0.3 + state.height_offset);
+            }
+        }
+    }
+
     let mut cur_proj = map.project(
         mousepos,
         (log_camheight * 5.0).clamp(1.0, 10.0),
@@ -156,66 +221,170 @@ pub fn roadbuild(sim: &Simulation, uiworld: &mut UiWorld) {
     }
 
     let is_rail = state.pattern_builder.rail;
-
-    let is_valid = match (state.build_state, cur_proj.kind) {
-        (Hover, Building(_)) => false,
-        (Start(selected_proj), _) => {
-            let sp = BoldLine::new(
-                PolyLine::new(vec![selected_proj.pos.xy(), cur_proj.pos.xy()]),
-                patwidth * 0.5,
-            );
-
-            compatible(map, cur_proj, selected_proj)
-                && check_angle(map, selected_proj, cur_proj.pos.xy(), is_rail)
-                && check_angle(map, cur_proj, selected_proj.pos.xy(), is_rail)
-                && !check_intersect(
-                    map,
-                    &ShapeEnum::BoldLine(sp),
-                    (selected_proj.pos.z + cur_proj.pos.z) / 2.0,
-                    cur_proj.kind,
-                    selected_proj.kind,
-                )
+    let pattern = state.pattern_builder.build();
+
+    // The arc and parallel modes pick the road shape themselves, they are then built like curves
+    let mut build_state = state.build_state;
+    let mut radius = None;
+    match (state.mode, build_state) {
+        (RoadBuildMode::Arc, Start(selected_proj)) => {
+            if let Some(tangent) = reference_dir(map, selected_proj, cur_proj.pos.xy()) {
+                let start = selected_proj.pos.xy();
+                if let Some(r) = state.fixed_radius {
+                    let right = tangent.perpendicular();
+                    let side = (mousepos.xy() - start).dot(right).signum();
+                    let center = start + right * side * r;
+                    let end =
+                        center + (mousepos.xy() - center).try_normalize().unwrap_or(-right) * r;
+                    cur_proj = map.project(
+                        end.z(unwrap_ret!(map.environment.height(end)) + 0.3 + state.height_offset),
+                        1.0,
+                        ProjectFilter::INTER | ProjectFilter::ROAD,
+                    );
+                }
+                let chord = cur_proj.pos.xy() - start;
+                let half_angle = tangent.angle(chord).abs();
+                if half_angle > 0.01 && half_angle < MAX_ARC_HALF_ANGLE {
+                    let elbow = start + tangent * (chord.mag() * 0.5 / half_angle.cos());
+                    radius = Some(chord.mag2() / (2.0 * tangent.cross(chord).abs()));
+                    build_state = Interpolation(elbow, selected_proj);
+                }
+            }
+        }
+        (RoadBuildMode::Parallel, Hover) => {
+            if let Road(r_id) = cur_proj.kind {
+                let r = &map.roads()[r_id];
+                let (proj, _, dir) = r.points().project_segment_dir(mousepos);
+                let side = (mousepos.xy() - proj.xy()).dot(dir.xy().perpendicular());
+                let (from, to, elbow) = r.parallel(state.parallel_offset.copysign(side));
+                // connect to the roads crossing the parallel, but not to the copied road
+                let snap = |p: Vec3| {
+                    let proj = map.project(p, patwidth, ProjectFilter::INTER | ProjectFilter::ROAD);
+                    match proj.kind {
+                        Road(id) if id != r_id => proj,
+                        Inter(id) if id != r.src && id != r.dst => proj,
+                        _ => MapProject::ground(p),
+                    }
+                };
+                build_state = match elbow {
+                    Some(elbow) => Interpolation(elbow, snap(from)),
+                    None => Start(snap(from)),
+                };
+                cur_proj = snap(to);
+            }
         }
-        (Interpolation(interpoint, selected_proj), _) => {
-            let sp = Spline {
+        _ => {}
+    }
+
+    let max_grade = pattern.max_grade();
+    let readout = match build_state {
+        Hover => None,
+        Start(selected_proj) => Some((
+            selected_proj,
+            None,
+            selected_proj.pos.xy().distance(cur_proj.pos.xy()),
+        )),
+        Interpolation(interpoint, selected_proj) => Some((
+            selected_proj,
+            Some(interpoint),
+            Spline {
                 from: selected_proj.pos.xy(),
                 to: cur_proj.pos.xy(),
                 from_derivative: (interpoint - selected_proj.pos.xy())
                     * std::f32::consts::FRAC_1_SQRT_2,
                 to_derivative: (cur_proj.pos.xy() - interpoint) * std::f32::consts::FRAC_1_SQRT_2,
-            };
+            }
+            .length(1.0),
+        )),
+    }
+    .map(|(from, interpoint, length)| RoadReadout {
+        length,
+        grade: Map::road_grade(from.pos, cur_proj.pos, interpoint),
+        radius,
+    });
+    state.readout = readout;
+
+    // the map refuses the roads steeper than this, show why before sending the command
+    let too_steep = readout.is_some_and(|r| r.grade > max_grade);
+    if too_steep {
+        *uiworld.write::<ErrorTooltip>() = ErrorTooltip::new(Cow::Owned(format!(
+            "Too steep, at most {:.0}% for {}",
+            max_grade * 100.0,
+            if is_rail { "rails" } else { "roads" }
+        )));
+    }
 
-            compatible(map, cur_proj, selected_proj)
-                && check_angle(map, selected_proj, interpoint, is_rail)
-                && check_angle(map, cur_proj, interpoint, is_rail)
-                && !sp.is_steep(state.pattern_builder.width())
-                && !check_intersect(
-                    map,
-                    &ShapeEnum::BoldSpline(BoldSpline::new(sp, patwidth * 0.5)),
-                    (selected_proj.pos.z + cur_proj.pos.z) / 2.0,
-                    selected_proj.kind,
-                    cur_proj.kind,
-                )
-        }
-        _ => true,
-    };
+    let is_valid = !too_steep
+        && match (build_state, cur_proj.kind) {
+            (Hover, Building(_)) => false,
+            (Start(selected_proj), _) => {
+                let sp = BoldLine::new(
+                    PolyLine::new(vec![selected_proj.pos.xy(), cur_proj.pos.xy()]),
+                    patwidth * 0.5,
+                );
+
+                compatible(map, cur_proj, selected_proj)
+                    && check_angle(map, selected_proj, cur_proj.pos.xy(), is_rail)
+                    && check_angle(map, cur_proj, selected_proj.pos.xy(), is_rail)
+                    && !map.road_needs_bridge(selected_proj.pos, cur_proj.pos, None)
+                    && !check_intersect(
+                        map,
+                        &ShapeEnum::BoldLine(sp),
+                        (selected_proj.pos.z + cur_proj.pos.z) / 2.0,
+                        cur_proj.kind,
+                        selected_proj.kind,
+                    )
+            }
+            (Interpolation(interpoint, selected_proj), _) => {
+                let sp = Spline {
+                    from: selected_proj.pos.xy(),
+                    to: cur_proj.pos.xy(),
+                    from_derivative: (interpoint - selected_proj.pos.xy())
+                        * std::f32::consts::FRAC_1_SQRT_2,
+                    to_derivative: (cur_proj.pos.xy() - interpoint)
+                        * std::f32::consts::FRAC_1_SQRT_2,
+                };
 
-    state.update_drawing(map, immdraw, cur_proj, patwidth, tool, is_valid);
+                compatible(map, cur_proj, selected_proj)
+                    && check_angle(map, selected_proj, interpoint, is_rail)
+                    && check_angle(map, cur_proj, interpoint, is_rail)
+                    && !sp.is_steep(state.pattern_builder.width())
+                    && !map.road_needs_bridge(selected_proj.pos, cur_proj.pos, Some(interpoint))
+                    && !check_intersect(
+                        map,
+                        &ShapeEnum::BoldSpline(BoldSpline::new(sp, patwidth * 0.5)),
+                        (selected_proj.pos.z + cur_proj.pos.z) / 2.0,
+                        selected_proj.kind,
+                        cur_proj.kind,
+                    )
+            }
+            _ => true,
+        };
+
+    update_drawing(
+        map,
+        immdraw,
+        build_state,
+        cur_proj,
+        patwidth,
+        tool,
+        is_valid,
+    );
     potential_command.0.clear();
-    match state.build_state {
+    match build_state {
         Hover => {}
         Start(selected_proj) => potential_command.set(WorldCommand::MapMakeConnection {
             from: selected_proj,
             to: cur_proj,
             inter: None,
-            pat: state.pattern_builder.build(),
+            pat: pattern,
         }),
         Interpolation(interpoint, selected_proj) => {
             potential_command.set(WorldCommand::MapMakeConnection {
                 from: selected_proj,
                 to: cur_proj,
                 inter: Some(interpoint),
-                pat: state.pattern_builder.build(),
+                pat: pattern,
             })
         }
     }
@@ -227,7 +396,14 @@ pub fn roadbuild(sim: &Simulation, uiworld: &mut UiWorld) {
             cur_proj.kind
         );
 
-        match (state.build_state, cur_proj.kind, tool) {
+        match (build_state, cur_proj.kind, tool) {
+            (Start(_) | Interpolation(_, _), _, _) if state.mode == RoadBuildMode::Parallel => {
+                immsound.play("road_lay", AudioKind::Ui);
+                if let Some(wc) = potential_command.0.drain(..).next() {
+                    commands.push(wc);
+                }
+            }
+            (Hover, _, _) if state.mode == RoadBuildMode::Parallel => {}
             (Hover, Ground, _) | (Hover, Road(_), _) | (Hover, Inter(_), _) => {
                 // Hover selection
                 state.build_state = Start(cur_proj);
@@ -259,6 +435,30 @@ pub fn roadbuild(sim: &Simulation, uiworld: &mut UiWorld) {
     }
 }
 
+/// Direction of the roads at `proj` that is closest to the direction towards `to`, going either
+/// way along them
+fn reference_dir(map: &Map, proj: MapProject, to: Vec2) -> Option<Vec2> {
+    let dir = (to - proj.pos.xy()).try_normalize()?;
+    let dirs: Vec<Vec2> = match proj.kind {
+        Inter(i) => {
+            let inter = map.intersections().get(i)?;
+            inter
+                .roads
+                .iter()
+                .filter_map(|&r| Some(map.roads().get(r)?.dir_from(i)))
+                .collect()
+        }
+        Road(r) => {
+            let (_, _, rdir) = map.roads().get(r)?.points().project_segment_dir(proj.pos);
+            vec![rdir.xy().try_normalize()?]
+        }
+        _ => return None,
+    };
+    dirs.into_iter()
+        .flat_map(|d| [d, -d])
+        .max_by(|a, b| a.dot(dir).total_cmp(&b.dot(dir)))
+}
+
 fn check_angle(map: &Map, from: MapProject, to: Vec2, is_rail: bool) -> bool {
     let max_turn_angle = if is_rail {
         1.0 * std::f32::consts::PI / 180.0
@@ -296,10 +496,7 @@ fn check_angle(map: &Map, from: MapProject, to: Vec2, is_rail: bool) -> bool {
 }
 
 fn compatible(map: &Map, x: MapProject, y: MapProject) -> bool {
-    // enforce at most 18 deg angle
-    if x.pos.distance(y.pos) < 10.0
-        || (x.pos.z - y.pos.z).abs() > 0.2 * x.pos.xy().distance(y.pos.xy())
-    {
+    if x.pos.distance(y.pos) < 10.0 {
         return false;
     }
     match (x.kind, y.kind) {
@@ -349,100 +546,98 @@ fn check_intersect(
         })
 }
 
-impl RoadBuildResource {
-    pub fn update_drawing(
-        &self,
-        map: &Map,
-        immdraw: &mut ImmediateDraw,
-        proj: MapProject,
-        patwidth: f32,
-        tool: Tool,
-        is_valid: bool,
-    ) {
-        let mut proj_pos = proj.pos;
-        proj_pos.z += 0.1;
-        let col = if is_valid {
-            simulation::config().gui_primary
-        } else {
-            simulation::config().gui_danger
-        };
+fn update_drawing(
+    map: &Map,
+    immdraw: &mut ImmediateDraw,
+    build_state: BuildState,
+    proj: MapProject,
+    patwidth: f32,
+    tool: Tool,
+    is_valid: bool,
+) {
+    let mut proj_pos = proj.pos;
+    proj_pos.z += 0.1;
+    let col = if is_valid {
+        simulation::config().gui_primary
+    } else {
+        simulation::config().gui_danger
+    };
 
-        let interf = |ang: Vec2, proj: MapProject| match proj.kind {
-            Inter(i) => map
-                .intersections()
-                .get(i)
-                .map(|i| i.interface_at(map.roads(), patwidth, ang))
-                .unwrap_or_default(),
-            Road(_) => Intersection::empty_interface(patwidth),
-            Building(_) => 0.0,
-            ProjectKind::Lot(_) => 0.0,
-            Ground => Intersection::empty_interface(patwidth),
-        };
+    let interf = |ang: Vec2, proj: MapProject| match proj.kind {
+        Inter(i) => map
+            .intersections()
+            .get(i)
+            .map(|i| i.interface_at(map.roads(), patwidth, ang))
+            .unwrap_or_default(),
+        Road(_) => Intersection::empty_interface(patwidth),
+        Building(_) => 0.0,
+        ProjectKind::Lot(_) => 0.0,
+        Ground => Intersection::empty_interface(patwidth),
+    };
 
-        let p = match self.build_state {
-            Hover => {
+    let p = match build_state {
+        Hover => {
+            immdraw.circle(proj_pos, patwidth * 0.5).color(col);
+            return;
+        }
+        Start(x) if matches!(tool, Tool::RoadbuildCurved) && proj.kind.is_ground() => {
+            let dir = unwrap_or!((proj_pos - x.pos).try_normalize(), {
                 immdraw.circle(proj_pos, patwidth * 0.5).color(col);
                 return;
+            });
+            let mut poly = Vec::with_capacity(33);
+            for i in 0..=32 {
+                let ang = std::f32::consts::PI * i as f32 * (2.0 / 32.0);
+                let mut v = Vec3::from_angle(ang, dir.z);
+                let center = if v.dot(dir) < 0.0 { x.pos } else { proj.pos };
+
+                v = v * patwidth * 0.5;
+                v.z = 0.0;
+                v += center;
+
+                poly.push(v);
             }
-            Start(x) if matches!(tool, Tool::RoadbuildCurved) && proj.kind.is_ground() => {
-                let dir = unwrap_or!((proj_pos - x.pos).try_normalize(), {
-                    immdraw.circle(proj_pos, patwidth * 0.5).color(col);
-                    return;
-                });
-                let mut poly = Vec::with_capacity(33);
-                for i in 0..=32 {
-                    let ang = std::f32::consts::PI * i as f32 * (2.0 / 32.0);
-                    let mut v = Vec3::from_angle(ang, dir.z);
-                    let center = if v.dot(dir) < 0.0 { x.pos } else { proj.pos };
-
-                    v = v * patwidth * 0.5;
-                    v.z = 0.0;
-                    v += center;
-
-                    poly.push(v);
-                }
-                immdraw.polyline(poly, 3.0, true).color(col);
-
-                return;
-            }
-            Start(x) => {
-                immdraw.circle(proj_pos, patwidth * 0.5).color(col);
-                immdraw.circle(x.pos.up(0.1), patwidth * 0.5).color(col);
-                immdraw.line(proj_pos, x.pos.up(0.1), patwidth).color(col);
-                let istart = interf((proj_pos - x.pos).xy().normalize(), x);
-                let iend = interf(-(proj_pos - x.pos).xy().normalize(), proj);
-                PolyLine3::new(vec![x.pos.up(0.1), proj_pos]).cut(istart, iend)
-            }
-            Interpolation(p, x) => {
-                let sp = Spline3 {
-                    from: x.pos.up(0.1),
-                    to: proj_pos,
-                    from_derivative: (p - x.pos.xy()).z0() * std::f32::consts::FRAC_1_SQRT_2,
-                    to_derivative: (proj_pos.xy() - p).z0() * std::f32::consts::FRAC_1_SQRT_2,
-                };
-                let points: Vec<_> = sp.smart_points(1.0, 0.0, 1.0).collect();
+            immdraw.polyline(poly, 3.0, true).color(col);
 
-                immdraw.polyline(&*points, patwidth, false).color(col);
+            return;
+        }
+        Start(x) => {
+            immdraw.circle(proj_pos, patwidth * 0.5).color(col);
+            immdraw.circle(x.pos.up(0.1), patwidth * 0.5).color(col);
+            immdraw.line(proj_pos, x.pos.up(0.1), patwidth).color(col);
+            let istart = interf((proj_pos - x.pos).xy().normalize(), x);
+            let iend = interf(-(proj_pos - x.pos).xy().normalize(), proj);
+            PolyLine3::new(vec![x.pos.up(0.1), proj_pos]).cut(istart, iend)
+        }
+        Interpolation(p, x) => {
+            let sp = Spline3 {
+                from: x.pos.up(0.1),
+                to: proj_pos,
+                from_derivative: (p - x.pos.xy()).z0() * std::f32::consts::FRAC_1_SQRT_2,
+                to_derivative: (proj_pos.xy() - p).z0() * std::f32::consts::FRAC_1_SQRT_2,
+            };
+            let points: Vec<_> = sp.smart_points(1.0, 0.0, 1.0).collect();
 
-                immdraw.circle(sp.get(0.0), patwidth * 0.5).color(col);
-                immdraw.circle(sp.get(1.0), patwidth * 0.5).color(col);
+            immdraw.polyline(&*points, patwidth, false).color(col);
 
-                let istart = interf((p - x.pos.xy()).normalize(), x);
-                let iend = interf(-(proj_pos.xy() - p).normalize(), proj);
+            immdraw.circle(sp.get(0.0), patwidth * 0.5).color(col);
+            immdraw.circle(sp.get(1.0), patwidth * 0.5).color(col);
 
-                PolyLine3::new(points).cut(istart, iend)
-            }
-        };
+            let istart = interf((p - x.pos.xy()).normalize(), x);
+            let iend = interf(-(proj_pos.xy() - p).normalize(), proj);
 
-        for PylonPosition {
-            terrain_height,
-            pos,
-            ..
-        } in simulation::map::Road::pylons_positions(&p, &map.environment)
-        {
-            immdraw
-                .circle(pos.xy().z(terrain_height + 0.1), patwidth * 0.5)
-                .color(col);
+            PolyLine3::new(points).cut(istart, iend)
         }
+    };
+
+    for PylonPosition {
+        terrain_height,
+        pos,
+        ..
+    } in simulation::map::Road::pylons_positions(&p, &map.environment)
+    {
+        immdraw
+            .circle(pos.xy().z(terrain_height + 0.1), patwidth * 0.5)
+            .color(col);
     }
 }
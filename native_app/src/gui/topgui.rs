@@ -1,7 +1,11 @@
+use crate::gui::blueprint::BlueprintResource;
 use crate::gui::bulldozer::BulldozerState;
 use crate::gui::chat::chat;
 use crate::gui::inspect::inspector;
 use crate::gui::lotbrush::LotBrushResource;
+use crate::gui::notifications::notifications;
+use crate::gui::overlays::overlay_menu;
+use crate::gui::roadbuild::RoadBuildMode;
 use crate::gui::roadeditor::RoadEditorResource;
 use crate::gui::specialbuilding::{SpecialBuildKind, SpecialBuildingResource};
 use crate::gui::terraforming::TerraformingResource;
@@ -18,8 +22,9 @@ use egui::{
     Widget, Window,
 };
 use egui_inspect::{Inspect, InspectArgs};
-use geom::{Polygon, Vec2};
+use geom::{Degrees, Polygon, Vec2};
 use serde::{Deserialize, Serialize};
+use simulation::blueprint::{Blueprint, InterchangePreset};
 use simulation::economy::{Government, Item, ItemRegistry, Money};
 use simulation::map::{
     BuildingKind, LanePatternBuilder, LightPolicy, MapProject, TerraformKind, TurnPolicy, Zone,
@@ -87,6 +92,8 @@ impl Gui {
 
         chat(ui, uiworld, sim);
 
+        notifications(ui, uiworld, sim);
+
         self.windows.render(ui, uiworld, sim);
 
         Self::toolbox(ui, uiworld, sim);
@@ -117,12 +124,26 @@ impl Gui {
             .drain(..)
             .map(|cmd| Government::action_cost(&cmd, sim))
             .sum();
+        let readout = uiworld.read::<RoadBuildResource>().readout;
 
-        if cost == Money::default() {
+        if cost == Money::default() && readout.is_none() {
             return;
         }
 
         egui::show_tooltip(ui, Id::new("tooltip_command_cost"), |ui| {
+            if let Some(readout) = readout {
+                ui.label(format!(
+                    "{:.0}m, grade {:.1}%",
+                    readout.length,
+                    readout.grade * 100.0
+                ));
+                if let Some(radius) = readout.radius {
+                    ui.label(format!("radius {radius:.0}m"));
+                }
+            }
+            if cost == Money::default() {
+                return;
+            }
             if cost > sim.read::<Government>().money {
                 ui.colored_label(Color32::RED, format!("{cost} too expensive"));
             } else {
@@ -164,6 +185,7 @@ impl Gui {
             Bulldozer,
             Train,
             Terraforming,
+            Blueprint,
         }
         uiworld.check_present(|| Tab::Hand);
 
@@ -193,6 +215,7 @@ impl Gui {
             ("bulldozer", Tab::Bulldozer, Tool::Bulldozer),
             ("traintool", Tab::Train, Tool::Train),
             ("terraform", Tab::Terraforming, Tool::Terraforming),
+            ("blueprint", Tab::Blueprint, Tool::Blueprint),
         ];
 
         Window::new("Toolbox")
@@ -209,14 +232,20 @@ impl Gui {
                 let cur_tab = *uiworld.read::<Tab>();
 
                 for (name, tab, default_tool) in &tools {
-                    if egui::ImageButton::new(SizedTexture::new(
-                        uiworld.read::<UiTextures>().get(name),
-                        [toolbox_w, 30.0],
-                    ))
-                    .selected(std::mem::discriminant(tab) == std::mem::discriminant(&cur_tab))
-                    .ui(ui)
-                    .clicked()
-                    {
+                    let selected = std::mem::discriminant(tab) == std::mem::discriminant(&cur_tab);
+                    let texture = uiworld.read::<UiTextures>().try_get(name);
+                    let resp = match texture {
+                        Some(texture) => {
+                            egui::ImageButton::new(SizedTexture::new(texture, [toolbox_w, 30.0]))
+                                .selected(selected)
+                                .ui(ui)
+                        }
+                        None => egui::Button::new(*name)
+                            .selected(selected)
+                            .min_size([toolbox_w, 30.0].into())
+                            .ui(ui),
+                    };
+                    if resp.clicked() {
                         uiworld.insert::<Tool>(*default_tool);
                         uiworld.insert(*tab);
                     }
@@ -356,7 +385,7 @@ impl Gui {
         if matches!(*uiworld.read::<Tab>(), Tab::Roadbuild | Tab::Roadcurved) {
             let rbw = 220.0;
             Window::new("Road Properties")
-                .fixed_size([rbw, 380.0])
+                .fixed_size([rbw, 460.0])
                 .fixed_pos([w - rbw - toolbox_w + tweak!(40.0), h * 0.5 - tweak!(125.0)])
                 .title_bar(true)
                 .collapsible(false)
@@ -374,6 +403,39 @@ impl Gui {
                             .ui(ui);
                         ui.label("height off");
                     });
+                    ui.checkbox(&mut roadbuild.snap_angle, "snap angle to roads");
+                    ui.horizontal(|ui| {
+                        ui.radio_value(&mut roadbuild.mode, RoadBuildMode::Free, "free");
+                        ui.radio_value(&mut roadbuild.mode, RoadBuildMode::Arc, "arc");
+                        ui.radio_value(&mut roadbuild.mode, RoadBuildMode::Parallel, "parallel");
+                    });
+                    match roadbuild.mode {
+                        RoadBuildMode::Free => {}
+                        RoadBuildMode::Arc => {
+                            ui.horizontal(|ui| {
+                                let mut fixed = roadbuild.fixed_radius.is_some();
+                                ui.checkbox(&mut fixed, "fixed radius");
+                                if fixed {
+                                    let radius = roadbuild.fixed_radius.get_or_insert(100.0);
+                                    egui::DragValue::new(radius)
+                                        .clamp_range(20.0..=2000.0f32)
+                                        .suffix("m")
+                                        .ui(ui);
+                                } else {
+                                    roadbuild.fixed_radius = None;
+                                }
+                            });
+                        }
+                        RoadBuildMode::Parallel => {
+                            ui.horizontal(|ui| {
+                                egui::DragValue::new(&mut roadbuild.parallel_offset)
+                                    .clamp_range(5.0..=200.0f32)
+                                    .suffix("m")
+                                    .ui(ui);
+                                ui.label("offset");
+                            });
+                        }
+                    }
                     let pat = &mut roadbuild.pattern_builder;
 
                     static BUILDERS: &[(&str, LanePatternBuilder)] = &[
@@ -545,6 +607,89 @@ impl Gui {
                 });
         }
 
+        if matches!(*uiworld.read::<Tab>(), Tab::Blueprint) {
+            let lbw = 180.0;
+            Window::new("Blueprint")
+                .min_width(lbw)
+                .auto_sized()
+                .fixed_pos([w - toolbox_w - lbw, h * 0.5 - 30.0])
+                .hscroll(false)
+                .title_bar(true)
+                .collapsible(false)
+                .resizable(false)
+                .show(ui, |ui| {
+                    let mut state = uiworld.write::<BlueprintResource>();
+                    let state = &mut *state;
+
+                    match state.blueprint {
+                        Some(ref bp) => {
+                            ui.label(format!(
+                                "{} roads, {} buildings",
+                                bp.roads.len(),
+                                bp.buildings.len()
+                            ));
+                            ui.label("Click to paste, rotate with the wheel");
+                            if ui.button("New selection").clicked() {
+                                state.blueprint = None;
+                            }
+                        }
+                        None => {
+                            ui.label("Drag to select the area to copy");
+                        }
+                    }
+                    ui.add_space(10.0);
+
+                    ui.horizontal(|ui| {
+                        egui::TextEdit::singleline(&mut state.name)
+                            .desired_width(100.0)
+                            .hint_text("name")
+                            .ui(ui);
+                        let can_save = state.blueprint.is_some() && !state.name.is_empty();
+                        if ui
+                            .add_enabled(can_save, egui::Button::new("Save"))
+                            .clicked()
+                        {
+                            if let Some(ref bp) = state.blueprint {
+                                if bp.save(&state.name).is_none() {
+                                    log::error!("could not save blueprint {}", state.name);
+                                }
+                                state.saved = None;
+                            }
+                        }
+                    });
+
+                    let saved = state.saved.get_or_insert_with(Blueprint::list);
+                    let mut picked = None;
+                    for name in saved.iter() {
+                        if ui.button(name).clicked() {
+                            picked = Some(name.clone());
+                        }
+                    }
+                    if let Some(name) = picked {
+                        match Blueprint::load(&name) {
+                            Ok(bp) => {
+                                state.blueprint = Some(bp);
+                                state.rotation = Degrees(0.0);
+                                state.name = name;
+                            }
+                            Err(e) => log::error!("could not load blueprint {}: {}", name, e),
+                        }
+                    }
+
+                    ui.add_space(10.0);
+                    ui.label("Interchanges");
+                    ui.horizontal(|ui| {
+                        for preset in InterchangePreset::ALL {
+                            if ui.button(preset.name()).clicked() {
+                                state.blueprint = Some(Blueprint::interchange(preset));
+                                state.rotation = Degrees(0.0);
+                                state.name.clear();
+                            }
+                        }
+                    });
+                });
+        }
+
         let building_select_w = 200.0;
         let registry = sim.read::<GoodsCompanyRegistry>();
         let gbuildings = registry.descriptions.values().peekable();
@@ -728,6 +873,8 @@ impl Gui {
             egui::menu::bar(ui, |ui| {
                 self.windows.menu(ui);
 
+                overlay_menu(ui, uiworld);
+
                 let mut name = "Save";
                 let mut enabled = true;
                 let mut slstate = uiworld.write::<SaveLoadState>();
@@ -13,7 +13,7 @@ use egui::Widget;
 use engine::{PerfCountersStatic, Tesselator};
 use geom::{Camera, Color, LinearColor, Spline3, Vec2};
 use simulation::map::{
-    IntersectionID, Map, MapSubscriber, RoadSegmentKind, TraverseKind, UpdateType,
+    Heightmap, IntersectionID, Map, MapSubscriber, RoadSegmentKind, TraverseKind, UpdateType,
 };
 use simulation::transportation::train::TrainReservations;
 use simulation::world_command::WorldCommand;
@@ -44,6 +44,7 @@ impl Default for DebugObjs {
             (false, "Debug lots", debug_lots),
             (false, "Debug road points", debug_road_points),
             (false, "Debug parking", debug_parking),
+            (false, "Debug water", debug_water),
         ])
     }
 }
@@ -63,6 +64,21 @@ impl Default for TestFieldProperties {
     }
 }
 
+#[derive(Clone)]
+pub struct CityGenProperties {
+    seed: u64,
+    radius: f32,
+}
+
+impl Default for CityGenProperties {
+    fn default() -> Self {
+        Self {
+            seed: 1,
+            radius: 1500.0,
+        }
+    }
+}
+
 /// debug window for various debug options
 pub fn debug(
     window: egui::Window<'_>,
@@ -208,6 +224,31 @@ pub fn debug(
             );
         }
 
+        drop(state);
+        ui.separator();
+        let mut state = uiworld.write::<CityGenProperties>();
+
+        ui.horizontal(|ui| {
+            egui::DragValue::new(&mut state.seed).ui(ui);
+            ui.label("seed");
+        });
+
+        ui.horizontal(|ui| {
+            egui::DragValue::new(&mut state.radius)
+                .clamp_range(200.0..=5000.0f32)
+                .ui(ui);
+            ui.label("radius");
+        });
+
+        if ui.small_button("generate city").clicked() {
+            uiworld.commands().map_generate_city(
+                state.seed,
+                uiworld.read::<Camera>().pos.xy(),
+                state.radius,
+            );
+        }
+        drop(state);
+
         ui.label(format!("{} pedestrians", sim.world().humans.len()));
         ui.label(format!("{} vehicles", sim.world().vehicles.len()));
 
@@ -246,6 +287,41 @@ pub fn debug_spline(tess: &mut Tesselator<true>, sim: &Simulation, _: &UiWorld)
     Some(())
 }
 
+pub fn debug_water(tess: &mut Tesselator<true>, sim: &Simulation, _: &UiWorld) -> Option<()> {
+    let map = sim.map();
+    let env = &map.environment;
+    tess.set_color(LinearColor::new(0.1, 0.3, 0.9, 0.5));
+    let cell = Heightmap::CELL_SIZE;
+    for (id, chunk) in env.water.chunks() {
+        let corner = id.corner();
+        for (y, row) in chunk.iter().enumerate() {
+            for (x, &depth) in row.iter().enumerate() {
+                if depth < 0.05 {
+                    continue;
+                }
+                let p = corner + Vec2::new(x as f32, y as f32) * cell;
+                let h = env.height(p).unwrap_or(0.0);
+                tess.draw_rect_cos_sin(
+                    (p + Vec2::splat(cell * 0.5)).z(h + depth),
+                    cell,
+                    cell,
+                    Vec2::X,
+                );
+            }
+        }
+    }
+
+    for source in env.water.sources() {
+        tess.set_color(Color::CYAN);
+        tess.draw_circle(
+            source.pos.z(env.height(source.pos).unwrap_or(0.0) + 1.0),
+            10.0,
+        );
+    }
+
+    Some(())
+}
+
 pub fn debug_lots(tess: &mut Tesselator<true>, sim: &Simulation, _: &UiWorld) -> Option<()> {
     tess.set_color(Color::RED);
     for lot in sim.map().lots().values() {
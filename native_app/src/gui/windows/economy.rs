@@ -1,20 +1,26 @@
+use crate::gui::InspectedBuilding;
 use crate::uiworld::UiWorld;
 use common::timestep::UP_DT;
-use egui::{Align2, Color32, Ui};
+use egui::{Align2, Color32, FontId, Pos2, Rect, Sense, Stroke, Ui, Widget};
 use egui_plot::{Line, PlotPoints};
 use geom::Color;
 use simulation::economy::{
-    EcoStats, ItemHistories, ItemRegistry, Market, HISTORY_SIZE, LEVEL_FREQS, LEVEL_NAMES,
+    EcoStats, ItemHistories, ItemRegistry, Market, PowerGrid, ProductionGraph, TradeFlows,
+    TradeTarget, HISTORY_SIZE, LEVEL_FREQS, LEVEL_NAMES,
 };
-use simulation::Simulation;
+use simulation::map::BuildingKind;
+use simulation::souls::goods_company::GoodsCompanyRegistry;
+use simulation::{CompanyID, Simulation, SoulID};
 use slotmapd::Key;
 use std::cmp::Reverse;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 
 enum EconomyTab {
     ImportExports,
     InternalTrade,
     MarketPrices,
+    Power,
+    Chains,
 }
 
 #[derive(Copy, Clone, Default)]
@@ -38,6 +44,7 @@ pub fn economy(window: egui::Window<'_>, ui: &egui::Context, uiw: &mut UiWorld,
         tab: EconomyTab::ImportExports,
         hist_type: Default::default(),
     });
+    let uiw: &UiWorld = uiw;
     let mut state = uiw.write::<EconomyState>();
     let ecostats = sim.read::<EcoStats>();
     let registry = sim.read::<ItemRegistry>();
@@ -74,9 +81,24 @@ pub fn economy(window: egui::Window<'_>, ui: &egui::Context, uiw: &mut UiWorld,
                 {
                     state.tab = EconomyTab::MarketPrices;
                 }
+                if ui
+                    .selectable_label(matches!(state.tab, EconomyTab::Power), "Power")
+                    .clicked()
+                {
+                    state.tab = EconomyTab::Power;
+                }
+                if ui
+                    .selectable_label(matches!(state.tab, EconomyTab::Chains), "Production Chains")
+                    .clicked()
+                {
+                    state.tab = EconomyTab::Chains;
+                }
             });
 
             ui.horizontal(|ui| {
+                if matches!(state.tab, EconomyTab::Chains) {
+                    return;
+                }
                 for (i, level) in LEVEL_NAMES.iter().enumerate() {
                     if ui.selectable_label(i == state.curlevel, *level).clicked() {
                         state.curlevel = i;
@@ -285,6 +307,16 @@ pub fn economy(window: egui::Window<'_>, ui: &egui::Context, uiw: &mut UiWorld,
                         render_market_prices(sim, ui);
                     });
                 }
+                EconomyTab::Power => {
+                    ui.push_id(4, |ui| {
+                        render_power(sim, ui, curlevel, &xs);
+                    });
+                }
+                EconomyTab::Chains => {
+                    ui.push_id(5, |ui| {
+                        render_chains(sim, uiw, ui);
+                    });
+                }
             }
             ui.allocate_space(ui.available_size());
         });
@@ -301,3 +333,232 @@ fn render_market_prices(sim: &Simulation, ui: &mut Ui) {
         }
     });
 }
+
+/// Formats a power given in kW
+pub fn format_power(kw: i64) -> String {
+    if kw.abs() >= 1000 {
+        format!("{:.1} MW", kw as f64 / 1000.0)
+    } else {
+        format!("{kw} kW")
+    }
+}
+
+fn render_power(sim: &Simulation, ui: &mut Ui, curlevel: usize, xs: &[f64]) {
+    let grid = sim.read::<PowerGrid>();
+
+    egui::Grid::new("powergrid").show(ui, |ui| {
+        ui.label("Capacity");
+        ui.label(format_power(grid.capacity));
+        ui.end_row();
+        ui.label("Consumption");
+        ui.label(format_power(grid.consumption));
+        ui.end_row();
+    });
+
+    let satisfaction = grid.satisfaction();
+    egui::ProgressBar::new(satisfaction)
+        .text(format!("demand met: {:.0}%", satisfaction * 100.0))
+        .desired_width(300.0)
+        .ui(ui);
+    if grid.is_brownout() {
+        ui.colored_label(
+            Color32::YELLOW,
+            format!(
+                "Brownout: companies drawing electricity work at {:.0}% of their productivity",
+                grid.brownout_factor() * 100.0
+            ),
+        );
+    } else {
+        ui.label(format!("Plants run at {:.0}%", grid.load_factor() * 100.0));
+    }
+
+    let history = grid.history(curlevel);
+    let c_next = (grid.cursors()[curlevel] + 1) % HISTORY_SIZE;
+    let line = |ring: &[i64; HISTORY_SIZE]| {
+        PlotPoints::from_iter(
+            ring[c_next..HISTORY_SIZE]
+                .iter()
+                .chain(ring[0..c_next].iter())
+                .zip(xs.iter())
+                .map(|(v, x)| [*x, *v as f64]),
+        )
+    };
+
+    egui_plot::Plot::new("powerplot")
+        .height(200.0)
+        .allow_boxed_zoom(false)
+        .include_y(0.0)
+        .include_x(0.0)
+        .allow_drag(false)
+        .allow_scroll(false)
+        .allow_zoom(false)
+        .show(ui, |ui| {
+            ui.line(
+                Line::new(line(&history.capacity))
+                    .color(Color32::GREEN)
+                    .name("Capacity (kW)"),
+            );
+            ui.line(
+                Line::new(line(&history.consumption))
+                    .color(Color32::RED)
+                    .name("Consumption (kW)"),
+            );
+        });
+}
+
+const NODE_SIZE: egui::Vec2 = egui::vec2(130.0, 24.0);
+const NODE_SPACING: egui::Vec2 = egui::vec2(180.0, 36.0);
+const IMPORT_COLOR: Color32 = Color32::from_rgb(230, 140, 40);
+const BOTTLENECK_FILL: Color32 = Color32::from_rgb(110, 35, 35);
+
+/// Production chains of the city: the companies laid out by depth in the chain, linked by the
+/// goods they traded recently. Companies relying on imports are highlighted.
+fn render_chains(sim: &Simulation, uiw: &UiWorld, ui: &mut Ui) {
+    let graph = ProductionGraph::new(&sim.read::<TradeFlows>());
+    let registry = sim.read::<ItemRegistry>();
+    let gregistry = sim.read::<GoodsCompanyRegistry>();
+    let world = sim.world();
+    let map = sim.map();
+
+    if graph.layers.is_empty() {
+        ui.label("No goods were traded between companies recently");
+        return;
+    }
+
+    let name = |id: CompanyID| {
+        world
+            .companies
+            .get(id)
+            .and_then(|c| map.buildings().get(c.comp.building))
+            .and_then(|b| match b.kind {
+                BuildingKind::GoodsCompany(gid) => gregistry.descriptions.get(gid),
+                _ => None,
+            })
+            .map_or("???", |d| d.name.as_str())
+    };
+    let inspect = |id: CompanyID| {
+        let Some(c) = world.companies.get(id) else {
+            return;
+        };
+        uiw.write::<InspectedBuilding>().e = Some(c.comp.building);
+        if let Some(b) = map.buildings().get(c.comp.building) {
+            uiw.camera_mut().targetpos = b.door_pos;
+        }
+    };
+
+    // The external market takes the first column, the companies follow by layer
+    let columns = graph.columns();
+    let n_rows = columns.iter().map(Vec::len).max().unwrap_or(0).max(1);
+    let size = egui::vec2(
+        (columns.len() + 1) as f32 * NODE_SPACING.x,
+        n_rows as f32 * NODE_SPACING.y,
+    );
+
+    egui::ScrollArea::both()
+        .max_height(300.0)
+        .auto_shrink([false, true])
+        .show(ui, |ui| {
+            let (rect, _) = ui.allocate_exact_size(size, Sense::hover());
+            let node_rect = |column: usize, row: usize, n: usize| {
+                let offset = (n_rows - n) as f32 * 0.5 * NODE_SPACING.y;
+                Rect::from_min_size(
+                    rect.min
+                        + egui::vec2(
+                            column as f32 * NODE_SPACING.x,
+                            offset + row as f32 * NODE_SPACING.y,
+                        ),
+                    NODE_SIZE,
+                )
+            };
+
+            let mut rects = BTreeMap::new();
+            rects.insert(TradeTarget::ExternalTrade, node_rect(0, 0, 1));
+            for (layer, column) in columns.iter().enumerate() {
+                for (row, &id) in column.iter().enumerate() {
+                    rects.insert(
+                        TradeTarget::Soul(SoulID::GoodsCompany(id)),
+                        node_rect(layer + 1, row, column.len()),
+                    );
+                }
+            }
+
+            let max_qty = graph.edges.iter().map(|(_, qty)| *qty).max().unwrap_or(1) as f32;
+            let painter = ui.painter();
+            for (key, qty) in &graph.edges {
+                let (Some(from), Some(to)) = (rects.get(&key.seller), rects.get(&key.buyer)) else {
+                    continue;
+                };
+                let color = match (key.seller, key.buyer) {
+                    (TradeTarget::ExternalTrade, _) => IMPORT_COLOR,
+                    (_, TradeTarget::ExternalTrade) => Color32::LIGHT_BLUE,
+                    _ => Color32::GRAY,
+                };
+                let (a, b) = (from.right_center(), to.left_center());
+                painter.line_segment(
+                    [a, b],
+                    Stroke::new(1.0 + 4.0 * *qty as f32 / max_qty, color),
+                );
+                painter.text(
+                    Pos2::new((a.x + b.x) * 0.5, (a.y + b.y) * 0.5),
+                    Align2::CENTER_BOTTOM,
+                    format!("{} {}", qty, registry[key.item].label),
+                    FontId::proportional(10.0),
+                    color,
+                );
+            }
+
+            ui.put(
+                rects[&TradeTarget::ExternalTrade],
+                egui::Button::new("External market").stroke(Stroke::new(1.0, IMPORT_COLOR)),
+            );
+
+            for &id in graph.layers.keys() {
+                let mut button = egui::Button::new(name(id)).wrap(false);
+                if graph.bottlenecks.contains_key(&id) {
+                    button = button
+                        .fill(BOTTLENECK_FILL)
+                        .stroke(Stroke::new(1.0, IMPORT_COLOR));
+                }
+                let mut resp = ui.put(rects[&TradeTarget::Soul(SoulID::GoodsCompany(id))], button);
+                if let Some(bottlenecks) = graph.bottlenecks.get(&id) {
+                    resp = resp.on_hover_ui(|ui| {
+                        for b in bottlenecks {
+                            ui.label(format!(
+                                "imports {} of {} {}",
+                                b.imported, b.total, registry[b.item].label
+                            ));
+                        }
+                    });
+                }
+                if resp.clicked() {
+                    inspect(id);
+                }
+            }
+        });
+
+    if graph.bottlenecks.is_empty() {
+        return;
+    }
+    ui.separator();
+    ui.colored_label(IMPORT_COLOR, "Bottlenecks: companies relying on imports");
+    egui::ScrollArea::vertical()
+        .id_source("bottlenecks")
+        .max_height(120.0)
+        .show(ui, |ui| {
+            egui::Grid::new("bottlenecks").show(ui, |ui| {
+                for (&id, bottlenecks) in &graph.bottlenecks {
+                    for b in bottlenecks {
+                        if ui.link(name(id)).clicked() {
+                            inspect(id);
+                        }
+                        ui.label(&registry[b.item].label);
+                        ui.label(format!(
+                            "{:.0}% imported",
+                            b.imported as f32 * 100.0 / b.total as f32
+                        ));
+                        ui.end_row();
+                    }
+                }
+            });
+        });
+}
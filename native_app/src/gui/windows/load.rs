@@ -1,13 +1,15 @@
 #![allow(unused)]
 use crate::uiworld::{SaveLoadState, UiWorld};
 use egui::{Color32, DroppedFile, Widget};
-use simulation::Simulation;
+use simulation::map::procgen::heightmap::{Biome, WorldGenOptions};
+use simulation::{Simulation, SimulationOptions};
 use std::path::PathBuf;
 
 #[derive(Default)]
 pub struct LoadState {
     curpath: Option<PathBuf>,
     load_fail: String,
+    worldgen: WorldGenOptions,
 }
 
 /// Load window
@@ -23,9 +25,40 @@ pub fn load(window: egui::Window<'_>, ui: &egui::Context, uiw: &mut UiWorld, _:
         });
 
         if ui.button("New Game").clicked() {
-            uiw.write::<SaveLoadState>().please_load_sim = Some(Simulation::new(true));
+            uiw.write::<SaveLoadState>().please_load_sim =
+                Some(Simulation::new_with_options(SimulationOptions {
+                    worldgen: lstate.worldgen,
+                    ..Default::default()
+                }));
         }
 
+        ui.collapsing("World generation", |ui| {
+            let wg = &mut lstate.worldgen;
+            egui::ComboBox::from_label("Biome")
+                .selected_text(format!("{:?}", wg.biome))
+                .show_ui(ui, |ui| {
+                    for biome in Biome::ALL {
+                        if ui
+                            .selectable_label(wg.biome == biome, format!("{biome:?}"))
+                            .clicked()
+                        {
+                            *wg = WorldGenOptions {
+                                seed: wg.seed,
+                                ..WorldGenOptions::preset(biome)
+                            };
+                        }
+                    }
+                });
+            ui.horizontal(|ui| {
+                egui::DragValue::new(&mut wg.seed).ui(ui);
+                ui.label("Seed");
+            });
+            ui.add(egui::Slider::new(&mut wg.sea_level, 0.0..=0.5).text("Sea level"));
+            ui.add(egui::Slider::new(&mut wg.mountains, 0.0..=1.0).text("Mountains"));
+            ui.add(egui::Slider::new(&mut wg.tree_density, 0.0..=3.0).text("Tree density"));
+            ui.checkbox(&mut wg.rivers, "Rivers");
+        });
+
         if has_save {
             if ui.button("Load world/world_replay.json").clicked() {
                 let replay = Simulation::load_replay_from_disk("world");
@@ -7,10 +7,11 @@ use simulation::Simulation;
 
 mod config;
 pub mod debug;
-mod economy;
+pub mod economy;
 pub mod load;
 #[cfg(feature = "multiplayer")]
 pub mod network;
+pub mod scripts;
 pub mod settings;
 
 pub trait GUIWindow: Send + Sync {
@@ -64,6 +65,7 @@ impl Default for GUIWindows {
         #[cfg(feature = "multiplayer")]
         s.insert("Network", network::network, false);
         s.insert("Load", load::load, false);
+        s.insert("Scripts", scripts::scripts, false);
         s
     }
 }
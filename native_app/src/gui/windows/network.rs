@@ -9,6 +9,12 @@ use std::collections::BTreeMap;
 pub struct NetworkConnectionInfo {
     pub name: String,
     pub ip: String,
+    /// Password of the server to join or to start, not saved
+    pub password: String,
+    /// Join servers without being able to act
+    pub spectator: bool,
+    /// World parts downloaded before a disconnection, to resume the download
+    pub partial_world: Option<BTreeMap<String, Vec<u8>>>,
     pub error: String,
     show_hashes: bool,
     hashes: BTreeMap<String, u64>,
@@ -40,6 +46,11 @@ pub fn network(window: egui::Window<'_>, ui: &Context, uiworld: &mut UiWorld, si
                     return;
                 }
 
+                ui.horizontal(|ui| {
+                    ui.add(egui::TextEdit::singleline(&mut info.password).password(true));
+                    ui.label("Password (optional)");
+                });
+
                 if ui.small_button("Start server").clicked() {
                     if let Some(server) = crate::network::start_server(&mut info, sim) {
                         *state = NetworkState::Server(server);
@@ -52,8 +63,9 @@ pub fn network(window: egui::Window<'_>, ui: &Context, uiworld: &mut UiWorld, si
                     ui.text_edit_singleline(&mut info.ip);
                     ui.label("IP");
                 });
+                ui.checkbox(&mut info.spectator, "Spectate");
                 if ui.small_button("Connect").clicked() {
-                    if let Some(c) = crate::network::start_client(&mut info) {
+                    if let Some(c) = crate::network::start_client(&mut info, sim) {
                         *state = NetworkState::Client(c);
                     }
                 }
@@ -91,6 +103,9 @@ impl Default for NetworkConnectionInfo {
         Self {
             name: String::with_capacity(100),
             ip: String::with_capacity(100),
+            password: String::new(),
+            spectator: false,
+            partial_world: None,
             error: String::new(),
             show_hashes: false,
             hashes: Default::default(),
@@ -0,0 +1,82 @@
+use crate::uiworld::UiWorld;
+use egui::{Color32, Context, RichText};
+use simulation::scripting::{run_macro, Scripts};
+use simulation::Simulation;
+
+#[derive(Default)]
+pub struct ScriptsState {
+    name: String,
+    source: String,
+    error: String,
+}
+
+/// Scripts window
+/// Allows to run macros and to install scripts run by the simulation every tick
+pub fn scripts(window: egui::Window<'_>, ui: &Context, uiworld: &mut UiWorld, sim: &Simulation) {
+    window.default_width(400.0).show(ui, |ui| {
+        let mut state = uiworld.write::<ScriptsState>();
+
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut state.name);
+            ui.label("Name");
+        });
+        ui.add(
+            egui::TextEdit::multiline(&mut state.source)
+                .code_editor()
+                .desired_rows(12)
+                .desired_width(f32::INFINITY),
+        );
+
+        ui.horizontal(|ui| {
+            if ui.button("Run as macro").clicked() {
+                match run_macro(sim, &state.source) {
+                    Ok(commands) => {
+                        state.error.clear();
+                        uiworld.commands().extend(commands);
+                    }
+                    Err(e) => state.error = e,
+                }
+            }
+            if ui
+                .add_enabled(!state.name.is_empty(), egui::Button::new("Install"))
+                .on_hover_text("Runs the on_tick function of the script every tick")
+                .clicked()
+            {
+                state.error.clear();
+                uiworld
+                    .commands()
+                    .set_script(state.name.clone(), state.source.clone());
+            }
+        });
+
+        if !state.error.is_empty() {
+            ui.label(RichText::new(&state.error).color(Color32::RED));
+        }
+
+        ui.separator();
+
+        let scripts = sim.read::<Scripts>();
+        if scripts.iter().next().is_none() {
+            ui.label("No installed scripts");
+        }
+        for (name, script) in scripts.iter() {
+            ui.horizontal(|ui| {
+                if script.enabled {
+                    ui.label(name);
+                } else {
+                    ui.label(RichText::new(name).strikethrough());
+                }
+                if ui.small_button("edit").clicked() {
+                    state.name = name.clone();
+                    state.source = script.source.clone();
+                }
+                if ui.small_button("remove").clicked() {
+                    uiworld.commands().remove_script(name.clone());
+                }
+            });
+            if let Some(ref error) = script.error {
+                ui.label(RichText::new(error).color(Color32::RED));
+            }
+        }
+    });
+}
@@ -1,12 +1,15 @@
 use crate::game_loop::Timings;
+use crate::gui::blueprint::BlueprintResource;
 use crate::gui::bulldozer::BulldozerState;
 use crate::gui::chat::GUIChatState;
 use crate::gui::lotbrush::LotBrushResource;
+use crate::gui::notifications::NotificationsState;
+use crate::gui::overlays::{Overlay, TrafficCounts};
 use crate::gui::roadbuild::RoadBuildResource;
 use crate::gui::roadeditor::RoadEditorResource;
 use crate::gui::specialbuilding::SpecialBuildingResource;
 use crate::gui::terraforming::TerraformingResource;
-use crate::gui::windows::debug::{DebugObjs, DebugState, TestFieldProperties};
+use crate::gui::windows::debug::{CityGenProperties, DebugObjs, DebugState, TestFieldProperties};
 use crate::gui::windows::settings::Settings;
 use crate::gui::zoneedit::ZoneEditState;
 use crate::gui::{
@@ -34,6 +37,7 @@ pub fn init() {
 
     register_resource_noserialize::<TerraformingResource>();
     register_resource_noserialize::<BulldozerState>();
+    register_resource_noserialize::<BlueprintResource>();
     register_resource_noserialize::<DebugObjs>();
     register_resource_noserialize::<DebugState>();
     register_resource_noserialize::<ErrorTooltip>();
@@ -46,17 +50,22 @@ pub fn init() {
     register_resource_noserialize::<InspectedEntity>();
     register_resource_noserialize::<InspectedBuilding>();
     register_resource_noserialize::<NetworkState>();
+    register_resource_noserialize::<NotificationsState>();
+    register_resource_noserialize::<Overlay>();
     register_resource_noserialize::<PotentialCommands>();
     register_resource_noserialize::<ZoneEditState>();
     register_resource_noserialize::<TestFieldProperties>();
+    register_resource_noserialize::<CityGenProperties>();
     register_resource_noserialize::<ReceivedCommands>();
     register_resource_noserialize::<RoadBuildResource>();
     register_resource_noserialize::<RoadEditorResource>();
     register_resource_noserialize::<SpecialBuildingResource>();
     register_resource_noserialize::<Timings>();
+    register_resource_noserialize::<TrafficCounts>();
     register_resource_noserialize::<Tool>();
     register_resource_noserialize::<WorldCommands>();
     register_resource_noserialize::<crate::gui::windows::load::LoadState>();
+    register_resource_noserialize::<crate::gui::windows::scripts::ScriptsState>();
     register_resource_noserialize::<crate::uiworld::SaveLoadState>();
 }
 
@@ -104,9 +104,11 @@ mod inner {
     use crate::gui::windows::network::NetworkConnectionInfo;
     use crate::network::handle_replay;
     use crate::uiworld::{ReceivedCommands, SaveLoadState};
+    use common::saveload::{Encoder, JSON};
     use common::timestep::Timestep;
     use networking::{
-        ConnectConf, Frame, PollResult, ServerConfiguration, ServerPollResult, VirtualClientConf,
+        ConnectConf, Frame, PollResult, Role, ServerConfiguration, ServerPollResult,
+        VirtualClientConf,
     };
     use simulation::world_command::WorldCommands;
     use simulation::Simulation;
@@ -168,7 +170,12 @@ mod inner {
                 }
             }
             NetworkState::Client(ref mut client) => {
-                let polled = client.get_mut().unwrap().poll(commands);
+                let client = client.get_mut().unwrap();
+                let rejected = client.take_rejected();
+                if !rejected.is_empty() {
+                    state.uiw.write::<NetworkConnectionInfo>().error = rejected.join("\n");
+                }
+                let polled = client.poll(commands);
                 match polled {
                     PollResult::Wait(commands) => {
                         *state.uiw.write::<WorldCommands>() = commands;
@@ -184,8 +191,14 @@ mod inner {
                         log::error!(
                             "got disconnected :-( continuing with server world but it's sad"
                         );
+                        let mut info = state.uiw.write::<NetworkConnectionInfo>();
+                        // keep what was downloaded to resume the transfer on reconnection
+                        if !client.known_world().is_empty() {
+                            info.partial_world = Some(client.known_world().clone());
+                        }
+                        info.error = reason;
+                        drop(info);
                         *net_state = NetworkState::Singleplayer(Timestep::default());
-                        state.uiw.write::<NetworkConnectionInfo>().error = reason;
                     }
                 }
             }
@@ -220,7 +233,7 @@ mod inner {
     }
 
     pub fn start_server(info: &mut NetworkConnectionInfo, sim: &Simulation) -> Option<Server> {
-        let server = match networking::Server::start(ServerConfiguration {
+        let mut server = match networking::Server::start(ServerConfiguration {
             start_frame: Frame(sim.get_tick()),
             period: common::timestep::UP_DT,
             port: None,
@@ -228,7 +241,11 @@ mod inner {
                 name: info.name.to_string(),
             }),
             version: VERSION.to_string(),
+            mods: common::mods::active_infos(),
             always_run: true,
+            default_role: Role::Builder,
+            password: password(info),
+            spectators_only: false,
         }) {
             Ok(x) => x,
             Err(e) => {
@@ -236,11 +253,27 @@ mod inner {
                 return None;
             }
         };
+        server.set_input_filter(WorldCommands::retain_permitted);
+        server.set_world_parts(Simulation::parts);
 
         Some(Mutex::new(server))
     }
 
-    pub fn start_client(info: &mut NetworkConnectionInfo) -> Option<Client> {
+    fn password(info: &NetworkConnectionInfo) -> Option<String> {
+        Some(info.password.clone()).filter(|p| !p.is_empty())
+    }
+
+    /// The token is generated once and kept so that the servers recognize the player
+    fn identity_token() -> String {
+        if let Ok(token) = JSON::load::<String>("identity") {
+            return token;
+        }
+        let token = networking::generate_token();
+        JSON::save_silent(&token, "identity");
+        token
+    }
+
+    pub fn start_client(info: &mut NetworkConnectionInfo, sim: &Simulation) -> Option<Client> {
         let mut s = info.ip.to_string();
         if !s.contains(':') {
             s += ":23019"
@@ -261,12 +294,16 @@ mod inner {
 
         let port = parsed_addr.port();
 
-        let client = match networking::Client::connect(ConnectConf {
+        let mut client = match networking::Client::connect(ConnectConf {
             name: info.name.clone(),
             addr: parsed_addr.ip(),
             port: if port != 23019 { Some(port) } else { None },
             frame_buffer_advance: 8,
             version: VERSION.to_string(),
+            mods: common::mods::active_infos(),
+            password: password(info),
+            token: identity_token(),
+            spectator: info.spectator,
         }) {
             Ok(x) => x,
             Err(e) => {
@@ -274,6 +311,9 @@ mod inner {
                 return None;
             }
         };
+        // if the local world is an older copy of the server's, only what changed is downloaded
+        let known = info.partial_world.take().unwrap_or_else(|| sim.parts());
+        client.set_known_world(known, Simulation::from_parts);
 
         Some(Mutex::new(client))
     }
@@ -1,6 +1,6 @@
 use log::LevelFilter;
 use networking::{
-    Client, ConnectConf, Frame, PollResult, Server, ServerConfiguration, ServerPollResult,
+    Client, ConnectConf, Frame, PollResult, Role, Server, ServerConfiguration, ServerPollResult,
 };
 use serde::{Deserialize, Serialize};
 use std::net::Ipv4Addr;
@@ -59,6 +59,10 @@ pub fn main() {
         port: None,
         frame_buffer_advance: 10,
         version: "v1".to_string(),
+        mods: vec![],
+        password: None,
+        token: networking::generate_token(),
+        spectator: false,
     })
     .unwrap();
 
@@ -105,7 +109,11 @@ pub fn serv_c() {
         port: None,
         virtual_client: None,
         version: "v1".to_string(),
+        mods: vec![],
         always_run: true,
+        default_role: Role::Admin,
+        password: None,
+        spectators_only: false,
     })
     .unwrap();
 
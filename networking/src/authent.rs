@@ -1,8 +1,13 @@
-use crate::connections::Connections;
-use crate::packets::{AuthentResponse, ServerReliablePacket, ServerUnreliablePacket};
+use crate::packets::{
+    AuthentResponse, ConnectRequest, ServerReliablePacket, ServerUnreliablePacket,
+};
+use crate::transport::ServerTransport;
 use crate::{encode, hash_str, Frame, UserID};
+use common::mods::ModInfo;
+use common::Role;
 use common::{FastMap, FastSet};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt::{Display, Formatter};
 use std::net::SocketAddr;
 use std::time::Duration;
@@ -19,6 +24,18 @@ impl Display for AuthentID {
 
 impl AuthentID {
     pub const VIRTUAL_ID: AuthentID = AuthentID(0);
+    pub const SERVER_ID: AuthentID = AuthentID(u32::MAX);
+}
+
+/// What the server remembers about players between connections, it can be saved to keep it
+/// across restarts
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+pub struct Identities {
+    /// Token of the player who first connected with each name
+    names: BTreeMap<String, String>,
+    roles: BTreeMap<String, Role>,
+    banned_names: BTreeSet<String>,
+    banned_identities: BTreeSet<String>,
 }
 
 #[derive(PartialEq, Eq, Debug)]
@@ -33,6 +50,9 @@ pub(crate) struct Client {
     #[allow(dead_code)]
     pub uid: UserID,
     pub name: String,
+    pub role: Role,
+    /// Only acknowledges the frames, its inputs are never merged
+    pub spectator: bool,
     pub ack: Frame,
     pub udp_addr: SocketAddr,
     pub tcp_addr: SocketAddr,
@@ -50,22 +70,38 @@ enum ClientConnectState {
 
 pub(crate) struct Authent {
     names: FastSet<String>,
+    identities: Identities,
+    default_role: Role,
+    password: Option<String>,
+    spectators_only: bool,
     clients: FastMap<AuthentID, ClientConnectState>,
     addr_to_client: FastMap<SocketAddr, AuthentID>,
     n_connected_clients: u32,
     seq: u32,
     version: String,
+    mods: Vec<ModInfo>,
 }
 
 impl Authent {
-    pub fn new(version: String) -> Self {
+    pub fn new(
+        version: String,
+        mods: Vec<ModInfo>,
+        default_role: Role,
+        password: Option<String>,
+        spectators_only: bool,
+    ) -> Self {
         Self {
             names: Default::default(),
+            identities: Default::default(),
+            default_role,
+            password,
+            spectators_only,
             clients: Default::default(),
             addr_to_client: Default::default(),
             n_connected_clients: 0,
             seq: 1,
             version,
+            mods,
         }
     }
 
@@ -74,14 +110,56 @@ impl Authent {
         !self.names.insert(name)
     }
 
+    /// Bans the name and the identity that owns it
+    pub fn ban(&mut self, name: String) {
+        if let Some(identity) = self.identities.names.get(&name) {
+            self.identities.banned_identities.insert(identity.clone());
+        }
+        self.identities.banned_names.insert(name);
+    }
+
+    /// returns true if the player was banned
+    pub fn unban(&mut self, name: &str) -> bool {
+        if let Some(identity) = self.identities.names.get(name) {
+            self.identities.banned_identities.remove(identity);
+        }
+        self.identities.banned_names.remove(name)
+    }
+
+    pub fn identities(&self) -> &Identities {
+        &self.identities
+    }
+
+    pub fn set_identities(&mut self, identities: Identities) {
+        self.identities = identities;
+    }
+
+    /// Sets the role of a player, whether it is connected or not
+    pub fn set_role(&mut self, name: String, role: Role) {
+        for c in self.iter_mut() {
+            if c.name == name {
+                c.role = role;
+            }
+        }
+        self.identities.roles.insert(name, role);
+    }
+
     pub fn tcp_client_auth(
         &mut self,
         addr: SocketAddr,
         ack: Frame,
-        name: String,
-        version: String,
+        req: ConnectRequest,
         period: Duration,
     ) -> Option<AuthentResponse> {
+        let ConnectRequest {
+            name,
+            version,
+            mods,
+            password,
+            token,
+            spectator,
+            known_parts: _,
+        } = req;
         let v = self.get_client_state_mut(addr)?;
 
         if let ClientConnectState::Connecting {
@@ -93,9 +171,31 @@ impl Authent {
             log::info!("client authenticated: {}@{}", name, addr);
             let hash = hash_str(&name);
 
-            if self.register(name.clone()) {
+            let password_ok = match (&self.password, &password) {
+                (None, _) => true,
+                (Some(expected), Some(given)) => {
+                    constant_time_eq(expected.as_bytes(), given.as_bytes())
+                }
+                (Some(_), None) => false,
+            };
+            if !password_ok {
                 return Some(AuthentResponse::Refused {
-                    reason: format!("name is already in use: {name}"),
+                    reason: "wrong password".to_string(),
+                });
+            }
+
+            if self.identities.banned_names.contains(&name)
+                || self.identities.banned_identities.contains(&token)
+            {
+                return Some(AuthentResponse::Refused {
+                    reason: "you are banned from this server".to_string(),
+                });
+            }
+
+            let owner = self.identities.names.get(&name);
+            if owner.is_some_and(|owner| !constant_time_eq(owner.as_bytes(), token.as_bytes())) {
+                return Some(AuthentResponse::Refused {
+                    reason: format!("name belongs to another player: {name}"),
                 });
             }
 
@@ -108,11 +208,35 @@ impl Authent {
                 });
             }
 
+            if let Some(mismatch) = common::mods::mismatch(&self.mods, &mods) {
+                return Some(AuthentResponse::Refused {
+                    reason: format!("Incompatible mods, serv vs client: {mismatch}"),
+                });
+            }
+
+            if self.register(name.clone()) {
+                return Some(AuthentResponse::Refused {
+                    reason: format!("name is already in use: {name}"),
+                });
+            }
+
+            // the name is claimed only once the player is accepted
+            self.identities.names.entry(name.clone()).or_insert(token);
+
+            let role = self
+                .identities
+                .roles
+                .get(&name)
+                .copied()
+                .unwrap_or(self.default_role);
+
             // Unwrap ok: already checked right before
             *self.get_client_state_mut(tcp_addr).unwrap() = ClientConnectState::Connected(Client {
                 id,
                 uid: UserID(hash),
                 name,
+                role,
+                spectator: spectator || self.spectators_only,
                 ack,
 
                 udp_addr,
@@ -127,7 +251,7 @@ impl Authent {
         None
     }
 
-    pub fn udp_connect(&mut self, addr: SocketAddr, id: AuthentID, net: &Connections) {
+    pub fn udp_connect(&mut self, addr: SocketAddr, id: AuthentID, net: &dyn ServerTransport) {
         log::info!("udp connect: {}", addr);
         self.addr_to_client.insert(addr, id);
         if let Some(ClientConnectState::Connecting { udp_addr, .. }) =
@@ -140,7 +264,7 @@ impl Authent {
         }
     }
 
-    pub fn tcp_connected(&mut self, tcp_addr: SocketAddr, net: &Connections) {
+    pub fn tcp_connected(&mut self, tcp_addr: SocketAddr, net: &dyn ServerTransport) {
         log::info!("connected: {}", tcp_addr);
 
         let id = self.next_auth_id();
@@ -233,3 +357,13 @@ impl ClientConnectState {
         }
     }
 }
+
+/// Compares two secrets in a time that depends only on their length, so that a client cannot
+/// guess them byte after byte by timing the answers
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let diff = a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y));
+    std::hint::black_box(diff) == 0
+}
@@ -1,6 +1,6 @@
 use crate::authent::{Client, ClientGameState};
-use crate::connections::Connections;
 use crate::packets::ServerReliablePacket;
+use crate::transport::ServerTransport;
 use crate::{encode, AuthentID, Frame, MergedInputs};
 use common::FastMap;
 
@@ -18,10 +18,16 @@ pub(crate) struct CatchUp {
 
 impl CatchUp {
     pub fn begin_remembering(&mut self, from: Frame, c: &Client) {
+        self.begin_remembering_with(from, vec![], c);
+    }
+
+    /// Same as [`CatchUp::begin_remembering`] with the inputs of the frames after `from` that
+    /// were already consumed
+    pub fn begin_remembering_with(&mut self, from: Frame, inputs: Vec<MergedInputs>, c: &Client) {
         let v = self.frame_history.insert(
             c.id,
             CatchUpState {
-                inputs: vec![],
+                inputs,
                 sent: 0,
                 from,
                 ready: false,
@@ -48,7 +54,7 @@ impl CatchUp {
         }
     }
 
-    pub fn update(&mut self, c: &mut Client, net: &Connections) {
+    pub fn update(&mut self, c: &mut Client, net: &dyn ServerTransport) {
         let state = match self.frame_history.get_mut(&c.id) {
             Some(x) => x,
             None => return,
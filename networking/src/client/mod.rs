@@ -8,13 +8,16 @@ use client_playout::ClientPlayoutBuffer;
 use crate::connection_client::ConnectionClient;
 use crate::connections::ConnectionsError;
 use crate::packets::{
-    AuthentResponse, ClientReliablePacket, ClientUnreliablePacket, ServerReliablePacket,
-    ServerUnreliablePacket,
+    AuthentResponse, ClientReliablePacket, ClientUnreliablePacket, ConnectRequest,
+    ServerReliablePacket, ServerUnreliablePacket,
 };
+use crate::transport::ClientTransport;
 use crate::worldsend::WorldReceive;
 use crate::{
-    decode, decode_merged, encode, AuthentID, Frame, PhantomSendSync, PlayerInput, DEFAULT_PORT,
+    decode, decode_merged, encode, AuthentID, Frame, FromParts, PhantomSendSync, PlayerInput,
+    WorldParts, DEFAULT_PORT,
 };
+use common::mods::ModInfo;
 use common::timestep::Timestep;
 
 mod client_playout;
@@ -61,15 +64,26 @@ enum ClientState<W, I> {
 }
 
 pub struct Client<WORLD: DeserializeOwned, INPUT: Serialize + DeserializeOwned + Default> {
-    net: ConnectionClient,
+    net: Box<dyn ClientTransport>,
 
     name: String,
     version: String,
+    mods: Vec<ModInfo>,
+    password: Option<String>,
+    token: String,
+    spectator: bool,
+    /// Parts of an older copy of the world, see [`Client::set_known_world`]
+    known: WorldParts,
+    from_parts: Option<FromParts<WORLD>>,
 
     state: ClientState<WORLD, INPUT>,
+    /// Challenge answered over udp until the server is ready for authentication, as it can be lost
+    challenge: Option<AuthentID>,
 
     pub step: Timestep,
     lag_compensate: u64,
+    /// Reasons of the inputs rejected by the server, see [`Client::take_rejected`]
+    rejected: Vec<String>,
 
     _phantom: PhantomSendSync<(INPUT, WORLD)>,
 }
@@ -80,6 +94,13 @@ pub struct ConnectConf {
     pub port: Option<u16>,
     pub frame_buffer_advance: u64,
     pub version: String,
+    /// Mods the client loaded its data with, they must be the same as the server's
+    pub mods: Vec<ModInfo>,
+    pub password: Option<String>,
+    /// Identifies the player across connections, see [`crate::generate_token`]
+    pub token: String,
+    /// Only watch the game, the inputs given to [`Client::poll`] are never sent
+    pub spectator: bool,
 }
 
 impl<W: DeserializeOwned, I: Serialize + DeserializeOwned + Default> Client<W, I> {
@@ -89,16 +110,28 @@ impl<W: DeserializeOwned, I: Serialize + DeserializeOwned + Default> Client<W, I
         let saddr = SocketAddr::new(addr, port);
 
         let net = ConnectionClient::new(saddr)?;
+        Ok(Self::connect_with_transport(conf, Box::new(net)))
+    }
 
-        Ok(Self {
+    /// Same as [`Client::connect`] over any transport, the address of the configuration is ignored
+    pub fn connect_with_transport(conf: ConnectConf, net: Box<dyn ClientTransport>) -> Self {
+        Self {
             net,
             state: ClientState::Connecting,
+            challenge: None,
             name: conf.name,
             lag_compensate: conf.frame_buffer_advance,
             step: Timestep::default(),
+            rejected: vec![],
             _phantom: Default::default(),
             version: conf.version,
-        })
+            mods: conf.mods,
+            password: conf.password,
+            token: conf.token,
+            spectator: conf.spectator,
+            known: Default::default(),
+            from_parts: None,
+        }
     }
 
     #[allow(clippy::collapsible_if)]
@@ -139,6 +172,10 @@ impl<W: DeserializeOwned, I: Serialize + DeserializeOwned + Default> Client<W, I
                 return PollResult::Disconnect(reason.clone());
             }
             ClientState::Connecting => {
+                if let Some(challenge) = self.challenge {
+                    self.net
+                        .send_udp(encode(&ClientUnreliablePacket::Connection(challenge)));
+                }
                 return PollResult::Wait(input);
             }
             ClientState::Downloading {
@@ -203,7 +240,8 @@ impl<W: DeserializeOwned, I: Serialize + DeserializeOwned + Default> Client<W, I
                     return PollResult::Wait(input);
                 }
 
-                let mut inp = Some(&input);
+                // spectators send default inputs only to acknowledge the frames
+                let mut inp = Some(&input).filter(|_| !self.spectator);
                 let mut mk_input = || {
                     let d = Default::default();
                     let v = inp.take().unwrap_or(&d);
@@ -244,19 +282,42 @@ impl<W: DeserializeOwned, I: Serialize + DeserializeOwned + Default> Client<W, I
         PollResult::Wait(input)
     }
 
+    /// Lets the server send only the parts of the world that differ from `known`, which can be
+    /// the parts of an older save of the same world or [`Client::known_world`] of a previous
+    /// connection interrupted while downloading
+    pub fn set_known_world(&mut self, known: WorldParts, from_parts: FromParts<W>) {
+        self.known = known;
+        self.from_parts = Some(from_parts);
+    }
+
+    /// Parts of the world known so far, including the ones downloaded before a disconnection
+    pub fn known_world(&self) -> &WorldParts {
+        &self.known
+    }
+
+    /// Reasons of the inputs rejected by the server since the last call
+    pub fn take_rejected(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.rejected)
+    }
+
     fn message_reliable(&mut self, p: ServerReliablePacket) -> Option<()> {
         match p {
+            ServerReliablePacket::Rejected { reasons } => {
+                log::warn!("{}: server rejected inputs: {:?}", self.name, reasons);
+                self.rejected.extend(reasons);
+            }
             ServerReliablePacket::WorldSend(fragment) => {
                 log::info!("{}: received world fragment", self.name);
 
                 if let ClientState::Downloading { ref mut wr, .. } = self.state {
-                    wr.handle(fragment, &self.net);
+                    wr.handle(fragment, &*self.net, &mut self.known, self.from_parts);
                 } else {
                     log::error!("received world but was not downloading.. weird");
                 }
             }
             ServerReliablePacket::Challenge(challenge) => {
                 log::info!("{}: received challenge", self.name);
+                self.challenge = Some(challenge);
                 self.net
                     .send_udp(encode(&ClientUnreliablePacket::Connection(challenge)));
             }
@@ -363,11 +424,24 @@ impl<W: DeserializeOwned, I: Serialize + DeserializeOwned + Default> Client<W, I
                 }
             }
             ServerUnreliablePacket::ReadyForAuth => {
+                if self.challenge.take().is_none() {
+                    return;
+                }
                 log::info!("{}: received ready for auth", self.name);
-                let connect = ClientReliablePacket::Connect {
+                let connect = ClientReliablePacket::Connect(ConnectRequest {
                     name: self.name.clone(),
                     version: self.version.clone(),
-                };
+                    mods: self.mods.clone(),
+                    password: self.password.clone(),
+                    token: self.token.clone(),
+                    spectator: self.spectator,
+                    known_parts: self.from_parts.map(|_| {
+                        self.known
+                            .iter()
+                            .map(|(name, data)| (name.clone(), common::hash_u64(&**data)))
+                            .collect()
+                    }),
+                });
                 self.net.send_tcp(encode(&connect));
             }
         }
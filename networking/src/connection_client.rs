@@ -1,4 +1,5 @@
 use crate::connections::{ConnectionsError, FramedTcpReceiver};
+use crate::transport::ClientTransport;
 use std::io::{Read, Write};
 use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, TcpStream, UdpSocket};
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -50,34 +51,6 @@ impl ConnectionClient {
         })
     }
 
-    pub fn is_disconnected(&self) -> bool {
-        self.disconnected.load(Ordering::SeqCst)
-    }
-
-    pub fn send_udp(&self, data: Vec<u8>) -> Option<()> {
-        self.udp_send.send(data).ok()
-    }
-
-    pub fn recv_udp(&self) -> Option<Vec<u8>> {
-        self.udp_recv.try_recv().ok()
-    }
-
-    pub fn send_tcp(&self, data: Vec<u8>) -> Option<()> {
-        self.tcp_conn.2.send(data).ok()
-    }
-
-    pub fn recv_tcp(&mut self) -> Vec<Vec<u8>> {
-        let mut packets = Vec::new();
-        let Ok(v) = self.tcp_conn.1.try_recv() else {
-            return packets;
-        };
-
-        self.tcp_conn.0.recv(&v, |d| {
-            packets.push(d);
-        });
-        packets
-    }
-
     fn start_process_threads(
         udp_sock: UdpSocket,
         udp_recv: Receiver<Vec<u8>>,
@@ -155,3 +128,33 @@ impl ConnectionClient {
         });
     }
 }
+
+impl ClientTransport for ConnectionClient {
+    fn is_disconnected(&self) -> bool {
+        self.disconnected.load(Ordering::SeqCst)
+    }
+
+    fn send_udp(&self, data: Vec<u8>) -> Option<()> {
+        self.udp_send.send(data).ok()
+    }
+
+    fn recv_udp(&self) -> Option<Vec<u8>> {
+        self.udp_recv.try_recv().ok()
+    }
+
+    fn send_tcp(&self, data: Vec<u8>) -> Option<()> {
+        self.tcp_conn.2.send(data).ok()
+    }
+
+    fn recv_tcp(&mut self) -> Vec<Vec<u8>> {
+        let mut packets = Vec::new();
+        let Ok(v) = self.tcp_conn.1.try_recv() else {
+            return packets;
+        };
+
+        self.tcp_conn.0.recv(&v, |d| {
+            packets.push(d);
+        });
+        packets
+    }
+}
@@ -3,6 +3,8 @@ use std::io::{Read, Write};
 use std::net::{SocketAddr, TcpListener, UdpSocket};
 use std::sync::mpsc::{channel, Receiver, Sender};
 
+use crate::transport::ServerTransport;
+
 pub struct Packet {
     pub addr: SocketAddr,
     pub data: Vec<u8>,
@@ -65,60 +67,6 @@ impl Connections {
         })
     }
 
-    pub fn send_udp(&self, addr: SocketAddr, data: Vec<u8>) -> Option<()> {
-        self.udp_send.send(Packet { addr, data }).ok()
-    }
-
-    pub fn recv_udp(&self) -> Option<Packet> {
-        self.udp_recv.try_recv().ok()
-    }
-
-    pub fn send_tcp(&self, addr: SocketAddr, frame: Vec<u8>) -> Option<()> {
-        if let Some((_, _, send)) = self.tcp_conns.get(&addr) {
-            return send.send(frame).ok();
-        }
-        None
-    }
-
-    pub fn remove_tcp(&mut self, addr: SocketAddr) {
-        self.tcp_conns.remove(&addr);
-    }
-
-    // returns new and deleted conns
-    pub fn handle_tcp_conns(&mut self) -> (Vec<SocketAddr>, Vec<SocketAddr>) {
-        let mut newconns = vec![];
-        let mut deletedconns = vec![];
-        for event in self.tcp_conn_events.try_iter() {
-            match event {
-                TcpConnEvent::New { send, recv, addr } => {
-                    self.tcp_conns
-                        .insert(addr, (FramedTcpReceiver::new(), recv, send));
-                    newconns.push(addr);
-                }
-                TcpConnEvent::Killed { addr } => {
-                    deletedconns.push(addr);
-                    self.tcp_conns.remove(&addr);
-                }
-            }
-        }
-        (newconns, deletedconns)
-    }
-
-    pub fn recv_tcp(&mut self) -> Vec<Packet> {
-        let mut packets = Vec::new();
-        for (addr, (frame, recv, _)) in self.tcp_conns.iter_mut() {
-            let Ok(v) = recv.try_recv() else { continue };
-
-            frame.recv(&v, |d| {
-                packets.push(Packet {
-                    addr: *addr,
-                    data: d,
-                })
-            });
-        }
-        packets
-    }
-
     fn start_process_threads(
         udp_sock: UdpSocket,
         udp_recv: Receiver<Packet>,
@@ -212,6 +160,62 @@ impl Connections {
     }
 }
 
+impl ServerTransport for Connections {
+    fn send_udp(&self, addr: SocketAddr, data: Vec<u8>) -> Option<()> {
+        self.udp_send.send(Packet { addr, data }).ok()
+    }
+
+    fn recv_udp(&self) -> Option<Packet> {
+        self.udp_recv.try_recv().ok()
+    }
+
+    fn send_tcp(&self, addr: SocketAddr, frame: Vec<u8>) -> Option<()> {
+        if let Some((_, _, send)) = self.tcp_conns.get(&addr) {
+            return send.send(frame).ok();
+        }
+        None
+    }
+
+    fn remove_tcp(&mut self, addr: SocketAddr) {
+        self.tcp_conns.remove(&addr);
+    }
+
+    // returns new and deleted conns
+    fn handle_tcp_conns(&mut self) -> (Vec<SocketAddr>, Vec<SocketAddr>) {
+        let mut newconns = vec![];
+        let mut deletedconns = vec![];
+        for event in self.tcp_conn_events.try_iter() {
+            match event {
+                TcpConnEvent::New { send, recv, addr } => {
+                    self.tcp_conns
+                        .insert(addr, (FramedTcpReceiver::new(), recv, send));
+                    newconns.push(addr);
+                }
+                TcpConnEvent::Killed { addr } => {
+                    deletedconns.push(addr);
+                    self.tcp_conns.remove(&addr);
+                }
+            }
+        }
+        (newconns, deletedconns)
+    }
+
+    fn recv_tcp(&mut self) -> Vec<Packet> {
+        let mut packets = Vec::new();
+        for (addr, (frame, recv, _)) in self.tcp_conns.iter_mut() {
+            let Ok(v) = recv.try_recv() else { continue };
+
+            frame.recv(&v, |d| {
+                packets.push(Packet {
+                    addr: *addr,
+                    data: d,
+                })
+            });
+        }
+        packets
+    }
+}
+
 impl FramedTcpReceiver {
     pub fn new() -> Self {
         Self {
@@ -5,6 +5,7 @@ use common::saveload::{CompressedBincode, Encoder};
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
 use std::hash::{Hash, Hasher};
 use std::marker::PhantomData;
 use std::ops::Add;
@@ -17,11 +18,20 @@ mod connections;
 mod packets;
 mod ring;
 mod server;
+mod transport;
 mod worldsend;
 
+pub mod memory;
+
 use crate::client::FrameInputs;
+pub use authent::Identities;
 pub use client::{Client, ConnectConf, PollResult, ServerInput};
-pub use server::{Server, ServerConfiguration, ServerPollResult, VirtualClientConf};
+pub use common::Role;
+pub use connections::{ConnectionsError, Packet};
+pub use server::{
+    InputFilter, PlayerInfo, Server, ServerConfiguration, ServerPollResult, VirtualClientConf,
+};
+pub use transport::{ClientTransport, ServerTransport};
 
 pub(crate) const MAX_WORLDSEND_PACKET_SIZE: usize = 262144; //32 ko at least 1.3Mo per s at 50FPS
 pub(crate) const DEFAULT_PORT: u16 = 23019;
@@ -40,6 +50,12 @@ pub(crate) struct PlayerInput(pub Vec<u8>);
 
 pub(crate) type MergedInputs = Vec<(AuthentID, PlayerInput)>;
 
+/// A world serialized in named parts, so that only the parts that changed need to be sent
+pub type WorldParts = BTreeMap<String, Vec<u8>>;
+
+/// Rebuilds a world from all of its parts
+pub type FromParts<W> = fn(WorldParts) -> Option<W>;
+
 impl Add for Frame {
     type Output = Self;
     #[inline]
@@ -88,6 +104,14 @@ impl<T> Default for PhantomSendSync<T> {
     }
 }
 
+/// Generates a random token to be used as [`ConnectConf::token`], it should be stored by the
+/// client so that it keeps its identity when reconnecting
+pub fn generate_token() -> String {
+    let mut bytes = [0; 16];
+    getrandom::getrandom(&mut bytes).expect("no secure random source to generate a token");
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
 pub(crate) fn hash_str(s: &str) -> u32 {
     let mut hasher = DefaultHasher::new();
     s.hash(&mut hasher);
@@ -0,0 +1,325 @@
+//! In-memory network to run a [`crate::Server`] and its [`crate::Client`]s in one process.
+//!
+//! Packets are held back according to the [`NetworkConditions`], udp packets can be lost and
+//! reordered while tcp messages are only delayed, like over a real network.
+//! ```ignore
+//! let network = MemoryNetwork::new(NetworkConditions::default());
+//! let server = Server::start_with_transport(server_conf, Box::new(network.server()));
+//! let client = Client::connect_with_transport(client_conf, Box::new(network.connect()));
+//! ```
+
+use crate::connections::Packet;
+use crate::transport::{ClientTransport, ServerTransport};
+use common::rand::RandGen;
+use std::collections::{BTreeMap, VecDeque};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+#[derive(Copy, Clone, Debug)]
+pub struct NetworkConditions {
+    /// Time for a packet to go from one end to the other
+    pub latency: Duration,
+    /// Random delay added to the latency of udp packets, reorders them
+    pub jitter: Duration,
+    /// Probability for an udp packet to be lost, between 0 and 1
+    pub loss: f32,
+    /// Seed of the random decisions, so that a run can be reproduced
+    pub seed: u64,
+}
+
+impl Default for NetworkConditions {
+    fn default() -> Self {
+        Self {
+            latency: Duration::ZERO,
+            jitter: Duration::ZERO,
+            loss: 0.0,
+            seed: 0,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Dest {
+    Server,
+    Client(SocketAddr),
+}
+
+struct InFlight {
+    deliver_at: Instant,
+    dest: Dest,
+    tcp: bool,
+    /// Tcp address of the client that sent or receives the packet
+    peer: SocketAddr,
+    data: Vec<u8>,
+}
+
+#[derive(Default)]
+struct ClientInbox {
+    udp: VecDeque<Vec<u8>>,
+    tcp: Vec<Vec<u8>>,
+    disconnected: bool,
+}
+
+struct Inner {
+    conditions: NetworkConditions,
+    rng: RandGen,
+    next_port: u16,
+    in_flight: Vec<InFlight>,
+
+    server_udp: VecDeque<Packet>,
+    server_tcp: Vec<Packet>,
+    new_conns: Vec<SocketAddr>,
+    killed_conns: Vec<SocketAddr>,
+
+    /// Keyed by the tcp address of the client
+    clients: BTreeMap<SocketAddr, ClientInbox>,
+}
+
+/// Shared handle to the simulated network, clones refer to the same network
+#[derive(Clone)]
+pub struct MemoryNetwork {
+    inner: Arc<Mutex<Inner>>,
+}
+
+pub struct MemoryServer {
+    inner: Arc<Mutex<Inner>>,
+}
+
+pub struct MemoryClient {
+    inner: Arc<Mutex<Inner>>,
+    tcp_addr: SocketAddr,
+    udp_addr: SocketAddr,
+}
+
+/// The server sees the udp address of a client on the port right after its tcp address
+fn udp_of(tcp_addr: SocketAddr) -> SocketAddr {
+    SocketAddr::new(tcp_addr.ip(), tcp_addr.port() + 1)
+}
+
+fn tcp_of(udp_addr: SocketAddr) -> SocketAddr {
+    SocketAddr::new(udp_addr.ip(), udp_addr.port() - 1)
+}
+
+fn lock(inner: &Mutex<Inner>) -> MutexGuard<'_, Inner> {
+    inner.lock().unwrap()
+}
+
+impl MemoryNetwork {
+    pub fn new(conditions: NetworkConditions) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Inner {
+                conditions,
+                rng: common::rand::gen(conditions.seed),
+                next_port: 1000,
+                in_flight: vec![],
+                server_udp: Default::default(),
+                server_tcp: vec![],
+                new_conns: vec![],
+                killed_conns: vec![],
+                clients: Default::default(),
+            })),
+        }
+    }
+
+    /// Changes the conditions of the packets sent from now on
+    pub fn set_conditions(&self, conditions: NetworkConditions) {
+        lock(&self.inner).conditions = conditions;
+    }
+
+    /// The end of the network the server listens on, there should be only one
+    pub fn server(&self) -> MemoryServer {
+        MemoryServer {
+            inner: self.inner.clone(),
+        }
+    }
+
+    /// Opens a new connection to the server
+    pub fn connect(&self) -> MemoryClient {
+        let mut inner = lock(&self.inner);
+        let tcp_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), inner.next_port);
+        inner.next_port += 2;
+        inner.clients.insert(tcp_addr, ClientInbox::default());
+        inner.new_conns.push(tcp_addr);
+        MemoryClient {
+            inner: self.inner.clone(),
+            tcp_addr,
+            udp_addr: udp_of(tcp_addr),
+        }
+    }
+
+    /// Cuts the connection of the client as if its cable was pulled, see [`MemoryClient::tcp_addr`]
+    pub fn disconnect(&self, tcp_addr: SocketAddr) {
+        lock(&self.inner).cut(tcp_addr);
+    }
+}
+
+impl Inner {
+    fn cut(&mut self, tcp_addr: SocketAddr) {
+        let Some(inbox) = self.clients.get_mut(&tcp_addr) else {
+            return;
+        };
+        if inbox.disconnected {
+            return;
+        }
+        inbox.disconnected = true;
+        self.in_flight.retain(|p| p.peer != tcp_addr);
+        self.killed_conns.push(tcp_addr);
+    }
+
+    fn is_connected(&self, tcp_addr: SocketAddr) -> bool {
+        self.clients
+            .get(&tcp_addr)
+            .map(|inbox| !inbox.disconnected)
+            .unwrap_or(false)
+    }
+
+    fn send(&mut self, dest: Dest, tcp: bool, peer: SocketAddr, data: Vec<u8>) -> Option<()> {
+        if !self.is_connected(peer) {
+            return None;
+        }
+        let mut delay = self.conditions.latency;
+        if !tcp {
+            if self.rng.next_f32() < self.conditions.loss {
+                return Some(());
+            }
+            delay += self.conditions.jitter.mul_f32(self.rng.next_f32());
+        }
+        let mut deliver_at = Instant::now() + delay;
+        if tcp {
+            // tcp keeps the order even if the latency went down in between
+            for p in &self.in_flight {
+                if p.tcp && p.peer == peer && p.dest == dest {
+                    deliver_at = deliver_at.max(p.deliver_at);
+                }
+            }
+        }
+        self.in_flight.push(InFlight {
+            deliver_at,
+            dest,
+            tcp,
+            peer,
+            data,
+        });
+        Some(())
+    }
+
+    /// Moves the packets that arrived into the inboxes, in order of arrival
+    fn deliver(&mut self) {
+        let now = Instant::now();
+        let (mut arrived, in_flight): (Vec<_>, Vec<_>) = std::mem::take(&mut self.in_flight)
+            .into_iter()
+            .partition(|p| p.deliver_at <= now);
+        self.in_flight = in_flight;
+        arrived.sort_by_key(|p| p.deliver_at);
+
+        for p in arrived {
+            match p.dest {
+                Dest::Server if p.tcp => self.server_tcp.push(Packet {
+                    addr: p.peer,
+                    data: p.data,
+                }),
+                Dest::Server => self.server_udp.push_back(Packet {
+                    addr: udp_of(p.peer),
+                    data: p.data,
+                }),
+                Dest::Client(addr) => {
+                    let Some(inbox) = self.clients.get_mut(&addr) else {
+                        continue;
+                    };
+                    if p.tcp {
+                        inbox.tcp.push(p.data);
+                    } else {
+                        inbox.udp.push_back(p.data);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl ServerTransport for MemoryServer {
+    fn send_udp(&self, addr: SocketAddr, data: Vec<u8>) -> Option<()> {
+        let peer = tcp_of(addr);
+        lock(&self.inner).send(Dest::Client(peer), false, peer, data)
+    }
+
+    fn recv_udp(&self) -> Option<Packet> {
+        let mut inner = lock(&self.inner);
+        inner.deliver();
+        inner.server_udp.pop_front()
+    }
+
+    fn send_tcp(&self, addr: SocketAddr, data: Vec<u8>) -> Option<()> {
+        lock(&self.inner).send(Dest::Client(addr), true, addr, data)
+    }
+
+    fn recv_tcp(&mut self) -> Vec<Packet> {
+        let mut inner = lock(&self.inner);
+        inner.deliver();
+        std::mem::take(&mut inner.server_tcp)
+    }
+
+    fn remove_tcp(&mut self, addr: SocketAddr) {
+        let mut inner = lock(&self.inner);
+        inner.cut(addr);
+        inner.killed_conns.retain(|&killed| killed != addr);
+    }
+
+    fn handle_tcp_conns(&mut self) -> (Vec<SocketAddr>, Vec<SocketAddr>) {
+        let mut inner = lock(&self.inner);
+        (
+            std::mem::take(&mut inner.new_conns),
+            std::mem::take(&mut inner.killed_conns),
+        )
+    }
+}
+
+impl MemoryClient {
+    pub fn tcp_addr(&self) -> SocketAddr {
+        self.tcp_addr
+    }
+
+    pub fn udp_addr(&self) -> SocketAddr {
+        self.udp_addr
+    }
+}
+
+impl ClientTransport for MemoryClient {
+    fn is_disconnected(&self) -> bool {
+        !lock(&self.inner).is_connected(self.tcp_addr)
+    }
+
+    fn send_udp(&self, data: Vec<u8>) -> Option<()> {
+        lock(&self.inner).send(Dest::Server, false, self.tcp_addr, data)
+    }
+
+    fn recv_udp(&self) -> Option<Vec<u8>> {
+        let mut inner = lock(&self.inner);
+        inner.deliver();
+        inner.clients.get_mut(&self.tcp_addr)?.udp.pop_front()
+    }
+
+    fn send_tcp(&self, data: Vec<u8>) -> Option<()> {
+        lock(&self.inner).send(Dest::Server, true, self.tcp_addr, data)
+    }
+
+    fn recv_tcp(&mut self) -> Vec<Vec<u8>> {
+        let mut inner = lock(&self.inner);
+        inner.deliver();
+        inner
+            .clients
+            .get_mut(&self.tcp_addr)
+            .map(|inbox| std::mem::take(&mut inbox.tcp))
+            .unwrap_or_default()
+    }
+}
+
+impl Drop for MemoryClient {
+    fn drop(&mut self) {
+        // not `lock`, panicking again while unwinding would abort the tests
+        if let Ok(mut inner) = self.inner.lock() {
+            inner.cut(self.tcp_addr);
+        }
+    }
+}
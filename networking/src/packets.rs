@@ -1,6 +1,8 @@
 use crate::authent::AuthentID;
 use crate::{Frame, MergedInputs, PlayerInput};
+use common::mods::ModInfo;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::time::Duration;
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -21,6 +23,10 @@ pub(crate) enum ServerReliablePacket {
         inputs: Vec<MergedInputs>,
     },
     WorldSend(WorldDataFragment),
+    /// Some inputs were dropped because the role of the player does not allow them
+    Rejected {
+        reasons: Vec<String>,
+    },
 }
 
 #[derive(Serialize, Deserialize)]
@@ -31,12 +37,27 @@ pub(crate) enum ClientUnreliablePacket {
 
 #[derive(Serialize, Deserialize)]
 pub(crate) enum ClientReliablePacket {
-    Connect { name: String, version: String },
+    Connect(ConnectRequest),
     BeginCatchUp,
     CatchUpAck,
     WorldAck,
 }
 
+#[derive(Serialize, Deserialize)]
+pub(crate) struct ConnectRequest {
+    pub name: String,
+    pub version: String,
+    pub mods: Vec<ModInfo>,
+    pub password: Option<String>,
+    /// Secret proving the identity of the player, it is kept by the client between connections
+    pub token: String,
+    /// Spectators receive the inputs but cannot send any
+    pub spectator: bool,
+    /// Hashes of the parts of the world the client already has, None if it cannot rebuild a
+    /// world from its parts
+    pub known_parts: Option<BTreeMap<String, u64>>,
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub(crate) enum AuthentResponse {
     Accepted { id: AuthentID, period: Duration },
@@ -46,6 +67,12 @@ pub(crate) enum AuthentResponse {
 #[derive(Serialize, Deserialize)]
 pub(crate) struct WorldDataFragment {
     pub is_over: Option<Frame>,
+    /// Size of all the parts
     pub data_size: usize,
+    /// Name of the part the data belongs to, a fragment only contains data of one part
+    pub part: Option<String>,
+    pub part_size: usize,
+    /// The parts are only the ones that changed, see [`ConnectRequest::known_parts`]
+    pub delta: bool,
     pub data: Vec<u8>,
 }
@@ -2,7 +2,7 @@ use std::time::Duration;
 
 use serde::Serialize;
 
-use crate::authent::{Authent, AuthentID, ClientGameState};
+use crate::authent::{Authent, AuthentID, ClientGameState, Identities};
 use crate::catchup::CatchUp;
 use crate::client::FrameInputs;
 use crate::connections::{Connections, ConnectionsError};
@@ -11,10 +11,17 @@ use crate::packets::{
     ServerUnreliablePacket,
 };
 use crate::server::server_playout::ServerPlayoutBuffer;
+use crate::transport::ServerTransport;
 use crate::worldsend::WorldSend;
-use crate::{decode, decode_merged, encode, Frame, PhantomSendSync, PlayerInput, DEFAULT_PORT};
+use crate::{
+    decode, decode_merged, encode, Frame, MergedInputs, PhantomSendSync, PlayerInput, WorldParts,
+    DEFAULT_PORT,
+};
+use common::mods::ModInfo;
 use common::timestep::Timestep;
+use common::Role;
 use serde::de::DeserializeOwned;
+use std::collections::BTreeMap;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 
 mod server_playout;
@@ -26,8 +33,16 @@ pub struct ServerConfiguration {
     pub virtual_client: Option<VirtualClientConf>,
     /// Checks if client has same version or refuses authent otherwise
     pub version: String,
+    /// Mods the world was loaded with, clients with other mods are refused
+    pub mods: Vec<ModInfo>,
     /// Always run, even when everyone is disconnected
     pub always_run: bool,
+    /// Role given to players that were not assigned one
+    pub default_role: Role,
+    /// Players must give this password to join
+    pub password: Option<String>,
+    /// Every client joins as a spectator, for example to broadcast a replay
+    pub spectators_only: bool,
 }
 
 pub struct VirtualClientConf {
@@ -43,8 +58,30 @@ struct VirtualClient {
     name: String,
 }
 
+pub struct PlayerInfo {
+    pub name: String,
+    pub role: Role,
+    pub spectator: bool,
+    /// Lag in frames, None if the player is too late
+    pub lag: Option<u64>,
+}
+
+/// Number of frames after which the baseline snapshot of the world is taken again
+const BASELINE_PERIOD: u64 = 3000;
+
+/// Snapshot of the world sent in parts to the joining clients, followed by the inputs since
+struct Baseline {
+    frame: Frame,
+    parts: WorldParts,
+    hashes: BTreeMap<String, u64>,
+    inputs: Vec<MergedInputs>,
+}
+
+/// Removes from the inputs what the role does not allow, returns the reasons of the removals
+pub type InputFilter<INPUT> = fn(Role, &mut INPUT) -> Vec<String>;
+
 pub struct Server<WORLD: Serialize, INPUT> {
-    net: Connections,
+    net: Box<dyn ServerTransport>,
 
     authent: Authent,
     v_client: Option<VirtualClient>,
@@ -55,6 +92,13 @@ pub struct Server<WORLD: Serialize, INPUT> {
 
     step: Timestep,
     always_run: bool,
+    paused: bool,
+    time_warp: u32,
+    /// Inputs issued by the server itself, merged into their frame
+    server_inputs: BTreeMap<Frame, Vec<PlayerInput>>,
+    input_filter: Option<InputFilter<INPUT>>,
+    world_parts: Option<fn(&WORLD) -> WorldParts>,
+    baseline: Option<Baseline>,
 
     _phantom: PhantomSendSync<(WORLD, INPUT)>,
 }
@@ -63,14 +107,24 @@ impl<WORLD: 'static + Serialize, INPUT: Serialize + DeserializeOwned> Server<WOR
     pub fn start(conf: ServerConfiguration) -> Result<Self, ConnectionsError> {
         let port = conf.port.unwrap_or(DEFAULT_PORT);
         let net = Connections::new(SocketAddr::new(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), port))?;
+        Ok(Self::start_with_transport(conf, Box::new(net)))
+    }
 
-        let mut authent = Authent::new(conf.version);
+    /// Same as [`Server::start`] over any transport, the port of the configuration is ignored
+    pub fn start_with_transport(conf: ServerConfiguration, net: Box<dyn ServerTransport>) -> Self {
+        let mut authent = Authent::new(
+            conf.version,
+            conf.mods,
+            conf.default_role,
+            conf.password,
+            conf.spectators_only,
+        );
         let v_client = conf.virtual_client.map(|c| VirtualClient { name: c.name });
         if let Some(ref v_client) = v_client {
             authent.register(v_client.name.clone());
         }
 
-        Ok(Self {
+        Self {
             net,
             step: Timestep::new(conf.period),
             buffer: ServerPlayoutBuffer::new(conf.start_frame),
@@ -80,8 +134,14 @@ impl<WORLD: 'static + Serialize, INPUT: Serialize + DeserializeOwned> Server<WOR
             worldsend: Default::default(),
             _phantom: Default::default(),
             always_run: conf.always_run,
+            paused: false,
+            time_warp: 1,
+            server_inputs: BTreeMap::new(),
+            input_filter: None,
+            world_parts: None,
+            baseline: None,
             next_inputs: vec![],
-        })
+        }
     }
 
     pub fn poll(
@@ -90,6 +150,8 @@ impl<WORLD: 'static + Serialize, INPUT: Serialize + DeserializeOwned> Server<WOR
         frame: Frame,
         local_inputs: Option<INPUT>,
     ) -> ServerPollResult<INPUT> {
+        self.update_baseline(world, frame);
+
         let (new, deleted) = self.net.handle_tcp_conns();
         for addr in new {
             self.tcp_connected(addr);
@@ -141,11 +203,11 @@ impl<WORLD: 'static + Serialize, INPUT: Serialize + DeserializeOwned> Server<WOR
     fn send_merged_inputs(&mut self) {
         let n_playing = self.authent.iter_playing().count() + self.v_client.is_some() as usize;
 
-        if n_playing == 0 && !self.always_run {
+        if (n_playing == 0 && !self.always_run) || self.paused {
             return;
         }
 
-        self.step.prepare_frame(1);
+        self.step.prepare_frame(self.time_warp);
 
         while self.step.tick() {
             let buffer = &self.buffer;
@@ -165,6 +227,11 @@ impl<WORLD: 'static + Serialize, INPUT: Serialize + DeserializeOwned> Server<WOR
                 self.disconnect(tcp_addr);
             }
 
+            let next_frame = self.buffer.consumed_frame.incred();
+            for input in self.server_inputs.remove(&next_frame).into_iter().flatten() {
+                self.buffer.insert_server_input(AuthentID::SERVER_ID, input);
+            }
+
             let clients_playing = self.authent.iter_playing();
 
             let (consumed_inputs, inputs) =
@@ -183,6 +250,10 @@ impl<WORLD: 'static + Serialize, INPUT: Serialize + DeserializeOwned> Server<WOR
                 self.buffer.consumed_frame,
             ));
 
+            if let Some(ref mut baseline) = self.baseline {
+                baseline.inputs.push(consumed_inputs.clone());
+            }
+
             self.catchup
                 .add_merged_inputs(self.buffer.consumed_frame, consumed_inputs);
         }
@@ -192,10 +263,10 @@ impl<WORLD: 'static + Serialize, INPUT: Serialize + DeserializeOwned> Server<WOR
         for c in self.authent.iter_mut() {
             match c.state {
                 ClientGameState::Downloading => {
-                    self.worldsend.update(c, &self.net);
+                    self.worldsend.update(c, &*self.net);
                 }
                 ClientGameState::CatchingUp => {
-                    self.catchup.update(c, &self.net);
+                    self.catchup.update(c, &*self.net);
                 }
                 _ => {}
             }
@@ -215,11 +286,34 @@ impl<WORLD: 'static + Serialize, INPUT: Serialize + DeserializeOwned> Server<WOR
 
                 for (frame, input) in input {
                     client.ack = client.ack.max(frame);
+                    if client.spectator || self.buffer.is_seen(client.id, frame) {
+                        continue;
+                    }
+                    let input = match self.input_filter {
+                        Some(filter) if client.role != Role::Admin => {
+                            let Some(mut decoded) = decode::<INPUT>(&input.0) else {
+                                log::error!("{} sent invalid input", client.name);
+                                continue;
+                            };
+                            let reasons = filter(client.role, &mut decoded);
+                            if reasons.is_empty() {
+                                input
+                            } else {
+                                log::info!("rejected inputs of {}: {:?}", client.name, reasons);
+                                self.net.send_tcp(
+                                    client.tcp_addr,
+                                    encode(&ServerReliablePacket::Rejected { reasons }),
+                                );
+                                PlayerInput(encode(&decoded))
+                            }
+                        }
+                        _ => input,
+                    };
                     self.buffer.insert_input(client.id, frame, input);
                 }
             }
             ClientUnreliablePacket::Connection(id) => {
-                self.authent.udp_connect(addr, id, &self.net);
+                self.authent.udp_connect(addr, id, &*self.net);
             }
         }
         Some(())
@@ -233,13 +327,13 @@ impl<WORLD: 'static + Serialize, INPUT: Serialize + DeserializeOwned> Server<WOR
         w_frame: Frame,
     ) -> Option<()> {
         match packet {
-            ClientReliablePacket::Connect { name, version } => {
-                log::info!("received tcp game handshake: {} {}", name, version);
+            ClientReliablePacket::Connect(mut req) => {
+                log::info!("received tcp game handshake: {} {}", req.name, req.version);
+                let known_parts = req.known_parts.take();
                 let auth_r = self.authent.tcp_client_auth(
                     addr,
                     self.buffer.consumed_frame,
-                    name,
-                    version,
+                    req,
                     self.step.period,
                 )?;
 
@@ -251,10 +345,42 @@ impl<WORLD: 'static + Serialize, INPUT: Serialize + DeserializeOwned> Server<WOR
                 match auth_r {
                     AuthentResponse::Accepted { .. } => {
                         let c = self.authent.get_client(addr)?;
-                        assert_eq!(self.buffer.consumed_frame, w_frame);
-                        self.worldsend.begin_send(c, encode(&w), w_frame);
-                        self.catchup
-                            .begin_remembering(self.buffer.consumed_frame, c);
+                        match (known_parts, &self.baseline) {
+                            (Some(known), Some(baseline)) => {
+                                let changed: Vec<_> = baseline
+                                    .parts
+                                    .iter()
+                                    .filter(|(name, _)| {
+                                        known.get(*name) != baseline.hashes.get(*name)
+                                    })
+                                    .map(|(name, data)| (name.clone(), data.clone()))
+                                    .collect();
+                                log::info!(
+                                    "sending {}/{} parts of the baseline at {:?} to {}",
+                                    changed.len(),
+                                    baseline.parts.len(),
+                                    baseline.frame,
+                                    c.name
+                                );
+                                self.worldsend.begin_send(c, changed, baseline.frame, true);
+                                self.catchup.begin_remembering_with(
+                                    baseline.frame,
+                                    baseline.inputs.clone(),
+                                    c,
+                                );
+                            }
+                            _ => {
+                                assert_eq!(self.buffer.consumed_frame, w_frame);
+                                self.worldsend.begin_send(
+                                    c,
+                                    vec![(String::new(), encode(&w))],
+                                    w_frame,
+                                    false,
+                                );
+                                self.catchup
+                                    .begin_remembering(self.buffer.consumed_frame, c);
+                            }
+                        }
 
                         self.authent.get_client_mut(addr)?.state = ClientGameState::Downloading;
                     }
@@ -285,7 +411,7 @@ impl<WORLD: 'static + Serialize, INPUT: Serialize + DeserializeOwned> Server<WOR
     }
 
     fn tcp_connected(&mut self, addr: SocketAddr) {
-        self.authent.tcp_connected(addr, &self.net)
+        self.authent.tcp_connected(addr, &*self.net)
     }
 
     fn tcp_disconnected(&mut self, tcp_addr: SocketAddr) {
@@ -304,6 +430,155 @@ impl<WORLD: 'static + Serialize, INPUT: Serialize + DeserializeOwned> Server<WOR
         s
     }
 
+    /// Number of players in game, including the virtual client
+    pub fn n_players(&self) -> usize {
+        self.authent.iter_playing().count() + self.v_client.is_some() as usize
+    }
+
+    /// Every client in game
+    pub fn players(&self) -> Vec<PlayerInfo> {
+        self.authent
+            .iter_playing()
+            .map(|c| PlayerInfo {
+                name: c.name.clone(),
+                role: c.role,
+                spectator: c.spectator,
+                lag: self.buffer.lag(c.ack),
+            })
+            .collect()
+    }
+
+    /// Sets the role of a player, it is kept if the player reconnects
+    pub fn set_role(&mut self, name: &str, role: Role) {
+        log::info!("{} is now {}", name, role);
+        self.authent.set_role(name.to_string(), role);
+    }
+
+    /// Inputs of the players that are not admin go through the filter before being merged.
+    /// The virtual client and the server are not filtered.
+    pub fn set_input_filter(&mut self, filter: InputFilter<INPUT>) {
+        self.input_filter = Some(filter);
+    }
+
+    /// Disconnects the client with the given name, returns false if there is no such client
+    pub fn kick(&mut self, name: &str) -> bool {
+        let Some(tcp_addr) = self
+            .authent
+            .iter()
+            .find(|c| c.name == name)
+            .map(|c| c.tcp_addr)
+        else {
+            return false;
+        };
+        log::info!("kicking {}", name);
+        self.net.remove_tcp(tcp_addr);
+        self.disconnect(tcp_addr);
+        true
+    }
+
+    /// Kicks the client with the given name and refuses it from now on
+    pub fn ban(&mut self, name: &str) {
+        self.authent.ban(name.to_string());
+        self.kick(name);
+    }
+
+    pub fn unban(&mut self, name: &str) -> bool {
+        self.authent.unban(name)
+    }
+
+    /// Names, roles and bans of the players, to be saved and given back with
+    /// [`Server::set_identities`] after a restart
+    pub fn identities(&self) -> &Identities {
+        self.authent.identities()
+    }
+
+    pub fn set_identities(&mut self, identities: Identities) {
+        self.authent.set_identities(identities);
+    }
+
+    /// Clients able to rebuild a world from its parts are sent the parts of a periodic
+    /// snapshot that they do not already have, then the inputs since the snapshot
+    pub fn set_world_parts(&mut self, world_parts: fn(&WORLD) -> WorldParts) {
+        self.world_parts = Some(world_parts);
+    }
+
+    fn update_baseline(&mut self, world: &WORLD, frame: Frame) {
+        let Some(world_parts) = self.world_parts else {
+            return;
+        };
+        // the world must be up to date with the consumed inputs
+        if frame != self.buffer.consumed_frame {
+            return;
+        }
+        if let Some(ref b) = self.baseline {
+            if frame.0 < b.frame.0 + BASELINE_PERIOD {
+                return;
+            }
+        }
+
+        let parts = world_parts(world);
+        let hashes = parts
+            .iter()
+            .map(|(name, data)| (name.clone(), common::hash_u64(&**data)))
+            .collect();
+        self.baseline = Some(Baseline {
+            frame,
+            parts,
+            hashes,
+            inputs: vec![],
+        });
+    }
+
+    /// Stops producing frames until resumed, clients wait for the server
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Number of frames produced per period, clients catch up on their own
+    pub fn set_time_warp(&mut self, warp: u32) {
+        self.time_warp = warp.max(1);
+    }
+
+    pub fn time_warp(&self) -> u32 {
+        self.time_warp
+    }
+
+    /// Input sent by the server itself, it is merged with the inputs of the next frame
+    pub fn push_input(&mut self, input: &INPUT) {
+        self.push_input_at(self.buffer.consumed_frame.incred(), input);
+    }
+
+    /// Input sent by the server itself, it is merged with the inputs of the given frame.
+    /// Used to stream a recorded session by pushing its inputs ahead of time.
+    pub fn push_input_at(&mut self, frame: Frame, input: &INPUT) {
+        if frame <= self.buffer.consumed_frame {
+            log::error!("server input for {:?} is in the past", frame);
+            return;
+        }
+        self.server_inputs
+            .entry(frame)
+            .or_default()
+            .push(PlayerInput(encode(input)));
+    }
+
+    /// Kicks everyone and restarts the frame count, to be used when the world is replaced
+    pub fn reset(&mut self, frame: Frame) {
+        let names: Vec<String> = self.authent.iter().map(|c| c.name.clone()).collect();
+        for name in names {
+            self.kick(&name);
+        }
+        self.buffer = ServerPlayoutBuffer::new(frame);
+        self.catchup = CatchUp::default();
+        self.worldsend = Default::default();
+        self.next_inputs.clear();
+        self.server_inputs.clear();
+        self.baseline = None;
+    }
+
     fn disconnect(&mut self, tcp_addr: SocketAddr) {
         if let Some(c) = self.authent.disconnected(tcp_addr) {
             log::info!("player {} disconnected", c.name);
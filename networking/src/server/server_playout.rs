@@ -48,6 +48,16 @@ impl ServerPlayoutBuffer {
         }
     }
 
+    /// Returns true if an input of the player was already received for this frame
+    pub fn is_seen(&self, auth: AuthentID, frame: Frame) -> bool {
+        self.dedup.get(&auth).is_some_and(|r| *r.get(frame))
+    }
+
+    /// Inputs issued by the server are not deduplicated, they are sent only once
+    pub fn insert_server_input(&mut self, auth: AuthentID, input: PlayerInput) {
+        self.next.entry(auth).or_default().push(input);
+    }
+
     pub fn lag(&self, f: Frame) -> Option<u64> {
         let lag = self.consumed_frame.0 - f.0;
         if lag < self.past.len() as u64 - 1 {
@@ -0,0 +1,27 @@
+use crate::connections::Packet;
+use std::net::SocketAddr;
+
+/// How the server exchanges packets with the clients.
+/// Implemented over real sockets by [`crate::connections::Connections`] and in memory by
+/// [`crate::memory::MemoryServer`] to test the lockstep under bad network conditions.
+pub trait ServerTransport: Send {
+    fn send_udp(&self, addr: SocketAddr, data: Vec<u8>) -> Option<()>;
+    fn recv_udp(&self) -> Option<Packet>;
+    fn send_tcp(&self, addr: SocketAddr, data: Vec<u8>) -> Option<()>;
+    /// Every tcp message received since the last call, whole
+    fn recv_tcp(&mut self) -> Vec<Packet>;
+    /// Closes the tcp connection
+    fn remove_tcp(&mut self, addr: SocketAddr);
+    /// Returns the new and the deleted tcp connections since the last call
+    fn handle_tcp_conns(&mut self) -> (Vec<SocketAddr>, Vec<SocketAddr>);
+}
+
+/// How a client exchanges packets with the server, see [`ServerTransport`]
+pub trait ClientTransport: Send {
+    fn is_disconnected(&self) -> bool;
+    fn send_udp(&self, data: Vec<u8>) -> Option<()>;
+    fn recv_udp(&self) -> Option<Vec<u8>>;
+    fn send_tcp(&self, data: Vec<u8>) -> Option<()>;
+    /// Every tcp message received since the last call, whole
+    fn recv_tcp(&mut self) -> Vec<Vec<u8>>;
+}
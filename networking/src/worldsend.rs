@@ -1,8 +1,7 @@
 use crate::authent::{Client, ClientGameState};
-use crate::connection_client::ConnectionClient;
-use crate::connections::Connections;
 use crate::packets::{ClientReliablePacket, ServerReliablePacket, WorldDataFragment};
-use crate::{decode, encode, AuthentID, Frame, MAX_WORLDSEND_PACKET_SIZE};
+use crate::transport::{ClientTransport, ServerTransport};
+use crate::{decode, encode, AuthentID, Frame, FromParts, WorldParts, MAX_WORLDSEND_PACKET_SIZE};
 use common::FastMap;
 use serde::de::DeserializeOwned;
 
@@ -14,8 +13,12 @@ enum WorldSendStatus {
 }
 
 struct WorldSendState {
-    data: Vec<u8>,
-    sent: usize,
+    /// Sent one after the other, a full world is a single part with an empty name
+    parts: Vec<(String, Vec<u8>)>,
+    part: usize,
+    sent_in_part: usize,
+    total_size: usize,
+    delta: bool,
     status: WorldSendStatus,
     frame: Frame,
 }
@@ -26,12 +29,22 @@ pub(crate) struct WorldSend {
 }
 
 impl WorldSend {
-    pub fn begin_send(&mut self, c: &Client, data: Vec<u8>, frame: Frame) {
+    /// If `delta` is true, `parts` are the parts of the world that the client does not have
+    pub fn begin_send(
+        &mut self,
+        c: &Client,
+        parts: Vec<(String, Vec<u8>)>,
+        frame: Frame,
+        delta: bool,
+    ) {
         self.send_state.insert(
             c.id,
             WorldSendState {
-                data,
-                sent: 0,
+                total_size: parts.iter().map(|(_, data)| data.len()).sum(),
+                parts,
+                part: 0,
+                sent_in_part: 0,
+                delta,
                 status: WorldSendStatus::ReadyToSend,
                 frame,
             },
@@ -50,7 +63,7 @@ impl WorldSend {
         }
     }
 
-    pub fn update(&mut self, c: &mut Client, net: &Connections) {
+    pub fn update(&mut self, c: &mut Client, net: &dyn ServerTransport) {
         if let Some(state) = self.send_state.get_mut(&c.id) {
             if state.status == WorldSendStatus::Over {
                 self.send_state.remove(&c.id);
@@ -61,15 +74,32 @@ impl WorldSend {
                 return;
             }
 
-            let to_send = MAX_WORLDSEND_PACKET_SIZE.min(state.data.len() - state.sent);
-            let is_over = (to_send < MAX_WORLDSEND_PACKET_SIZE).then_some(state.frame);
+            let (part, data, part_size) = match state.parts.get(state.part) {
+                Some((name, data)) => {
+                    let to_send = MAX_WORLDSEND_PACKET_SIZE.min(data.len() - state.sent_in_part);
+                    let fragment = &data[state.sent_in_part..state.sent_in_part + to_send];
+                    (Some(name.clone()), Vec::from(fragment), data.len())
+                }
+                // nothing to send, only tell the client that the world is over
+                None => (None, vec![], 0),
+            };
+
+            state.sent_in_part += data.len();
+            if state.sent_in_part >= part_size {
+                state.part += 1;
+                state.sent_in_part = 0;
+            }
+            let is_over = (state.part >= state.parts.len()).then_some(state.frame);
 
             net.send_tcp(
                 c.tcp_addr,
                 encode(&ServerReliablePacket::WorldSend(WorldDataFragment {
                     is_over,
-                    data_size: state.data.len(),
-                    data: Vec::from(&state.data[state.sent..state.sent + to_send]),
+                    data_size: state.total_size,
+                    part,
+                    part_size,
+                    delta: state.delta,
+                    data,
                 })),
             );
 
@@ -79,8 +109,6 @@ impl WorldSend {
             } else {
                 log::info!("sending world fragment to {}", c.name);
             }
-
-            state.sent += to_send;
         } else {
             log::error!("updating a non existing world send");
         }
@@ -95,7 +123,8 @@ impl WorldSend {
 pub(crate) enum WorldReceive<W> {
     Downloading {
         datasize: usize,
-        data_so_far: Vec<u8>,
+        received: usize,
+        part: Vec<u8>,
     },
     Finished {
         frame: Frame,
@@ -108,9 +137,8 @@ impl<W> WorldReceive<W> {
     pub fn progress(&self) -> Option<(usize, usize)> {
         match self {
             WorldReceive::Downloading {
-                datasize,
-                data_so_far,
-            } => Some((data_so_far.len(), *datasize)),
+                datasize, received, ..
+            } => Some((*received, *datasize)),
             _ => None,
         }
     }
@@ -120,28 +148,50 @@ impl<W> Default for WorldReceive<W> {
     fn default() -> Self {
         Self::Downloading {
             datasize: 0,
-            data_so_far: vec![],
+            received: 0,
+            part: vec![],
         }
     }
 }
 
 impl<W: DeserializeOwned> WorldReceive<W> {
-    pub fn handle(&mut self, fragment: WorldDataFragment, net: &ConnectionClient) {
+    /// Parts of a delta are added to `known` as soon as they are complete, so that a transfer
+    /// interrupted by a disconnection can be resumed
+    pub fn handle(
+        &mut self,
+        fragment: WorldDataFragment,
+        net: &dyn ClientTransport,
+        known: &mut WorldParts,
+        from_parts: Option<FromParts<W>>,
+    ) {
         if let WorldReceive::Downloading {
             ref mut datasize,
-            ref mut data_so_far,
+            ref mut received,
+            ref mut part,
         } = self
         {
             *datasize = fragment.data_size;
-            if data_so_far.capacity() == 0 {
-                data_so_far.reserve(fragment.data_size)
+            *received += fragment.data.len();
+            if part.capacity() == 0 {
+                part.reserve(fragment.part_size)
             }
-            data_so_far.extend(fragment.data);
+            part.extend(fragment.data);
+
+            if fragment.delta && part.len() >= fragment.part_size {
+                if let Some(name) = fragment.part {
+                    known.insert(name, std::mem::take(part));
+                }
+            }
+
             if let Some(frame) = fragment.is_over {
                 log::info!("received last fragment at {:?}", frame);
                 net.send_tcp(encode(&ClientReliablePacket::WorldAck));
 
-                let d = decode(data_so_far);
+                let d = if fragment.delta {
+                    from_parts.and_then(|from_parts| from_parts(std::mem::take(known)))
+                } else {
+                    decode(part)
+                };
 
                 if let Some(w) = d {
                     *self = WorldReceive::Finished { frame, world: w }
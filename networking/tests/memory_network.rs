@@ -0,0 +1,407 @@
+use common::mods::ModInfo;
+use networking::memory::{MemoryNetwork, NetworkConditions};
+use networking::{
+    Client, ConnectConf, Frame, PollResult, Role, Server, ServerConfiguration, ServerPollResult,
+    VirtualClientConf, WorldParts,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+struct World {
+    tick: u64,
+    /// Sum of the inputs of everyone, weighted by the tick to catch reordered inputs
+    sum: u64,
+}
+
+impl World {
+    fn apply(&mut self, frame: Frame, inputs: impl Iterator<Item = u64>) {
+        assert_eq!(self.tick + 1, frame.0, "inputs were skipped or repeated");
+        self.tick += 1;
+        for inp in inputs {
+            self.sum = self.sum.wrapping_mul(31).wrapping_add(inp * self.tick);
+        }
+    }
+
+    /// Each field is a part, so that a client can be sent the fields it does not have
+    fn parts(&self) -> WorldParts {
+        WorldParts::from([
+            ("sum".to_string(), self.sum.to_le_bytes().to_vec()),
+            ("tick".to_string(), self.tick.to_le_bytes().to_vec()),
+        ])
+    }
+
+    fn from_parts(parts: WorldParts) -> Option<Self> {
+        REBUILT_FROM_PARTS.fetch_add(1, Ordering::SeqCst);
+        let field = |name| {
+            Some(u64::from_le_bytes(
+                parts.get(name)?.as_slice().try_into().ok()?,
+            ))
+        };
+        Some(Self {
+            tick: field("tick")?,
+            sum: field("sum")?,
+        })
+    }
+}
+
+const PERIOD: Duration = Duration::from_millis(10);
+const MAX_STEPS: usize = 30_000;
+
+/// Number of worlds rebuilt by [`World::from_parts`], only the baseline test uses it
+static REBUILT_FROM_PARTS: AtomicUsize = AtomicUsize::new(0);
+
+struct Player {
+    name: String,
+    client: Client<World, u64>,
+    tcp_addr: SocketAddr,
+    token: String,
+    world: Option<World>,
+    disconnected: bool,
+}
+
+/// A server with a virtual client and players over the memory network, every world records its
+/// state at each tick so that they can be compared afterwards
+struct Game {
+    network: MemoryNetwork,
+    server: Server<World, u64>,
+    world: World,
+    players: Vec<Player>,
+    history: BTreeMap<u64, World>,
+    /// Every input merged by the server: the host sends 1 and each player its index plus 2
+    merged_inputs: BTreeSet<u64>,
+}
+
+impl Game {
+    fn new(conditions: NetworkConditions, n_players: usize) -> Self {
+        Self::with_conf(conditions, n_players, |_| {})
+    }
+
+    /// Starts with the default server configuration changed by `conf`
+    fn with_conf(
+        conditions: NetworkConditions,
+        n_players: usize,
+        conf: impl FnOnce(&mut ServerConfiguration),
+    ) -> Self {
+        let network = MemoryNetwork::new(conditions);
+        let world = World { tick: 0, sum: 0 };
+        let mut server_conf = ServerConfiguration {
+            start_frame: Frame(world.tick),
+            period: PERIOD,
+            port: None,
+            virtual_client: Some(VirtualClientConf {
+                name: "host".to_string(),
+            }),
+            version: "v1".to_string(),
+            mods: vec![],
+            always_run: true,
+            default_role: Role::Admin,
+            password: None,
+            spectators_only: false,
+        };
+        conf(&mut server_conf);
+        let server = Server::start_with_transport(server_conf, Box::new(network.server()));
+
+        let mut game = Self {
+            network,
+            server,
+            world,
+            players: vec![],
+            history: BTreeMap::new(),
+            merged_inputs: BTreeSet::new(),
+        };
+        for i in 0..n_players {
+            game.join(format!("player{i}"), networking::generate_token());
+        }
+        game
+    }
+
+    fn join(&mut self, name: String, token: String) {
+        self.join_with(name, token, |_| {});
+    }
+
+    /// Joins with the default configuration changed by `conf`
+    fn join_with(&mut self, name: String, token: String, conf: impl FnOnce(&mut ConnectConf)) {
+        let transport = self.network.connect();
+        let tcp_addr = transport.tcp_addr();
+        let mut connect = ConnectConf {
+            name: name.clone(),
+            addr: Ipv4Addr::LOCALHOST.into(),
+            port: None,
+            frame_buffer_advance: 8,
+            version: "v1".to_string(),
+            mods: vec![],
+            password: None,
+            token: token.clone(),
+            spectator: false,
+        };
+        conf(&mut connect);
+        let client = Client::connect_with_transport(connect, Box::new(transport));
+        self.players.push(Player {
+            name,
+            client,
+            tcp_addr,
+            token,
+            world: None,
+            disconnected: false,
+        });
+    }
+
+    fn step(&mut self) {
+        if let ServerPollResult::Input(inputs) =
+            self.server
+                .poll(&self.world, Frame(self.world.tick), Some(1))
+        {
+            for inp in inputs {
+                self.merged_inputs.extend(inp.inputs.iter().map(|x| x.inp));
+                self.world
+                    .apply(inp.frame, inp.inputs.into_iter().map(|x| x.inp));
+                self.history.insert(self.world.tick, self.world.clone());
+            }
+        }
+
+        for (i, p) in self.players.iter_mut().enumerate() {
+            if p.disconnected {
+                continue;
+            }
+            match p.client.poll(i as u64 + 2) {
+                PollResult::GameWorld(_, w) => p.world = Some(w),
+                PollResult::Input(inputs) => {
+                    let w = p.world.as_mut().expect("inputs before the world");
+                    for inp in inputs {
+                        w.apply(inp.frame, inp.inputs.into_iter().map(|x| x.inp));
+                        assert_eq!(
+                            self.history.get(&w.tick),
+                            Some(&*w),
+                            "{} desynced at tick {}",
+                            p.name,
+                            w.tick
+                        );
+                    }
+                }
+                PollResult::Wait(_) => {}
+                PollResult::Disconnect(reason) => {
+                    log::info!("{} disconnected: {}", p.name, reason);
+                    p.disconnected = true;
+                }
+            }
+        }
+    }
+
+    /// Steps until `done` holds, the number of steps is bounded so that a stuck game fails
+    /// instead of hanging, while a slow machine only takes more steps
+    fn run_until(&mut self, what: &str, done: impl Fn(&Self) -> bool) {
+        for _ in 0..MAX_STEPS {
+            if done(self) {
+                return;
+            }
+            self.step();
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        panic!("gave up waiting for {what}");
+    }
+
+    /// Steps until every connected player reached the tick
+    fn run_until_tick(&mut self, tick: u64) {
+        self.run_until(&format!("tick {tick}"), |g| {
+            (0..g.players.len()).all(|i| g.players[i].disconnected || g.player_tick(i) >= tick)
+        });
+    }
+
+    /// Steps until the player either plays according to the server or was disconnected
+    fn run_until_joined(&mut self, i: usize) {
+        let name = self.players[i].name.clone();
+        self.run_until(&format!("{name} to join"), |g| {
+            g.players[i].disconnected || g.server.players().iter().any(|p| p.name == name)
+        });
+    }
+
+    fn player_tick(&self, i: usize) -> u64 {
+        self.players[i].world.as_ref().map(|w| w.tick).unwrap_or(0)
+    }
+}
+
+#[test]
+fn perfect_network() {
+    let mut game = Game::new(NetworkConditions::default(), 3);
+    game.run_until_tick(50);
+
+    for i in 0..3 {
+        assert!(!game.players[i].disconnected);
+    }
+}
+
+#[test]
+fn latency_jitter_and_loss() {
+    let mut game = Game::new(
+        NetworkConditions {
+            latency: Duration::from_millis(30),
+            jitter: Duration::from_millis(20),
+            loss: 0.2,
+            seed: 42,
+        },
+        3,
+    );
+    game.run_until_tick(50);
+
+    for i in 0..3 {
+        assert!(!game.players[i].disconnected);
+    }
+}
+
+#[test]
+fn disconnect_and_rejoin() {
+    let mut game = Game::new(
+        NetworkConditions {
+            latency: Duration::from_millis(10),
+            ..Default::default()
+        },
+        3,
+    );
+    game.run_until_tick(20);
+
+    game.network.disconnect(game.players[0].tcp_addr);
+    game.run_until("the server to drop player0", |g| {
+        g.players[0].disconnected && g.server.n_players() == 3
+    });
+
+    let tick_before = game.player_tick(1);
+    game.run_until_tick(tick_before + 20);
+    assert!(!game.players[1].disconnected, "the others kept playing");
+
+    let token = game.players[0].token.clone();
+    game.join("player0".to_string(), token);
+    game.run_until_joined(3);
+    game.run_until_tick(tick_before + 40);
+    assert!(!game.players[3].disconnected);
+    assert_eq!(game.server.n_players(), 4);
+}
+
+#[test]
+fn different_mods_are_refused() {
+    let mut game = Game::new(NetworkConditions::default(), 1);
+    game.join_with("modded".to_string(), networking::generate_token(), |c| {
+        c.mods = vec![ModInfo {
+            name: "bigger_trucks".to_string(),
+            version: "1.0".to_string(),
+            hash: 42,
+        }]
+    });
+    game.run_until_joined(0);
+    game.run_until_joined(1);
+
+    assert!(!game.players[0].disconnected);
+    assert!(game.players[1].disconnected);
+    assert_eq!(game.server.n_players(), 2);
+
+    // the refused player did not claim the name
+    game.join("modded".to_string(), networking::generate_token());
+    game.run_until_joined(2);
+    assert!(!game.players[2].disconnected);
+    assert_eq!(game.server.n_players(), 3);
+}
+
+#[test]
+fn wrong_password_is_refused() {
+    let mut game = Game::with_conf(NetworkConditions::default(), 0, |c| {
+        c.password = Some("hunter2".to_string())
+    });
+    game.join("anon".to_string(), networking::generate_token());
+    game.join_with("bob".to_string(), networking::generate_token(), |c| {
+        c.password = Some("hunter3".to_string())
+    });
+    game.run_until_joined(0);
+    game.run_until_joined(1);
+
+    assert!(game.players[0].disconnected, "no password");
+    assert!(game.players[1].disconnected, "wrong password");
+    assert_eq!(game.server.n_players(), 1);
+
+    // the name is still free for whoever knows the password
+    game.join_with("bob".to_string(), networking::generate_token(), |c| {
+        c.password = Some("hunter2".to_string())
+    });
+    game.run_until_joined(2);
+    assert!(!game.players[2].disconnected);
+    assert_eq!(game.server.n_players(), 2);
+}
+
+#[test]
+fn spectator_inputs_are_dropped() {
+    let mut game = Game::new(NetworkConditions::default(), 1);
+    game.join_with("watcher".to_string(), networking::generate_token(), |c| {
+        c.spectator = true
+    });
+    game.run_until_joined(1);
+    game.run_until_tick(30);
+
+    assert!(
+        !game.players[1].disconnected,
+        "the spectator follows the game"
+    );
+    let players = game.server.players();
+    let watcher = players.iter().find(|p| p.name == "watcher").unwrap();
+    assert!(watcher.spectator);
+    assert!(game.merged_inputs.contains(&2), "player0 plays");
+    assert!(!game.merged_inputs.contains(&3), "the watcher only watches");
+}
+
+#[test]
+fn spectators_only_server() {
+    let mut game = Game::with_conf(NetworkConditions::default(), 2, |c| {
+        c.spectators_only = true
+    });
+    game.run_until_joined(0);
+    game.run_until_joined(1);
+    game.run_until_tick(30);
+
+    let players = game.server.players();
+    assert_eq!(players.len(), 2);
+    assert!(players.iter().all(|p| p.spectator));
+    assert!(!game.merged_inputs.contains(&2));
+    assert!(!game.merged_inputs.contains(&3));
+    // the host still plays
+    assert!(game.merged_inputs.contains(&1));
+}
+
+#[test]
+fn resume_download_from_baseline() {
+    let mut game = Game::new(NetworkConditions::default(), 0);
+    game.server.set_world_parts(World::parts);
+    game.run_until_tick(10);
+
+    let token = networking::generate_token();
+    game.join("late".to_string(), token.clone());
+    game.players[0]
+        .client
+        .set_known_world(WorldParts::new(), World::from_parts);
+
+    // the parts are sent one per step, drop the connection once the first one arrived
+    game.run_until("the first part", |g| {
+        !g.players[0].client.known_world().is_empty()
+    });
+    assert!(game.players[0].world.is_none());
+    game.network.disconnect(game.players[0].tcp_addr);
+    game.run_until("late to be dropped", |g| g.players[0].disconnected);
+    let known = game.players[0].client.known_world().clone();
+    assert_eq!(known.keys().collect::<Vec<_>>(), ["sum"]);
+    game.run_until_tick(game.world.tick + 5);
+
+    // only the missing part is sent, the world is rebuilt from the baseline and catches up
+    game.join("late".to_string(), token);
+    game.players[1]
+        .client
+        .set_known_world(known, World::from_parts);
+    game.run_until_joined(1);
+    assert!(!game.players[1].disconnected);
+    assert_eq!(REBUILT_FROM_PARTS.load(Ordering::SeqCst), 1);
+
+    let tick = game.world.tick + 20;
+    game.run_until_tick(tick);
+    let world = game.players[1].world.clone().unwrap();
+    assert!(world.sum != 0, "the inputs since the baseline were applied");
+    assert_eq!(Some(&world), game.history.get(&world.tick));
+}
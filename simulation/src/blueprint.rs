@@ -0,0 +1,559 @@
+//! Blueprints are copies of a part of the road network and of the special buildings around it,
+//! that can be saved to a file and pasted elsewhere as regular world commands.
+
+use crate::map::{
+    BuildingKind, LanePattern, LanePatternBuilder, Map, MapProject, ProjectFilter, ProjectKind,
+    RoadSegmentKind, Zone,
+};
+use crate::souls::goods_company::GoodsCompanyRegistry;
+use crate::world_command::WorldCommand;
+use common::descriptions::BuildingGen;
+use common::saveload::{Encoder, JSON};
+use geom::{vec2, vec3, Polygon, Vec2, Vec3, AABB, OBB};
+use serde::{Deserialize, Serialize};
+
+const BLUEPRINTS_DIR: &str = "world/blueprints";
+/// Pasted intersections closer than this to the existing network are connected to it
+const SNAP_DISTANCE: f32 = 5.0;
+
+/// Half the distance between the two carriageways of a highway in the interchanges
+const CARRIAGEWAY_OFFSET: f32 = 15.0;
+/// Height of the roads passing over the highway in the interchanges
+const BRIDGE_HEIGHT: f32 = 10.0;
+/// Radius of the loops turning left in the interchanges
+const LOOP_RADIUS: f32 = 40.0;
+/// Half the size of the interchanges, their roads end there to be connected to the network
+const INTERCHANGE_EXTENT: f32 = 500.0;
+
+/// Positions are relative to the center of the captured area, and heights are relative to the
+/// terrain so elevated roads stay elevated wherever they are pasted.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Blueprint {
+    pub intersections: Vec<Vec3>,
+    pub roads: Vec<BlueprintRoad>,
+    pub buildings: Vec<BlueprintBuilding>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BlueprintRoad {
+    /// Indices into [`Blueprint::intersections`]
+    pub src: usize,
+    pub dst: usize,
+    /// Elbow of curved roads, see [`RoadSegmentKind::from_elbow`]
+    pub interpoint: Option<Vec2>,
+    pub pattern: LanePattern,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BlueprintBuilding {
+    pub kind: BlueprintBuildingKind,
+    pub obb: OBB,
+    pub zone: Option<Zone>,
+}
+
+/// Companies are saved by name as their ids are not stable between sessions
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum BlueprintBuildingKind {
+    Company(String),
+    RailFreightStation,
+}
+
+/// Grade-separated junctions of a highway going along the x axis, made of one-way ramps merging
+/// into its carriageways so the traffic never has to stop
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum InterchangePreset {
+    /// The highway passes under an avenue, with a signalized ramp terminal on each side
+    Diamond,
+    /// Two highways crossing, left turns are made by looping around
+    Cloverleaf,
+    /// A second highway coming from the north ends on the first one
+    Trumpet,
+}
+
+impl InterchangePreset {
+    pub const ALL: [Self; 3] = [Self::Diamond, Self::Cloverleaf, Self::Trumpet];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Diamond => "Diamond",
+            Self::Cloverleaf => "Cloverleaf",
+            Self::Trumpet => "Trumpet",
+        }
+    }
+}
+
+impl Blueprint {
+    /// Captures the roads with both ends inside `area`, and the companies and freight stations
+    /// centered in it. Houses are left out as they grow on their own.
+    pub fn capture(map: &Map, companies: &GoodsCompanyRegistry, area: AABB) -> Self {
+        let center = area.center();
+        let relative = |p: Vec3| {
+            let ground = map.environment.height(p.xy()).unwrap_or(p.z);
+            (p.xy() - center).z(p.z - ground)
+        };
+
+        let mut bp = Self::default();
+        let mut indices = Vec::new();
+        for kind in map.spatial_map().query(area, ProjectFilter::INTER) {
+            let ProjectKind::Inter(id) = kind else {
+                continue;
+            };
+            let Some(inter) = map.intersections().get(id) else {
+                continue;
+            };
+            if !area.contains(inter.pos.xy()) {
+                continue;
+            }
+            indices.push(id);
+            bp.intersections.push(relative(inter.pos));
+        }
+
+        for road in map.roads().values() {
+            let src = indices.iter().position(|&id| id == road.src);
+            let dst = indices.iter().position(|&id| id == road.dst);
+            let (Some(src), Some(dst)) = (src, dst) else {
+                continue;
+            };
+            let interpoint = match road.segment {
+                RoadSegmentKind::Straight => None,
+                RoadSegmentKind::Curved((from_derivative, _)) => Some(
+                    road.points.first().xy() + from_derivative * std::f32::consts::SQRT_2 - center,
+                ),
+            };
+            bp.roads.push(BlueprintRoad {
+                src,
+                dst,
+                interpoint,
+                pattern: road.pattern(map.lanes()),
+            });
+        }
+
+        for kind in map.spatial_map().query(area, ProjectFilter::BUILDING) {
+            let ProjectKind::Building(id) = kind else {
+                continue;
+            };
+            let Some(b) = map.buildings().get(id) else {
+                continue;
+            };
+            if !area.contains(b.obb.center()) {
+                continue;
+            }
+            let kind = match b.kind {
+                BuildingKind::GoodsCompany(id) => {
+                    BlueprintBuildingKind::Company(companies.descriptions[id].name.clone())
+                }
+                BuildingKind::RailFreightStation => BlueprintBuildingKind::RailFreightStation,
+                BuildingKind::House
+                | BuildingKind::TrainStation
+                | BuildingKind::ExternalTrading => continue,
+            };
+            bp.buildings.push(BlueprintBuilding {
+                kind,
+                obb: OBB::new_corners(b.obb.corners.map(|c| c - center)),
+                zone: b.zone.as_ref().map(|z| {
+                    Zone::new(
+                        Polygon(z.poly.0.iter().map(|&p| p - center).collect()),
+                        z.filldir,
+                    )
+                }),
+            });
+        }
+
+        bp
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.roads.is_empty() && self.buildings.is_empty()
+    }
+
+    /// The commands building the blueprint centered at `pos` and rotated by `cossin`: one
+    /// [`WorldCommand::MapMakeMultipleConnections`] for the roads, then one command per building
+    pub fn commands(
+        &self,
+        map: &Map,
+        companies: &GoodsCompanyRegistry,
+        pos: Vec2,
+        cossin: Vec2,
+    ) -> Vec<WorldCommand> {
+        let place = |p: Vec2| pos + p.rotated_by(cossin);
+
+        let projects = self
+            .intersections
+            .iter()
+            .map(|p| {
+                let xy = place(p.xy());
+                let z = map.environment.height(xy).unwrap_or(0.0) + p.z;
+                let proj = map.project(
+                    xy.z(z),
+                    SNAP_DISTANCE,
+                    ProjectFilter::INTER | ProjectFilter::ROAD,
+                );
+                match proj.kind {
+                    ProjectKind::Inter(_) | ProjectKind::Road(_) => proj,
+                    _ => MapProject::ground(xy.z(z)),
+                }
+            })
+            .collect();
+        let links = self
+            .roads
+            .iter()
+            .map(|r| (r.src, r.dst, r.interpoint.map(place), r.pattern.clone()))
+            .collect();
+
+        let mut commands = vec![WorldCommand::MapMakeMultipleConnections(projects, links)];
+
+        for b in &self.buildings {
+            let (kind, gen) = match b.kind {
+                BlueprintBuildingKind::Company(ref name) => {
+                    let Some(descr) = companies.descriptions.values().find(|d| &d.name == name)
+                    else {
+                        log::warn!("blueprint company {} does not exist anymore", name);
+                        continue;
+                    };
+                    (BuildingKind::GoodsCompany(descr.id), descr.bgen)
+                }
+                BlueprintBuildingKind::RailFreightStation => (
+                    BuildingKind::RailFreightStation,
+                    BuildingGen::NoWalkway {
+                        door_pos: Vec2::ZERO,
+                    },
+                ),
+            };
+            commands.push(WorldCommand::MapBuildSpecialBuilding {
+                pos: OBB::new_corners(b.obb.corners.map(place)),
+                kind,
+                gen,
+                zone: b.zone.as_ref().map(|z| {
+                    Zone::new(
+                        Polygon(z.poly.0.iter().map(|&p| place(p)).collect()),
+                        z.filldir.rotated_by(cossin),
+                    )
+                }),
+            });
+        }
+
+        commands
+    }
+
+    /// The roads of an interchange, with traffic driving on the right
+    pub fn interchange(preset: InterchangePreset) -> Self {
+        let highway = LanePatternBuilder::new()
+            .n_lanes(3)
+            .speed_limit(25.0)
+            .parking(false)
+            .sidewalks(false)
+            .one_way(true)
+            .build();
+        let ramp = LanePatternBuilder::new()
+            .speed_limit(13.0)
+            .parking(false)
+            .sidewalks(false)
+            .one_way(true)
+            .build();
+        let (o, h, e) = (CARRIAGEWAY_OFFSET, BRIDGE_HEIGHT, INTERCHANGE_EXTENT);
+        let c = o + LOOP_RADIUS;
+
+        let mut bp = Self::default();
+        match preset {
+            InterchangePreset::Diamond => {
+                let avenue = LanePatternBuilder::new()
+                    .n_lanes(2)
+                    .speed_limit(13.0)
+                    .parking(false)
+                    .build();
+
+                bp.add_path(
+                    &[
+                        vec3(-e, -o, 0.0),
+                        vec3(-250.0, -o, 0.0),
+                        vec3(250.0, -o, 0.0),
+                        vec3(e, -o, 0.0),
+                    ],
+                    &highway,
+                );
+                bp.add_path(
+                    &[
+                        vec3(e, o, 0.0),
+                        vec3(250.0, o, 0.0),
+                        vec3(-250.0, o, 0.0),
+                        vec3(-e, o, 0.0),
+                    ],
+                    &highway,
+                );
+                bp.add_path(
+                    &[
+                        vec3(0.0, -e, 0.0),
+                        vec3(0.0, -120.0, h),
+                        vec3(0.0, 120.0, h),
+                        vec3(0.0, e, 0.0),
+                    ],
+                    &avenue,
+                );
+                bp.add_path(
+                    &[
+                        vec3(-250.0, -o, 0.0),
+                        vec3(0.0, -120.0, h),
+                        vec3(250.0, -o, 0.0),
+                    ],
+                    &ramp,
+                );
+                bp.add_path(
+                    &[
+                        vec3(250.0, o, 0.0),
+                        vec3(0.0, 120.0, h),
+                        vec3(-250.0, o, 0.0),
+                    ],
+                    &ramp,
+                );
+            }
+            InterchangePreset::Cloverleaf => {
+                // the second highway goes along the y axis over the first one. Each quarter turn
+                // adds a carriageway, the loop turning left from it onto the next carriageway,
+                // and the ramp turning right onto it from the previous one
+                for cossin in [
+                    vec2(1.0, 0.0),
+                    vec2(0.0, 1.0),
+                    vec2(-1.0, 0.0),
+                    vec2(0.0, -1.0),
+                ] {
+                    let (h1, h2) = if cossin.x != 0.0 { (0.0, h) } else { (h, 0.0) };
+                    let rot = |p: Vec3| p.xy().rotated_by(cossin).z(p.z);
+
+                    let carriageway = [
+                        vec3(-e, -o, 0.0),
+                        vec3(-250.0, -o, h1),
+                        vec3(-c, -o, h1),
+                        vec3(c, -o, h1),
+                        vec3(250.0, -o, h1),
+                        vec3(e, -o, 0.0),
+                    ];
+                    bp.add_path(&carriageway.map(rot), &highway);
+                    bp.add_loop(cossin, h1, h2, &ramp);
+                    bp.add_road(
+                        rot(vec3(o, -250.0, h2)),
+                        rot(vec3(250.0, -o, h1)),
+                        Some(vec2(120.0, -120.0).rotated_by(cossin)),
+                        &ramp,
+                    );
+                }
+            }
+            InterchangePreset::Trumpet => {
+                bp.add_path(
+                    &[
+                        vec3(-e, -o, 0.0),
+                        vec3(c, -o, 0.0),
+                        vec3(350.0, -o, 0.0),
+                        vec3(e, -o, 0.0),
+                    ],
+                    &highway,
+                );
+                bp.add_path(
+                    &[
+                        vec3(e, o, 0.0),
+                        vec3(200.0, o, 0.0),
+                        vec3(-200.0, o, 0.0),
+                        vec3(-e, o, 0.0),
+                    ],
+                    &highway,
+                );
+                bp.add_path(&[vec3(-o, e, 0.0), vec3(-o, 250.0, 0.0)], &highway);
+                bp.add_path(&[vec3(o, 250.0, 0.0), vec3(o, e, 0.0)], &highway);
+
+                // left turn from the north: over the highway, then around the loop
+                bp.add_path(
+                    &[vec3(-o, 250.0, 0.0), vec3(-o, 60.0, h), vec3(-o, -60.0, h)],
+                    &ramp,
+                );
+                bp.add_road(
+                    vec3(-o, -60.0, h),
+                    vec3(350.0, -o, 0.0),
+                    Some(vec2(-o, -220.0)),
+                    &ramp,
+                );
+
+                // left turn to the north: around the loop, then over the highway
+                bp.add_loop(vec2(1.0, 0.0), 0.0, 0.8 * h, &ramp);
+                bp.add_path(
+                    &[vec3(o, -c, 0.8 * h), vec3(o, 60.0, h), vec3(o, 250.0, 0.0)],
+                    &ramp,
+                );
+
+                // right turns
+                bp.add_road(
+                    vec3(-o, 250.0, 0.0),
+                    vec3(-200.0, o, 0.0),
+                    Some(vec2(-60.0, 60.0)),
+                    &ramp,
+                );
+                bp.add_road(
+                    vec3(200.0, o, 0.0),
+                    vec3(o, 250.0, 0.0),
+                    Some(vec2(60.0, 60.0)),
+                    &ramp,
+                );
+            }
+        }
+        bp
+    }
+
+    /// Index of the intersection at `p`, added if there is none yet
+    fn add_intersection(&mut self, p: Vec3) -> usize {
+        if let Some(i) = self
+            .intersections
+            .iter()
+            .position(|x| x.xy().is_close(p.xy(), 1.0))
+        {
+            return i;
+        }
+        self.intersections.push(p);
+        self.intersections.len() - 1
+    }
+
+    fn add_road(&mut self, from: Vec3, to: Vec3, interpoint: Option<Vec2>, pattern: &LanePattern) {
+        let src = self.add_intersection(from);
+        let dst = self.add_intersection(to);
+        self.roads.push(BlueprintRoad {
+            src,
+            dst,
+            interpoint,
+            pattern: pattern.clone(),
+        });
+    }
+
+    /// Straight roads going through `points`
+    fn add_path(&mut self, points: &[Vec3], pattern: &LanePattern) {
+        for w in points.windows(2) {
+            self.add_road(w[0], w[1], None, pattern);
+        }
+    }
+
+    /// Three quarters of a circle turning right, from the carriageway going towards +x at
+    /// height `from_z` to the one going towards +y at height `to_z`, both rotated by `cossin`
+    fn add_loop(&mut self, cossin: Vec2, from_z: f32, to_z: f32, pattern: &LanePattern) {
+        let (o, r) = (CARRIAGEWAY_OFFSET, LOOP_RADIUS);
+        let c = o + r;
+        let points = [vec2(c, -o), vec2(c + r, -c), vec2(c, -c - r), vec2(o, -c)];
+        let elbows = [vec2(c + r, -o), vec2(c + r, -c - r), vec2(o, -c - r)];
+
+        for (i, elbow) in elbows.into_iter().enumerate() {
+            let z = |i: usize| from_z + (to_z - from_z) * i as f32 / 3.0;
+            self.add_road(
+                points[i].rotated_by(cossin).z(z(i)),
+                points[i + 1].rotated_by(cossin).z(z(i + 1)),
+                Some(elbow.rotated_by(cossin)),
+                pattern,
+            );
+        }
+    }
+
+    pub fn save(&self, name: &str) -> Option<()> {
+        std::fs::create_dir_all(BLUEPRINTS_DIR).ok()?;
+        JSON::save(self, &format!("blueprints/{name}"))
+    }
+
+    pub fn load(name: &str) -> std::io::Result<Self> {
+        JSON::load(&format!("blueprints/{name}"))
+    }
+
+    /// Names of the saved blueprints
+    pub fn list() -> Vec<String> {
+        let mut names: Vec<String> = common::saveload::walkdir(BLUEPRINTS_DIR.as_ref())
+            .filter(|p| p.extension().is_some_and(|ext| ext == JSON::EXTENSION))
+            .filter_map(|p| Some(p.file_stem()?.to_str()?.to_string()))
+            .collect();
+        names.sort();
+        names
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Blueprint, InterchangePreset};
+    use crate::map::ProjectKind;
+    use crate::souls::goods_company::GoodsCompanyRegistry;
+    use crate::tests::TestCtx;
+    use crate::world_command::WorldCommand;
+    use geom::{vec2, vec3, AABB};
+
+    #[test]
+    fn copy_paste_roads() {
+        let mut test = TestCtx::new();
+        test.build_roads(&[
+            vec3(0.0, 0.0, 0.0),
+            vec3(100.0, 0.0, 0.0),
+            vec3(100.0, 100.0, 0.0),
+        ]);
+
+        let bp = Blueprint::capture(
+            &test.g.map(),
+            &test.g.read::<GoodsCompanyRegistry>(),
+            AABB::new(vec2(-50.0, -10.0), vec2(150.0, 10.0)),
+        );
+        // the corner at (100, 100) is outside
+        assert_eq!(bp.intersections.len(), 2);
+        assert_eq!(bp.roads.len(), 1);
+
+        // pasted rotated by 90° so its first end lands on the existing corner at (100, 100)
+        let commands = bp.commands(
+            &test.g.map(),
+            &test.g.read::<GoodsCompanyRegistry>(),
+            vec2(100.0, 150.0),
+            vec2(0.0, 1.0),
+        );
+        let WorldCommand::MapMakeMultipleConnections(ref projects, _) = commands[0] else {
+            panic!("expected the roads first");
+        };
+        assert!(matches!(projects[0].kind, ProjectKind::Inter(_)));
+
+        let (roads, inters) = (
+            test.g.map().roads().len(),
+            test.g.map().intersections().len(),
+        );
+        test.apply(&commands);
+        assert_eq!(test.g.map().roads().len(), roads + 1);
+        assert_eq!(test.g.map().intersections().len(), inters + 1);
+    }
+
+    #[test]
+    fn interchanges() {
+        for preset in InterchangePreset::ALL {
+            let mut test = TestCtx::new();
+            let bp = Blueprint::interchange(preset);
+            let commands = bp.commands(
+                &test.g.map(),
+                &test.g.read::<GoodsCompanyRegistry>(),
+                vec2(-5000.0, -5000.0),
+                vec2(1.0, 0.0),
+            );
+
+            let roads = test.g.map().roads().len();
+            test.apply(&commands);
+            let map = test.g.map();
+            assert_eq!(map.roads().len(), roads + bp.roads.len(), "{:?}", preset);
+
+            // the ramps join the highway without crossing its lanes: the three lanes continue,
+            // plus one turn to or from the ramp
+            let merges = map
+                .intersections()
+                .values()
+                .filter(|i| i.roads.len() == 3 && i.pos.x < -4000.0)
+                .filter(|i| {
+                    i.roads
+                        .iter()
+                        .all(|&r| map.roads()[r].lanes_iter().all(|(_, k)| k.vehicles()))
+                })
+                .collect::<Vec<_>>();
+            assert!(!merges.is_empty(), "{:?}", preset);
+            for i in merges {
+                let highways = i
+                    .roads
+                    .iter()
+                    .filter(|&&r| map.roads()[r].n_lanes() == 3)
+                    .count();
+                if highways == 2 {
+                    assert_eq!(i.turns().len(), 4, "{:?} at {:?}", preset, i.pos);
+                }
+            }
+        }
+    }
+}
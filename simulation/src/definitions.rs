@@ -0,0 +1,125 @@
+//! Data-driven definitions of the vehicles, wagons, special buildings and costs.
+//!
+//! They are loaded from `assets/definitions.json` and validated at load time, see
+//! [`DefinitionsJSON::validate`]. The companies are defined separately in `assets/companies.json`.
+
+use crate::map::BuildingKind;
+use crate::transportation::train::RailWagonKind;
+use crate::transportation::VehicleKind;
+use crate::utils::resources::Resources;
+use crate::World;
+use common::descriptions::{
+    DefinitionsJSON, LocomotiveDescription, RoadCostDescription, SpecialBuildingDescription,
+    VehicleDescription, WagonDescription, SPECIAL_BUILDING_NAMES, VEHICLE_NAMES, WAGON_NAMES,
+};
+use common::saveload::Encoder;
+
+#[cfg(not(test))]
+pub(crate) const DEFINITIONS_PATH: &str = "assets/definitions.json";
+#[cfg(test)]
+pub(crate) const DEFINITIONS_PATH: &str = "../assets/definitions.json";
+
+pub struct Definitions {
+    /// Indexed by [`VehicleKind`]
+    vehicles: [VehicleDescription; VEHICLE_NAMES.len()],
+    pub locomotive: LocomotiveDescription,
+    /// Indexed like [`WAGON_NAMES`]
+    wagons: [WagonDescription; WAGON_NAMES.len()],
+    /// Indexed like [`SPECIAL_BUILDING_NAMES`]
+    buildings: [SpecialBuildingDescription; SPECIAL_BUILDING_NAMES.len()],
+    pub roads: RoadCostDescription,
+}
+
+impl Definitions {
+    /// Parses and validates the definitions, returns every problem found otherwise
+    pub fn parse(source: &str) -> Result<Self, Vec<String>> {
+        let json: DefinitionsJSON =
+            common::saveload::JSON::decode(source.as_bytes()).map_err(|e| vec![e.to_string()])?;
+        let errors = json.validate();
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        let DefinitionsJSON {
+            mut vehicles,
+            locomotive,
+            mut wagons,
+            mut buildings,
+            roads,
+        } = json;
+
+        // Unwraps ok: validated
+        Ok(Self {
+            vehicles: VEHICLE_NAMES.map(|name| vehicles.remove(name).unwrap()),
+            locomotive,
+            wagons: WAGON_NAMES.map(|name| wagons.remove(name).unwrap()),
+            buildings: SPECIAL_BUILDING_NAMES.map(|name| buildings.remove(name).unwrap()),
+            roads,
+        })
+    }
+
+    pub fn vehicle(&self, kind: VehicleKind) -> &VehicleDescription {
+        &self.vehicles[kind as usize]
+    }
+
+    /// Price of one wagon, the locomotive is priced separately
+    pub fn wagon_price(&self, kind: RailWagonKind) -> i64 {
+        match kind {
+            RailWagonKind::Locomotive => self.locomotive.price,
+            RailWagonKind::Passenger => self.wagons[0].price,
+            RailWagonKind::Freight => self.wagons[1].price,
+        }
+    }
+
+    /// None for the companies, they are described in the `GoodsCompanyRegistry`
+    pub fn building(&self, kind: BuildingKind) -> Option<&SpecialBuildingDescription> {
+        Some(match kind {
+            BuildingKind::House => &self.buildings[0],
+            BuildingKind::RailFreightStation => &self.buildings[1],
+            BuildingKind::TrainStation => &self.buildings[2],
+            BuildingKind::ExternalTrading => &self.buildings[3],
+            BuildingKind::GoodsCompany(_) => return None,
+        })
+    }
+}
+
+impl Default for Definitions {
+    /// Only used before [`init_definitions`] runs
+    fn default() -> Self {
+        Self::parse(include_str!("../../assets/definitions.json"))
+            .expect("embedded definitions are invalid")
+    }
+}
+
+pub fn init_definitions(_: &mut World, res: &mut Resources) {
+    let source = common::mods::load_json(DEFINITIONS_PATH).unwrap();
+    match Definitions::parse(&source) {
+        Ok(defs) => {
+            res.insert(defs);
+        }
+        Err(errors) => panic!("invalid {}:\n  {}", DEFINITIONS_PATH, errors.join("\n  ")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Definitions;
+
+    #[test]
+    fn definitions_are_valid() {
+        let source = common::saveload::load_string(super::DEFINITIONS_PATH).unwrap();
+        if let Err(errors) = Definitions::parse(&source) {
+            panic!("{errors:?}");
+        }
+    }
+
+    #[test]
+    fn errors_are_reported() {
+        let source = common::saveload::load_string(super::DEFINITIONS_PATH)
+            .unwrap()
+            .replace("\"bus\"", "\"buss\"")
+            .replace("\"max_speed\": 50.0", "\"max_speed\": -1.0");
+        let errors = Definitions::parse(&source).err().unwrap();
+        assert_eq!(errors.len(), 3, "{errors:?}");
+    }
+}
@@ -0,0 +1,520 @@
+//! Checks of the items and companies definitions, and the steady state of their production chains.
+//!
+//! A company with all its workers runs one recipe every `complexity` seconds of game time, so the
+//! number of producers needed to keep a consumer busy follows from the recipes alone.
+
+use crate::economy::item::ItemDefinition;
+use crate::utils::time::SECONDS_PER_HOUR;
+use common::descriptions::GoodsCompanyDescriptionJSON;
+use common::saveload::Encoder;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
+
+/// Items the simulation itself needs, loading panics without them.
+/// Electricity is optional: without it, no company feeds the power grid
+const REQUIRED_ITEMS: [&str; 2] = ["job-opening", "bread"];
+/// Items sold by the simulation itself: companies offer jobs
+const PRODUCED_BY_SIMULATION: [&str; 1] = ["job-opening"];
+/// Items bought by the simulation itself: humans buy jobs and food, the grid takes electricity
+const CONSUMED_BY_SIMULATION: [&str; 3] = ["job-opening", "bread", "electricity"];
+
+#[derive(Debug, Default)]
+pub struct AssetReport {
+    /// The assets cannot be loaded with these
+    pub errors: Vec<String>,
+    /// The assets load but the economy might not work as intended
+    pub warnings: Vec<String>,
+    pub chains: Vec<ProductionChain>,
+}
+
+/// Companies needed to keep one company at the end of a chain running at full speed
+#[derive(Debug)]
+pub struct ProductionChain {
+    pub company: String,
+    /// Items produced by the company, per hour of game time
+    pub throughput: Vec<(String, f32)>,
+    /// In depth-first order, from the end of the chain to the raw producers
+    pub links: Vec<ChainLink>,
+}
+
+#[derive(Debug)]
+pub struct ChainLink {
+    /// Number of steps to the end of the chain, starting at 1
+    pub depth: usize,
+    pub company: String,
+    /// Item delivered to the step above
+    pub item: String,
+    /// Number of companies needed for one company at the end of the chain
+    pub ratio: f32,
+    /// Other companies producing the same item, the ratio only accounts for the first producer
+    pub alternatives: Vec<String>,
+}
+
+struct Graph<'a> {
+    companies: &'a [GoodsCompanyDescriptionJSON],
+    /// Index of the companies producing each item, in definition order
+    producers: BTreeMap<&'a str, Vec<usize>>,
+    /// Index of the companies consuming each item, in definition order
+    consumers: BTreeMap<&'a str, Vec<usize>>,
+}
+
+impl AssetReport {
+    /// Checks the contents of `items.json` and `companies.json`
+    pub fn analyze(items_source: &str, companies_source: &str) -> Self {
+        let mut report = Self::default();
+
+        let items: Vec<ItemDefinition> = match common::saveload::JSON::decode(items_source.as_ref())
+        {
+            Ok(x) => x,
+            Err(e) => {
+                report.errors.push(format!("items: {e}"));
+                return report;
+            }
+        };
+        let companies: Vec<GoodsCompanyDescriptionJSON> =
+            match common::saveload::JSON::decode(companies_source.as_ref()) {
+                Ok(x) => x,
+                Err(e) => {
+                    report.errors.push(format!("companies: {e}"));
+                    return report;
+                }
+            };
+
+        let mut item_names = BTreeSet::new();
+        for item in &items {
+            if !item_names.insert(&*item.name) {
+                report
+                    .errors
+                    .push(format!("item {} is defined twice", item.name));
+            }
+        }
+        for name in REQUIRED_ITEMS {
+            if !item_names.contains(name) {
+                report
+                    .errors
+                    .push(format!("item {name} is missing, the simulation needs it"));
+            }
+        }
+
+        let mut company_names = BTreeSet::new();
+        for comp in &companies {
+            if !company_names.insert(&*comp.name) {
+                report
+                    .errors
+                    .push(format!("company {} is defined twice", comp.name));
+            }
+            if comp.recipe.complexity <= 0 {
+                report.errors.push(format!(
+                    "company {}: complexity must be positive, got {}",
+                    comp.name, comp.recipe.complexity
+                ));
+            }
+            if comp.n_workers <= 0 {
+                report.errors.push(format!(
+                    "company {}: n_workers must be positive, got {}",
+                    comp.name, comp.n_workers
+                ));
+            }
+            for (what, list) in [
+                ("consumption", &comp.recipe.consumption),
+                ("production", &comp.recipe.production),
+            ] {
+                for (item, qty) in list {
+                    if !item_names.contains(&**item) {
+                        report.errors.push(format!(
+                            "company {}: unknown item {item} in {what}",
+                            comp.name
+                        ));
+                    }
+                    if *qty <= 0 {
+                        report.errors.push(format!(
+                            "company {}: quantity of {item} in {what} must be positive, got {qty}",
+                            comp.name
+                        ));
+                    }
+                }
+            }
+        }
+
+        if !report.errors.is_empty() {
+            return report;
+        }
+
+        let graph = Graph::new(&companies);
+
+        for item in &items {
+            let name = &*item.name;
+            let produced =
+                graph.producers.contains_key(name) || PRODUCED_BY_SIMULATION.contains(&name);
+            let consumed =
+                graph.consumers.contains_key(name) || CONSUMED_BY_SIMULATION.contains(&name);
+
+            if !produced && !consumed {
+                report
+                    .warnings
+                    .push(format!("item {name} is neither produced nor consumed"));
+            } else if !produced {
+                let consumers = graph
+                    .consumers
+                    .get(name)
+                    .map_or_else(|| "the simulation".to_string(), |c| graph.names(c));
+                report.warnings.push(if item.optout_exttrade {
+                    format!("item {name} is unreachable: consumed by {consumers} but produced by nothing")
+                } else {
+                    format!("item {name} is only imported: consumed by {consumers} but produced by nothing")
+                });
+            }
+        }
+
+        for comp in &companies {
+            for (item, _) in &comp.recipe.production {
+                if !graph.consumers.contains_key(&**item)
+                    && !CONSUMED_BY_SIMULATION.contains(&&**item)
+                {
+                    report.warnings.push(format!(
+                        "company {} produces {item} that nothing consumes",
+                        comp.name
+                    ));
+                }
+            }
+        }
+
+        for cycle in graph.cycles() {
+            report
+                .warnings
+                .push(format!("production cycle between {}", graph.names(&cycle)));
+        }
+
+        report.chains = graph.chains();
+        report
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+impl<'a> Graph<'a> {
+    fn new(companies: &'a [GoodsCompanyDescriptionJSON]) -> Self {
+        let mut producers: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+        let mut consumers: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+        for (i, comp) in companies.iter().enumerate() {
+            for (item, _) in &comp.recipe.production {
+                producers.entry(item.as_str()).or_default().push(i);
+            }
+            for (item, _) in &comp.recipe.consumption {
+                consumers.entry(item.as_str()).or_default().push(i);
+            }
+        }
+        Self {
+            companies,
+            producers,
+            consumers,
+        }
+    }
+
+    fn names(&self, ids: &[usize]) -> String {
+        ids.iter()
+            .map(|&i| &*self.companies[i].name)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Companies consuming what the company produces
+    fn successors(&self, i: usize) -> impl Iterator<Item = usize> + '_ {
+        self.companies[i]
+            .recipe
+            .production
+            .iter()
+            .flat_map(|(item, _)| self.consumers.get(&**item).into_iter().flatten().copied())
+    }
+
+    /// Strongly connected components with more than one company, or a company feeding itself
+    fn cycles(&self) -> Vec<Vec<usize>> {
+        struct Tarjan<'g, 'a> {
+            graph: &'g Graph<'a>,
+            index: Vec<Option<usize>>,
+            lowlink: Vec<usize>,
+            on_stack: Vec<bool>,
+            stack: Vec<usize>,
+            next: usize,
+            cycles: Vec<Vec<usize>>,
+        }
+
+        impl Tarjan<'_, '_> {
+            fn visit(&mut self, v: usize) {
+                self.index[v] = Some(self.next);
+                self.lowlink[v] = self.next;
+                self.next += 1;
+                self.stack.push(v);
+                self.on_stack[v] = true;
+
+                for w in self.graph.successors(v).collect::<Vec<_>>() {
+                    match self.index[w] {
+                        None => {
+                            self.visit(w);
+                            self.lowlink[v] = self.lowlink[v].min(self.lowlink[w]);
+                        }
+                        Some(wi) if self.on_stack[w] => {
+                            self.lowlink[v] = self.lowlink[v].min(wi);
+                        }
+                        Some(_) => {}
+                    }
+                }
+
+                if Some(self.lowlink[v]) != self.index[v] {
+                    return;
+                }
+                let mut component = vec![];
+                while let Some(w) = self.stack.pop() {
+                    self.on_stack[w] = false;
+                    component.push(w);
+                    if w == v {
+                        break;
+                    }
+                }
+                if component.len() > 1 || self.graph.successors(v).any(|w| w == v) {
+                    component.sort_unstable();
+                    self.cycles.push(component);
+                }
+            }
+        }
+
+        let n = self.companies.len();
+        let mut t = Tarjan {
+            graph: self,
+            index: vec![None; n],
+            lowlink: vec![0; n],
+            on_stack: vec![false; n],
+            stack: vec![],
+            next: 0,
+            cycles: vec![],
+        };
+        for v in 0..n {
+            if t.index[v].is_none() {
+                t.visit(v);
+            }
+        }
+        t.cycles.sort();
+        t.cycles
+    }
+
+    /// One chain for every company that consumes something but feeds no other company
+    fn chains(&self) -> Vec<ProductionChain> {
+        let mut chains = vec![];
+        for (i, comp) in self.companies.iter().enumerate() {
+            if comp.recipe.consumption.is_empty() || self.successors(i).next().is_some() {
+                continue;
+            }
+            let mut links = vec![];
+            self.expand(i, 1.0, 1, &mut vec![i], &mut links);
+            chains.push(ProductionChain {
+                company: comp.name.clone(),
+                throughput: comp
+                    .recipe
+                    .production
+                    .iter()
+                    .map(|(item, qty)| (item.clone(), per_hour(comp, *qty)))
+                    .collect(),
+                links,
+            });
+        }
+        chains
+    }
+
+    fn expand(
+        &self,
+        i: usize,
+        count: f32,
+        depth: usize,
+        path: &mut Vec<usize>,
+        links: &mut Vec<ChainLink>,
+    ) {
+        let comp = &self.companies[i];
+        for (item, qty) in &comp.recipe.consumption {
+            let Some(producers) = self.producers.get(&**item) else {
+                continue;
+            };
+            let p = producers[0];
+            if path.contains(&p) {
+                continue;
+            }
+            let producer = &self.companies[p];
+            let produced = producer
+                .recipe
+                .production
+                .iter()
+                .find(|(x, _)| x == item)
+                .map_or(0, |(_, qty)| *qty);
+
+            let ratio = count * per_hour(comp, *qty) / per_hour(producer, produced);
+            links.push(ChainLink {
+                depth,
+                company: producer.name.clone(),
+                item: item.clone(),
+                ratio,
+                alternatives: producers[1..]
+                    .iter()
+                    .map(|&x| self.companies[x].name.clone())
+                    .collect(),
+            });
+
+            path.push(p);
+            self.expand(p, ratio, depth + 1, path, links);
+            path.pop();
+        }
+    }
+}
+
+/// Quantity per hour of game time of a company with all its workers
+fn per_hour(comp: &GoodsCompanyDescriptionJSON, qty: i32) -> f32 {
+    qty as f32 * SECONDS_PER_HOUR as f32 / comp.recipe.complexity as f32
+}
+
+impl Display for AssetReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for e in &self.errors {
+            writeln!(f, "error: {e}")?;
+        }
+        for w in &self.warnings {
+            writeln!(f, "warning: {w}")?;
+        }
+        for chain in &self.chains {
+            writeln!(f)?;
+            write!(f, "{}", chain)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for ProductionChain {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.company)?;
+        for (item, qty) in &self.throughput {
+            write!(f, ", {qty:.1} {item}/h")?;
+        }
+        writeln!(f)?;
+        for link in &self.links {
+            write!(
+                f,
+                "{:indent$}{:.2} x {} for {}",
+                "",
+                link.ratio,
+                link.company,
+                link.item,
+                indent = link.depth * 2
+            )?;
+            if !link.alternatives.is_empty() {
+                write!(f, " (or {})", link.alternatives.join(", "))?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AssetReport;
+
+    const ITEMS: &str = r#"[
+        {"name": "job-opening", "label": "Job opening", "optout_exttrade": true},
+        {"name": "bread", "label": "Bread"},
+        {"name": "electricity", "label": "Electricity"},
+        {"name": "flour", "label": "Flour"},
+        {"name": "cereal", "label": "Cereal"},
+        {"name": "gold", "label": "Gold", "optout_exttrade": true},
+        {"name": "dust", "label": "Dust"}
+    ]"#;
+
+    fn company(name: &str, consumption: &str, production: &str, complexity: i32) -> String {
+        format!(
+            r#"{{"name": "{name}", "bgen": {{"kind": "farm"}}, "kind": "store",
+                "recipe": {{"consumption": {consumption}, "production": {production},
+                "complexity": {complexity}, "storage_multiplier": 5}},
+                "n_workers": 5, "size": 10.0, "asset_location": "", "price": 100}}"#
+        )
+    }
+
+    #[test]
+    fn real_assets_are_valid() {
+        let report = AssetReport::analyze(
+            &common::saveload::load_string(super::super::ITEMS_PATH).unwrap(),
+            &common::saveload::load_string(super::super::COMPANIES_PATH).unwrap(),
+        );
+        assert!(report.is_ok(), "{report}");
+        assert!(!report.chains.is_empty());
+    }
+
+    #[test]
+    fn ratios_and_warnings() {
+        let companies = format!(
+            "[{}]",
+            [
+                company("Bakery", r#"[["flour", 1]]"#, r#"[["bread", 1]]"#, 100),
+                company("Mill", r#"[["cereal", 1]]"#, r#"[["flour", 4]]"#, 200),
+                company("Farm", "[]", r#"[["cereal", 1]]"#, 25),
+                company("Jeweler", r#"[["gold", 1]]"#, "[]", 100),
+                company("Smelter", r#"[["cereal", 1]]"#, r#"[["cereal", 1]]"#, 100),
+            ]
+            .join(",")
+        );
+        let report = AssetReport::analyze(ITEMS, &companies);
+        assert!(report.is_ok(), "{report}");
+
+        let has = |s: &str| report.warnings.iter().any(|w| w.contains(s));
+        assert!(has("item gold is unreachable"), "{report}");
+        assert!(
+            has("item dust is neither produced nor consumed"),
+            "{report}"
+        );
+        assert!(has("production cycle between Smelter"), "{report}");
+
+        let bakery = report
+            .chains
+            .iter()
+            .find(|c| c.company == "Bakery")
+            .unwrap();
+        assert_eq!(bakery.throughput, vec![("bread".to_string(), 36.0)]);
+        // the bakery needs 36 flour/h, a mill makes 72
+        assert_eq!(bakery.links[0].company, "Mill");
+        assert!((bakery.links[0].ratio - 0.5).abs() < 1e-5);
+        // the mill needs 9 cereal/h, a farm makes 144
+        assert_eq!(bakery.links[1].company, "Farm");
+        assert_eq!(bakery.links[1].depth, 2);
+        assert!((bakery.links[1].ratio - 0.5 * 18.0 / 144.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn unknown_items_are_errors() {
+        let companies = format!(
+            "[{}]",
+            company("Bakery", r#"[["flor", 1]]"#, r#"[["bread", 1]]"#, 100)
+        );
+        let report = AssetReport::analyze(ITEMS, &companies);
+        assert_eq!(
+            report.errors,
+            vec!["company Bakery: unknown item flor in consumption".to_string()]
+        );
+    }
+
+    #[test]
+    fn electricity_is_optional() {
+        let items = r#"[
+            {"name": "job-opening", "label": "Job opening", "optout_exttrade": true},
+            {"name": "bread", "label": "Bread"}
+        ]"#;
+        let companies = format!("[{}]", company("Bakery", "[]", r#"[["bread", 1]]"#, 100));
+        let report = AssetReport::analyze(items, &companies);
+        assert!(report.is_ok(), "{report}");
+
+        let report = AssetReport::analyze("[]", &companies);
+        assert_eq!(
+            report.errors[..2],
+            [
+                "item job-opening is missing, the simulation needs it",
+                "item bread is missing, the simulation needs it"
+            ]
+        );
+    }
+}
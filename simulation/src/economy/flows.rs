@@ -0,0 +1,247 @@
+use crate::economy::{ItemID, Trade, TradeTarget, HISTORY_SIZE, LEVEL_FREQS};
+use crate::world::CompanyID;
+use crate::SoulID;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+pub const FLOW_BUCKETS: usize = 8;
+/// Ticks to wait before the next bucket, the flows cover the same span as the first history level
+pub const FLOW_BUCKET_TICKS: u64 = LEVEL_FREQS[0] * HISTORY_SIZE as u64 / FLOW_BUCKETS as u64;
+
+/// Who sold what to whom
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct FlowKey {
+    pub seller: TradeTarget,
+    pub buyer: TradeTarget,
+    pub item: ItemID,
+}
+
+/// Recent trade volumes between the companies, and between the companies and the external market.
+/// Trades with humans and freight stations are not part of the production chains and are ignored.
+#[derive(Default, Serialize, Deserialize)]
+pub struct TradeFlows {
+    flows: BTreeMap<FlowKey, [i64; FLOW_BUCKETS]>,
+    cursor: usize,
+}
+
+impl TradeFlows {
+    pub fn advance(&mut self, tick: u64, trades: &[Trade], job_opening: ItemID) {
+        if tick.is_multiple_of(FLOW_BUCKET_TICKS) {
+            self.cursor = (self.cursor + 1) % FLOW_BUCKETS;
+            let cursor = self.cursor;
+            self.flows.retain(|_, ring| {
+                ring[cursor] = 0;
+                ring.iter().any(|&v| v != 0)
+            });
+        }
+
+        for trade in trades {
+            if trade.qty <= 0 || trade.kind == job_opening {
+                continue;
+            }
+            let in_chain = |target: TradeTarget| {
+                matches!(
+                    target,
+                    TradeTarget::ExternalTrade | TradeTarget::Soul(SoulID::GoodsCompany(_))
+                )
+            };
+            if !in_chain(trade.seller) || !in_chain(trade.buyer) || trade.seller == trade.buyer {
+                continue;
+            }
+            let key = FlowKey {
+                seller: trade.seller,
+                buyer: trade.buyer,
+                item: trade.kind,
+            };
+            let v = &mut self.flows.entry(key).or_insert([0; FLOW_BUCKETS])[self.cursor];
+            *v = v.saturating_add(trade.qty as i64);
+        }
+    }
+
+    /// A soul was removed from the world, its flows are not relevant anymore
+    pub fn remove(&mut self, soul: SoulID) {
+        let soul = TradeTarget::Soul(soul);
+        self.flows
+            .retain(|key, _| key.seller != soul && key.buyer != soul);
+    }
+
+    /// Total quantity traded over the covered span for each flow
+    pub fn iter(&self) -> impl Iterator<Item = (FlowKey, i64)> + '_ {
+        self.flows
+            .iter()
+            .map(|(key, ring)| (*key, ring.iter().sum()))
+    }
+}
+
+/// A company relying on the external market for one of its inputs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bottleneck {
+    pub item: ItemID,
+    pub imported: i64,
+    /// Quantity bought from everyone, including the external market
+    pub total: i64,
+}
+
+/// The production chains currently active in the city, companies are the nodes and the trade
+/// volumes the edges
+#[derive(Default)]
+pub struct ProductionGraph {
+    /// The layer of each company: 0 when it buys from no other company, otherwise one more than
+    /// its deepest supplier
+    pub layers: BTreeMap<CompanyID, usize>,
+    pub edges: Vec<(FlowKey, i64)>,
+    pub bottlenecks: BTreeMap<CompanyID, Vec<Bottleneck>>,
+}
+
+impl ProductionGraph {
+    pub fn new(flows: &TradeFlows) -> Self {
+        let mut graph = Self {
+            edges: flows.iter().filter(|(_, qty)| *qty > 0).collect(),
+            ..Default::default()
+        };
+
+        let company = |target: TradeTarget| match target {
+            TradeTarget::Soul(SoulID::GoodsCompany(id)) => Some(id),
+            _ => None,
+        };
+
+        let mut bought: BTreeMap<(CompanyID, ItemID), (i64, i64)> = BTreeMap::new();
+        for (key, qty) in &graph.edges {
+            for id in [key.seller, key.buyer].into_iter().filter_map(company) {
+                graph.layers.insert(id, 0);
+            }
+            if let Some(buyer) = company(key.buyer) {
+                let (imported, total) = bought.entry((buyer, key.item)).or_default();
+                if key.seller == TradeTarget::ExternalTrade {
+                    *imported += qty;
+                }
+                *total += qty;
+            }
+        }
+
+        for ((company, item), (imported, total)) in bought {
+            if imported > 0 {
+                graph
+                    .bottlenecks
+                    .entry(company)
+                    .or_default()
+                    .push(Bottleneck {
+                        item,
+                        imported,
+                        total,
+                    });
+            }
+        }
+
+        // Longest path relaxation, bounded by the number of nodes so cycles terminate
+        let supplies: Vec<(CompanyID, CompanyID)> = graph
+            .edges
+            .iter()
+            .filter_map(|(key, _)| Some((company(key.seller)?, company(key.buyer)?)))
+            .collect();
+        let max_layer = graph.layers.len().saturating_sub(1);
+        for _ in 0..graph.layers.len() {
+            let mut changed = false;
+            for &(seller, buyer) in &supplies {
+                let layer = (graph.layers[&seller] + 1).min(max_layer);
+                let buyer_layer = graph.layers.get_mut(&buyer).unwrap();
+                if layer > *buyer_layer {
+                    *buyer_layer = layer;
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+
+        graph
+    }
+
+    /// Companies of each layer, in a stable order
+    pub fn columns(&self) -> Vec<Vec<CompanyID>> {
+        let n_layers = self.layers.values().max().map_or(0, |&l| l + 1);
+        let mut columns = vec![vec![]; n_layers];
+        for (&id, &layer) in &self.layers {
+            columns[layer].push(id);
+        }
+        columns
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::economy::Money;
+    use slotmapd::{HopSlotMap, SlotMap};
+
+    fn trade(seller: TradeTarget, buyer: TradeTarget, kind: ItemID, qty: i32) -> Trade {
+        Trade {
+            buyer,
+            seller,
+            qty,
+            kind,
+            money_delta: Money::ZERO,
+        }
+    }
+
+    #[test]
+    fn chain_layers_and_bottlenecks() {
+        let mut items = SlotMap::<ItemID, ()>::with_key();
+        let (cereal, flour, job) = (items.insert(()), items.insert(()), items.insert(()));
+        let mut companies = HopSlotMap::<CompanyID, ()>::with_key();
+        let [farm, mill, bakery] =
+            [(); 3].map(|_| TradeTarget::Soul(SoulID::GoodsCompany(companies.insert(()))));
+        let ext = TradeTarget::ExternalTrade;
+
+        let mut flows = TradeFlows::default();
+        flows.advance(
+            1,
+            &[
+                trade(farm, mill, cereal, 4),
+                trade(mill, bakery, flour, 1),
+                trade(ext, bakery, flour, 3),
+                trade(mill, farm, job, 1),
+            ],
+            job,
+        );
+        flows.advance(2, &[trade(farm, mill, cereal, 2)], job);
+
+        let graph = ProductionGraph::new(&flows);
+        let id = |t: TradeTarget| match t.soul() {
+            SoulID::GoodsCompany(id) => id,
+            _ => unreachable!(),
+        };
+        assert_eq!(
+            graph.columns(),
+            vec![vec![id(farm)], vec![id(mill)], vec![id(bakery)]]
+        );
+        assert_eq!(graph.edges.len(), 3);
+        assert!(graph.edges.contains(&(
+            FlowKey {
+                seller: farm,
+                buyer: mill,
+                item: cereal
+            },
+            6
+        )));
+        assert_eq!(
+            graph.bottlenecks[&id(bakery)],
+            vec![Bottleneck {
+                item: flour,
+                imported: 3,
+                total: 4
+            }]
+        );
+
+        // Cycles still terminate
+        flows.advance(3, &[trade(bakery, farm, flour, 1)], job);
+        assert_eq!(ProductionGraph::new(&flows).layers.len(), 3);
+
+        flows.remove(farm.soul());
+        for _ in 0..FLOW_BUCKETS {
+            flows.advance(0, &[], job);
+        }
+        assert_eq!(flows.iter().count(), 0);
+    }
+}
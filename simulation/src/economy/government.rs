@@ -1,7 +1,10 @@
+use crate::definitions::Definitions;
 use crate::economy::Money;
 use crate::map::{LanePattern, MapProject, MAX_ZONE_AREA};
+use crate::transportation::train::RailWagonKind;
 use crate::world_command::WorldCommand;
 use crate::{BuildingKind, GoodsCompanyRegistry, Simulation};
+use common::descriptions::RoadCostDescription;
 use serde::{Deserialize, Serialize};
 
 /// The government represents the player.
@@ -20,11 +23,15 @@ impl Default for Government {
 
 impl Government {
     pub fn action_cost(action: &WorldCommand, sim: &Simulation) -> Money {
+        let defs = sim.read::<Definitions>();
         Money::new_bucks(match action {
-            WorldCommand::MapBuildHouse(_) => 100,
-            WorldCommand::AddTrain { n_wagons, .. } => 1000 + 100 * (*n_wagons as i64),
+            WorldCommand::MapBuildHouse(_) => defs.building(BuildingKind::House).unwrap().price,
+            WorldCommand::AddTrain { n_wagons, .. } => {
+                defs.locomotive.price
+                    + defs.wagon_price(RailWagonKind::Freight) * (*n_wagons as i64)
+            }
             WorldCommand::MapMakeConnection { from, to, pat, .. } => {
-                Self::connection_cost(from, to, pat)
+                Self::connection_cost(&defs.roads, from, to, pat)
             }
             WorldCommand::UpdateZone {
                 building: bid,
@@ -49,7 +56,7 @@ impl Government {
             WorldCommand::MapMakeMultipleConnections(ref projs, ref links) => {
                 let mut total = 0;
                 for (from, to, _, pat) in links.iter() {
-                    total += Self::connection_cost(&projs[*from], &projs[*to], pat);
+                    total += Self::connection_cost(&defs.roads, &projs[*from], &projs[*to], pat);
                 }
                 total
             }
@@ -66,17 +73,21 @@ impl Government {
                             })
                             .unwrap_or(0)
                 }
-                BuildingKind::RailFreightStation => 1000,
-                BuildingKind::TrainStation => 1000,
-                _ => 0,
+                _ => defs.building(*x).map_or(0, |b| b.price),
             },
             _ => 0,
         })
     }
 
-    fn connection_cost(p1: &MapProject, p2: &MapProject, pat: &LanePattern) -> i64 {
+    fn connection_cost(
+        roads: &RoadCostDescription,
+        p1: &MapProject,
+        p2: &MapProject,
+        pat: &LanePattern,
+    ) -> i64 {
         let dist = p1.pos.distance(p2.pos);
-        50 + ((0.03 * dist) as i64).max(1)
-            * (pat.lanes_forward.len() + pat.lanes_backward.len()) as i64
+        roads.base_price
+            + ((roads.price_per_lane_meter * dist) as i64).max(1)
+                * (pat.lanes_forward.len() + pat.lanes_backward.len()) as i64
     }
 }
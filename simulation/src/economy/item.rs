@@ -6,11 +6,11 @@ use std::ops::Index;
 
 /// ItemDefinition is the definition of an item, as read from the items.json file.
 #[derive(Serialize, Deserialize)]
-struct ItemDefinition {
-    name: String,
-    label: String,
+pub(crate) struct ItemDefinition {
+    pub(crate) name: String,
+    pub(crate) label: String,
     #[serde(default)]
-    optout_exttrade: bool,
+    pub(crate) optout_exttrade: bool,
 }
 
 /// Item is the runtime representation of an item, such as meat, wood, etc.
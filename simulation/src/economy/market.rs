@@ -57,6 +57,14 @@ impl SingleMarket {
     pub fn capital_map(&self) -> &BTreeMap<SoulID, i32> {
         &self.capital
     }
+
+    pub fn buy_orders(&self) -> &BTreeMap<SoulID, BuyOrder> {
+        &self.buy_orders
+    }
+
+    pub fn sell_orders(&self) -> &BTreeMap<SoulID, SellOrder> {
+        &self.sell_orders
+    }
 }
 
 /// Market handles good exchanging between souls themselves and the external market.
@@ -211,6 +219,11 @@ impl Market {
         *v
     }
 
+    /// Trades made by the last call to [`Market::make_trades`]
+    pub fn last_trades(&self) -> &[Trade] {
+        &self.all_trades
+    }
+
     /// Returns a list of buy and sell orders matched together.
     /// A trade updates the buy and sell orders from the market, and the capital of the buyers and sellers.
     /// A trade can only be completed if the seller has enough capital.
@@ -532,6 +545,8 @@ mod tests {
                 asset_location: "".to_string(),
                 price: 0,
                 zone: None,
+                power_consumption: 0,
+                power_production: 0,
             });
 
         companies
@@ -552,6 +567,8 @@ mod tests {
                 asset_location: "".to_string(),
                 price: 0,
                 zone: None,
+                power_consumption: 0,
+                power_production: 0,
             });
 
         let prices = super::calculate_prices(&registry, &companies, 1.0);
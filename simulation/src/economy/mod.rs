@@ -7,6 +7,7 @@
 //! - The market, which is the place where goods are exchanged.
 //! - The government, which is the entity representing the player
 //!
+use crate::definitions::{Definitions, DEFINITIONS_PATH};
 use crate::utils::resources::Resources;
 use crate::World;
 use crate::{GoodsCompanyRegistry, SoulID};
@@ -17,17 +18,23 @@ use std::fmt::{Debug, Display, Formatter};
 use std::iter::Sum;
 use std::ops::{Add, AddAssign, Div, Mul, Neg, SubAssign};
 
+mod analysis;
 mod ecostats;
+mod flows;
 mod government;
 mod item;
 mod market;
+mod power;
 
 use crate::utils::time::{Tick, TICKS_PER_SECOND};
 use crate::world::HumanID;
+pub use analysis::*;
 pub use ecostats::*;
+pub use flows::*;
 pub use government::*;
 pub use item::*;
 pub use market::*;
+pub use power::*;
 
 const WORKER_CONSUMPTION_PER_SECOND: Money = Money::new_cents(1);
 
@@ -175,13 +182,24 @@ const ITEMS_PATH: &str = "../assets/items.json";
 const COMPANIES_PATH: &str = "../assets/companies.json";
 
 pub fn init_market(_: &mut World, res: &mut Resources) {
-    res.write::<ItemRegistry>()
-        .load_item_definitions(&common::saveload::load_string(ITEMS_PATH).unwrap());
+    let items = common::mods::load_json(ITEMS_PATH).unwrap();
+    let companies = common::mods::load_json(COMPANIES_PATH).unwrap();
 
-    res.write::<GoodsCompanyRegistry>().load(
-        &common::saveload::load_string(COMPANIES_PATH).unwrap(),
-        &res.read::<ItemRegistry>(),
-    );
+    let report = AssetReport::analyze(&items, &companies);
+    for w in &report.warnings {
+        log::warn!("{}", w);
+    }
+    if !report.is_ok() {
+        panic!(
+            "invalid items or companies:\n  {}",
+            report.errors.join("\n  ")
+        );
+    }
+
+    res.write::<ItemRegistry>().load_item_definitions(&items);
+
+    res.write::<GoodsCompanyRegistry>()
+        .load(&companies, &res.read::<ItemRegistry>());
 
     let market = Market::new(
         &res.read::<ItemRegistry>(),
@@ -192,6 +210,32 @@ pub fn init_market(_: &mut World, res: &mut Resources) {
     res.insert(stats);
 }
 
+/// Checks the items, companies and definitions the game would load with the active mods
+pub fn check_assets() -> AssetReport {
+    let mut report = match (
+        common::mods::load_json(ITEMS_PATH),
+        common::mods::load_json(COMPANIES_PATH),
+    ) {
+        (Ok(items), Ok(companies)) => AssetReport::analyze(&items, &companies),
+        (Err(e), _) | (_, Err(e)) => AssetReport {
+            errors: vec![e],
+            ..Default::default()
+        },
+    };
+
+    match common::mods::load_json(DEFINITIONS_PATH) {
+        Ok(source) => {
+            if let Err(errors) = Definitions::parse(&source) {
+                report
+                    .errors
+                    .extend(errors.into_iter().map(|e| format!("definitions: {e}")));
+            }
+        }
+        Err(e) => report.errors.push(e),
+    }
+    report
+}
+
 pub fn market_update(world: &mut World, resources: &mut Resources) {
     profiling::scope!("economy::market_update");
     let n_workers = world.humans.len();
@@ -208,6 +252,9 @@ pub fn market_update(world: &mut World, resources: &mut Resources) {
     let trades = m.make_trades();
 
     resources.write::<EcoStats>().advance(tick, trades);
+    resources
+        .write::<TradeFlows>()
+        .advance(tick, trades, job_opening);
 
     for &trade in trades.iter() {
         log::debug!("A trade was made! {:?}", trade);
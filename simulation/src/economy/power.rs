@@ -0,0 +1,134 @@
+use crate::definitions::Definitions;
+use crate::economy::{Market, HISTORY_SIZE, LEVEL_FREQS};
+use crate::map::Map;
+use crate::map_dynamic::BuildingInfos;
+use crate::souls::goods_company::GoodsCompanyRegistry;
+use crate::utils::resources::Resources;
+use crate::utils::time::Tick;
+use crate::{SoulID, World};
+use serde::{Deserialize, Serialize};
+use serde_big_array::BigArray;
+
+/// Productivity left to the companies drawing electricity when the grid delivers nothing
+pub const BROWNOUT_MIN_PRODUCTIVITY: f32 = 0.25;
+
+/// Capacity and consumption of the grid sampled at one frequency level, see [`LEVEL_FREQS`]
+#[derive(Serialize, Deserialize)]
+pub struct PowerHistoryLevel {
+    #[serde(with = "BigArray")]
+    pub capacity: [i64; HISTORY_SIZE],
+    #[serde(with = "BigArray")]
+    pub consumption: [i64; HISTORY_SIZE],
+}
+
+impl Default for PowerHistoryLevel {
+    fn default() -> Self {
+        Self {
+            capacity: [0; HISTORY_SIZE],
+            consumption: [0; HISTORY_SIZE],
+        }
+    }
+}
+
+/// Balance between the electricity the power plants can deliver and the one the buildings draw
+#[derive(Default, Serialize, Deserialize)]
+pub struct PowerGrid {
+    /// Power the plants can deliver right now, in kW
+    pub capacity: i64,
+    /// Power drawn by every building, in kW
+    pub consumption: i64,
+    levels: [PowerHistoryLevel; LEVEL_FREQS.len()],
+    cursors: [usize; LEVEL_FREQS.len()],
+}
+
+impl PowerGrid {
+    /// Share of the consumption that is delivered, in [0; 1]
+    pub fn satisfaction(&self) -> f32 {
+        if self.consumption <= 0 {
+            return 1.0;
+        }
+        (self.capacity as f32 / self.consumption as f32).min(1.0)
+    }
+
+    /// Share of the capacity that is used, the power plants throttle down to it
+    pub fn load_factor(&self) -> f32 {
+        if self.capacity <= 0 {
+            return 0.0;
+        }
+        (self.consumption as f32 / self.capacity as f32).min(1.0)
+    }
+
+    pub fn is_brownout(&self) -> bool {
+        self.consumption > self.capacity
+    }
+
+    /// Productivity multiplier of the companies drawing electricity
+    pub fn brownout_factor(&self) -> f32 {
+        BROWNOUT_MIN_PRODUCTIVITY + (1.0 - BROWNOUT_MIN_PRODUCTIVITY) * self.satisfaction()
+    }
+
+    pub fn cursors(&self) -> &[usize] {
+        &self.cursors
+    }
+
+    pub fn history(&self, level: usize) -> &PowerHistoryLevel {
+        &self.levels[level]
+    }
+
+    fn advance(&mut self, tick: u64) {
+        for ((level, c), freq) in self
+            .levels
+            .iter_mut()
+            .zip(self.cursors.iter_mut())
+            .zip(&LEVEL_FREQS)
+        {
+            if tick.is_multiple_of(*freq) {
+                *c = (*c + 1) % HISTORY_SIZE;
+            }
+            level.capacity[*c] = self.capacity;
+            level.consumption[*c] = self.consumption;
+        }
+    }
+}
+
+pub fn power_grid_update(world: &mut World, res: &mut Resources) {
+    profiling::scope!("economy::power_grid_update");
+    let tick = res.read::<Tick>().0;
+
+    let (capacity, consumption) = {
+        let map = res.read::<Map>();
+        let registry = res.read::<GoodsCompanyRegistry>();
+        let defs = res.read::<Definitions>();
+        let market = res.read::<Market>();
+        let binfos = res.read::<BuildingInfos>();
+
+        let consumption = map
+            .buildings()
+            .values()
+            .map(|b| b.kind.power_consumption(&registry, &defs) as i64)
+            .sum::<i64>();
+
+        let capacity = world
+            .companies
+            .iter()
+            .filter(|(_, c)| c.comp.power_production > 0)
+            .filter_map(|(id, c)| {
+                let b = map.buildings().get(c.comp.building)?;
+                if binfos.is_flooded(b.id)
+                    || !c.comp.recipe.has_inputs(SoulID::GoodsCompany(id), &market)
+                {
+                    return None;
+                }
+                let capacity = c.comp.capacity(c.workers.0.len(), b.zone.as_ref());
+                Some((c.comp.power_production as f32 * capacity) as i64)
+            })
+            .sum::<i64>();
+
+        (capacity, consumption)
+    };
+
+    let mut grid = res.write::<PowerGrid>();
+    grid.capacity = capacity;
+    grid.consumption = consumption;
+    grid.advance(tick);
+}
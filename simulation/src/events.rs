@@ -0,0 +1,181 @@
+//! Events the player should know about, emitted by the simulation systems.
+//!
+//! Events of the same kind are throttled: emitting one again while the previous one is recent
+//! only bumps its count. Problems that are normal for a short while (a company waiting for a
+//! delivery) are reported through [`Events::condition`] and only emitted once they last.
+
+use crate::economy::{Government, ItemID, Money};
+use crate::map::{BuildingID, IntersectionID};
+use crate::utils::resources::Resources;
+use crate::utils::time::{GameInstant, GameTime};
+use crate::world::CompanyID;
+use crate::World;
+use geom::Vec3;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+
+/// Number of events kept, the oldest are dropped first
+pub const MAX_EVENTS: usize = 100;
+/// Game seconds during which a repeated event is merged with the previous one
+pub const EVENT_THROTTLE: f64 = GameTime::HOUR as f64;
+/// Game seconds a condition must hold before its event is emitted
+pub const CONDITION_DELAY: f64 = GameTime::HOUR as f64;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum EventKind {
+    /// Vehicles are stuck waiting for each other
+    Gridlock(IntersectionID),
+    /// A staffed company cannot produce because an input is missing
+    MissingInput {
+        company: CompanyID,
+        building: BuildingID,
+        item: ItemID,
+    },
+    /// A rail freight station has goods to ship but no freight train is available
+    NoFreightTrain(BuildingID),
+    NegativeBudget,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Event {
+    pub kind: EventKind,
+    /// Where it happened, None for city-wide events
+    pub pos: Option<Vec3>,
+    pub first: GameInstant,
+    pub last: GameInstant,
+    /// How many times it was emitted, repeated events are merged
+    pub count: u32,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct Events {
+    /// Ordered by last emission, most recent last
+    events: VecDeque<Event>,
+    /// When each active condition started, and when it was last reported
+    conditions: BTreeMap<EventKind, (GameInstant, GameInstant)>,
+}
+
+impl Events {
+    pub fn emit(&mut self, kind: EventKind, pos: Option<Vec3>, time: &GameTime) {
+        let now = time.instant();
+        if let Some(i) = self.events.iter().rposition(|e| e.kind == kind) {
+            if self.events[i].last.elapsed(time) < EVENT_THROTTLE {
+                let mut e = self.events.remove(i).unwrap(); // Unwrap ok: i is in bounds
+                e.last = now;
+                e.count += 1;
+                e.pos = pos.or(e.pos);
+                self.events.push_back(e);
+                return;
+            }
+        }
+
+        log::info!("event: {:?}", kind);
+        self.events.push_back(Event {
+            kind,
+            pos,
+            first: now,
+            last: now,
+            count: 1,
+        });
+        if self.events.len() > MAX_EVENTS {
+            self.events.pop_front();
+        }
+    }
+
+    /// Reports whether a problem is ongoing, its event is emitted once it lasted long enough
+    pub fn condition(&mut self, kind: EventKind, active: bool, pos: Option<Vec3>, time: &GameTime) {
+        if !active {
+            self.conditions.remove(&kind);
+            return;
+        }
+        let now = time.instant();
+        let (since, last_seen) = self.conditions.entry(kind).or_insert((now, now));
+        *last_seen = now;
+        if since.elapsed(time) >= CONDITION_DELAY {
+            *since = now;
+            self.emit(kind, pos, time);
+        }
+    }
+
+    /// Most recent last
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &Event> + '_ {
+        self.events.iter()
+    }
+
+    /// Events emitted since `time`, most recent first
+    pub fn since(&self, time: GameInstant) -> impl Iterator<Item = &Event> + '_ {
+        self.events.iter().rev().take_while(move |e| e.last >= time)
+    }
+
+    /// Forgets the conditions that are not reported anymore, for example because their
+    /// company was removed
+    fn forget_stale_conditions(&mut self, time: &GameTime) {
+        self.conditions
+            .retain(|_, (_, last_seen)| last_seen.elapsed(time) < CONDITION_DELAY);
+    }
+}
+
+pub fn events_system(_: &mut World, resources: &mut Resources) {
+    profiling::scope!("events::events_system");
+    let time = resources.read::<GameTime>();
+    if !time.tick(10) {
+        return;
+    }
+    let mut events = resources.write::<Events>();
+    let negative = resources.read::<Government>().money < Money::ZERO;
+    events.condition(EventKind::NegativeBudget, negative, None, &time);
+    events.forget_stale_conditions(&time);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(timestamp: f64) -> GameTime {
+        GameTime::new(0.0, timestamp)
+    }
+
+    #[test]
+    fn throttling_merges_repeated_events() {
+        let mut events = Events::default();
+        events.emit(EventKind::NegativeBudget, None, &at(0.0));
+        events.emit(EventKind::NegativeBudget, None, &at(10.0));
+        assert_eq!(events.iter().count(), 1);
+        assert_eq!(events.iter().next().unwrap().count, 2);
+
+        events.emit(EventKind::NegativeBudget, None, &at(10.0 + EVENT_THROTTLE));
+        assert_eq!(events.iter().count(), 2);
+        assert_eq!(
+            events
+                .since(GameInstant {
+                    timestamp: 11.0 + EVENT_THROTTLE
+                })
+                .count(),
+            0
+        );
+
+        for i in 0..2 * MAX_EVENTS {
+            events.emit(
+                EventKind::NoFreightTrain(BuildingID::default()),
+                None,
+                &at(i as f64 * EVENT_THROTTLE),
+            );
+        }
+        assert_eq!(events.iter().count(), MAX_EVENTS);
+    }
+
+    #[test]
+    fn conditions_must_last() {
+        let mut events = Events::default();
+        let kind = EventKind::NegativeBudget;
+        events.condition(kind, true, None, &at(0.0));
+        events.condition(kind, false, None, &at(1.0));
+        events.condition(kind, true, None, &at(2.0));
+        assert_eq!(events.iter().count(), 0);
+        events.condition(kind, true, None, &at(2.0 + CONDITION_DELAY));
+        assert_eq!(events.iter().count(), 1);
+
+        events.forget_stale_conditions(&at(3.0 + 2.0 * CONDITION_DELAY));
+        assert!(events.conditions.is_empty());
+    }
+}
@@ -1,11 +1,17 @@
-use crate::economy::{init_market, market_update, EcoStats, Government, ItemRegistry, Market};
+use crate::definitions::{init_definitions, Definitions};
+use crate::economy::{
+    init_market, market_update, power_grid_update, EcoStats, Government, ItemRegistry, Market,
+    PowerGrid, TradeFlows,
+};
+use crate::events::{events_system, Events};
 use crate::map::Map;
 use crate::map_dynamic::{
-    dispatch_system, itinerary_update, routing_changed_system, routing_update_system,
+    dispatch_system, itinerary_update, routing_changed_system, routing_update_system, water_update,
     BuildingInfos, Dispatcher, ParkingManagement,
 };
 use crate::multiplayer::MultiplayerState;
 use crate::physics::coworld_synchronize;
+use crate::scripting::{run_scripts, Scripts};
 use crate::souls::freight_station::freight_station_system;
 use crate::souls::goods_company::{company_system, GoodsCompanyRegistry};
 use crate::souls::human::update_decision_system;
@@ -31,6 +37,7 @@ use serde::Serialize;
 pub fn init() {
     register_system("dispatch_system", dispatch_system);
     register_system("update_decision_system", update_decision_system);
+    register_system("power_grid_update", power_grid_update);
     register_system("company_system", company_system);
     register_system("pedestrian_decision_system", pedestrian_decision_system);
     register_system("coworld_synchronize", coworld_synchronize);
@@ -41,12 +48,16 @@ pub fn init() {
     register_system("routing_update_system", routing_update_system);
     register_system("itinerary_update", itinerary_update);
     register_system("market_update", market_update);
+    register_system("events_system", events_system);
     register_system("train_reservations_update", train_reservations_update);
     register_system("freight_station", freight_station_system);
     register_system("random_vehicles", random_vehicles_update);
+    register_system("water_update", water_update);
 
     register_system_sim("add_souls_to_empty_buildings", add_souls_to_empty_buildings);
+    register_system_sim("run_scripts", run_scripts);
 
+    register_resource_noserialize::<Definitions>();
     register_resource_noserialize::<GoodsCompanyRegistry>();
     register_resource_noserialize::<ItemRegistry>();
     register_resource_noserialize::<ParCommandBuffer<VehicleEnt>>();
@@ -59,6 +70,7 @@ pub fn init() {
     register_resource_noinit::<EcoStats, Bincode>("ecostats");
     register_resource_noinit::<SimulationOptions, Bincode>("simoptions");
 
+    register_init(init_definitions);
     register_init(init_market);
 
     register_resource_default::<MultiplayerState, Bincode>("multiplayer_state");
@@ -67,6 +79,9 @@ pub fn init() {
     register_resource_default::<Map, Bincode>("map");
     register_resource_default::<TrainReservations, Bincode>("train_reservations");
     register_resource_default::<Government, Bincode>("government");
+    register_resource_default::<PowerGrid, Bincode>("power_grid");
+    register_resource_default::<TradeFlows, Bincode>("trade_flows");
+    register_resource_default::<Events, Bincode>("events");
     register_resource_default::<ParkingManagement, Bincode>("pmanagement");
     register_resource_default::<BuildingInfos, Bincode>("binfos");
     register_resource::<GameTime, Bincode>("game_time", || {
@@ -76,6 +91,7 @@ pub fn init() {
     register_resource::<RandProvider, Bincode>("randprovider", || RandProvider::new(RNG_SEED));
     register_resource_default::<Dispatcher, Bincode>("dispatcher");
     register_resource_default::<Replay, JSON>("replay");
+    register_resource_default::<Scripts, Bincode>("scripts");
 }
 
 pub struct InitFunc {
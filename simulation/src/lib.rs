@@ -1,6 +1,7 @@
 #![allow(clippy::too_many_arguments)]
 #![allow(clippy::type_complexity)]
 
+use crate::map::procgen::heightmap::WorldGenOptions;
 use crate::map::{BuildingKind, Map};
 use crate::map_dynamic::{Itinerary, ItineraryLeader};
 use crate::physics::CollisionWorld;
@@ -9,6 +10,7 @@ use crate::souls::add_souls_to_empty_buildings;
 use crate::souls::goods_company::GoodsCompanyRegistry;
 use crate::utils::resources::{Ref, RefMut, Resources};
 use crate::world_command::WorldCommand;
+use common::mods::ModInfo;
 use common::saveload::Encoder;
 use derive_more::{From, TryInto};
 use geom::Vec3;
@@ -35,12 +37,16 @@ extern crate egui_inspect;
 #[macro_use]
 extern crate log as extern_log;
 
+pub mod blueprint;
+pub mod definitions;
 pub mod economy;
+pub mod events;
 pub mod init;
 pub mod map;
 pub mod map_dynamic;
 pub mod multiplayer;
 pub mod physics;
+pub mod scripting;
 pub mod souls;
 #[cfg(test)]
 mod tests;
@@ -112,10 +118,15 @@ pub struct Simulation {
 const RNG_SEED: u64 = 123;
 const VERSION: &str = include_str!("../../VERSION");
 
-#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SimulationOptions {
     pub terrain_size: u16,
     pub save_replay: bool,
+    #[serde(default)]
+    pub worldgen: WorldGenOptions,
+    /// Mods the data was loaded with, in load order
+    #[serde(default)]
+    pub mods: Vec<ModInfo>,
 }
 
 impl Default for SimulationOptions {
@@ -123,6 +134,8 @@ impl Default for SimulationOptions {
         SimulationOptions {
             terrain_size: 50,
             save_replay: true,
+            worldgen: WorldGenOptions::default(),
+            mods: common::mods::active_infos(),
         }
     }
 }
@@ -178,7 +191,7 @@ impl Simulation {
             resources: Default::default(),
         };
 
-        info!("Seed is {}", RNG_SEED);
+        info!("Seed is {}", opts.worldgen.seed);
         info!("{:?}", opts);
 
         unsafe {
@@ -269,18 +282,50 @@ impl Simulation {
     }
 
     pub fn hashes(&self) -> BTreeMap<String, u64> {
-        let mut hashes = BTreeMap::new();
+        self.parts()
+            .into_iter()
+            .map(|(name, data)| (name, common::hash_u64(&*data)))
+            .collect()
+    }
+
+    /// Serializes the world and each resource separately, so that only the parts that changed
+    /// can be sent over the network
+    pub fn parts(&self) -> BTreeMap<String, Vec<u8>> {
+        let mut parts = BTreeMap::new();
         let ser = common::saveload::Bincode::encode(&self.world).unwrap();
-        hashes.insert("world".to_string(), common::hash_u64(&*ser));
+        parts.insert("world".to_string(), ser);
 
         unsafe {
             for l in &SAVELOAD_FUNCS {
-                let v = (l.save)(self);
-                hashes.insert(l.name.to_string(), common::hash_u64(&*v));
+                parts.insert(l.name.to_string(), (l.save)(self));
             }
         }
 
-        hashes
+        parts
+    }
+
+    /// Inverse of [`Simulation::parts`], missing resources are left to their default value
+    pub fn from_parts(mut parts: BTreeMap<String, Vec<u8>>) -> Option<Simulation> {
+        let world = common::saveload::Bincode::decode(&parts.remove("world")?).ok()?;
+
+        let mut sim = Simulation {
+            world: World::default(),
+            resources: Default::default(),
+        };
+
+        unsafe {
+            for s in &INIT_FUNCS {
+                (s.f)(&mut sim);
+            }
+            sim.world = world;
+            for l in &SAVELOAD_FUNCS {
+                if let Some(data) = parts.remove(l.name) {
+                    (l.load)(&mut sim, data);
+                }
+            }
+        }
+
+        Some(sim)
     }
 
     pub fn load_replay_from_disk(save_name: &str) -> Option<Replay> {
@@ -439,6 +484,14 @@ impl<'de> Deserialize<'de> for Simulation {
             }
         }
 
+        if let Ok(opts) = sim.resources.try_read::<SimulationOptions>() {
+            if let Some(mismatch) =
+                common::mods::mismatch(&opts.mods, &common::mods::active_infos())
+            {
+                log::warn!("save was made with {}", mismatch);
+            }
+        }
+
         log::info!(
             "took {}s to deserialize in total",
             t.elapsed().as_secs_f32()
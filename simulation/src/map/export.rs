@@ -0,0 +1,405 @@
+//! Export of the map to formats that can be read by external tools.
+//!
+//! Two formats are supported:
+//! - [`MapExportFormat::GeoJSON`]: a `FeatureCollection` that can be opened by GIS tools.
+//!   Every feature has a `layer` property (`intersection`, `road`, `lane`, `turn`, `building` or `lot`).
+//! - [`MapExportFormat::Graph`]: the [`MapGraph`] JSON format documented below, easier to process
+//!   programmatically.
+//!
+//! Coordinates are in map space, in meters: x goes east, y goes north and z is the elevation.
+//! Ids are the raw slotmap keys (`index | version << 32`) so they are stable across exports of the
+//! same save. Everything is exported in slot order so that two exports can be diffed line by line.
+
+use crate::map::{BuildingKind, LaneKind, LightPolicy, Map, TurnKind, TurnPolicy};
+use crate::souls::goods_company::GoodsCompanyRegistry;
+use geom::Vec3;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use slotmapd::Key;
+use std::str::FromStr;
+
+/// Version of the [`MapGraph`] format, bumped on breaking changes
+pub const MAP_GRAPH_VERSION: u32 = 1;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MapExportFormat {
+    GeoJSON,
+    Graph,
+}
+
+impl FromStr for MapExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "geojson" => Ok(Self::GeoJSON),
+            "graph" | "json" => Ok(Self::Graph),
+            _ => Err(format!(
+                "unknown export format {s}, expected geojson or graph"
+            )),
+        }
+    }
+}
+
+/// Road network and buildings of a map as a plain graph
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MapGraph {
+    /// Always [`MAP_GRAPH_VERSION`]
+    pub version: u32,
+    pub intersections: Vec<GraphIntersection>,
+    pub roads: Vec<GraphRoad>,
+    pub lanes: Vec<GraphLane>,
+    pub turns: Vec<GraphTurn>,
+    pub buildings: Vec<GraphBuilding>,
+    pub lots: Vec<GraphLot>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GraphIntersection {
+    pub id: u64,
+    pub pos: Vec3,
+    /// Connected roads, sorted by angle
+    pub roads: Vec<u64>,
+    pub turn_policy: TurnPolicy,
+    pub light_policy: LightPolicy,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GraphRoad {
+    pub id: u64,
+    /// Source intersection
+    pub src: u64,
+    /// Destination intersection
+    pub dst: u64,
+    pub width: f32,
+    pub length: f32,
+    /// Center line, from src to dst
+    pub points: Vec<Vec3>,
+    /// Lanes from the right side of the road (looking from src to dst) to the left side
+    pub lanes: Vec<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GraphLane {
+    pub id: u64,
+    pub road: u64,
+    /// The lane goes from the src intersection to the dst intersection
+    pub src: u64,
+    pub dst: u64,
+    pub kind: LaneKind,
+    /// In m/s
+    pub speed_limit: f32,
+    pub length: f32,
+    pub points: Vec<Vec3>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GraphTurn {
+    pub intersection: u64,
+    pub src_lane: u64,
+    pub dst_lane: u64,
+    /// Crosswalks can be walked in both directions
+    pub bidirectional: bool,
+    pub kind: TurnKind,
+    pub points: Vec<Vec3>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GraphBuilding {
+    pub id: u64,
+    /// One of House, RailFreightStation, TrainStation, ExternalTrading or GoodsCompany
+    pub kind: String,
+    /// Name of the company for GoodsCompany buildings
+    pub company: Option<String>,
+    pub door_pos: Vec3,
+    /// The four corners of the building footprint
+    pub footprint: Vec<Vec3>,
+    /// Polygon of the zone, if any
+    pub zone: Option<Vec<Vec3>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GraphLot {
+    pub id: u64,
+    /// Road along which the lot was generated
+    pub road: u64,
+    pub footprint: Vec<Vec3>,
+}
+
+fn key(k: impl Key) -> u64 {
+    k.data().as_ffi()
+}
+
+impl MapGraph {
+    pub fn new(map: &Map, companies: &GoodsCompanyRegistry) -> Self {
+        let intersections = map
+            .intersections
+            .values()
+            .map(|i| GraphIntersection {
+                id: key(i.id),
+                pos: i.pos,
+                roads: i.roads.iter().map(|&r| key(r)).collect(),
+                turn_policy: i.turn_policy,
+                light_policy: i.light_policy,
+            })
+            .collect();
+
+        let roads = map
+            .roads
+            .values()
+            .map(|r| GraphRoad {
+                id: key(r.id),
+                src: key(r.src),
+                dst: key(r.dst),
+                width: r.width,
+                length: r.length(),
+                points: r.points.as_slice().to_vec(),
+                lanes: r.lanes_iter().map(|(id, _)| key(id)).collect(),
+            })
+            .collect();
+
+        let lanes = map
+            .lanes
+            .values()
+            .map(|l| GraphLane {
+                id: key(l.id),
+                road: key(l.parent),
+                src: key(l.src),
+                dst: key(l.dst),
+                kind: l.kind,
+                speed_limit: l.speed_limit,
+                length: l.points.length(),
+                points: l.points.as_slice().to_vec(),
+            })
+            .collect();
+
+        let turns = map
+            .intersections
+            .values()
+            .flat_map(|i| i.turns())
+            .map(|t| GraphTurn {
+                intersection: key(t.id.parent),
+                src_lane: key(t.id.src),
+                dst_lane: key(t.id.dst),
+                bidirectional: t.id.bidirectional,
+                kind: t.kind,
+                points: t.points.as_slice().to_vec(),
+            })
+            .collect();
+
+        let buildings = map
+            .buildings
+            .values()
+            .map(|b| {
+                let (kind, company) = match b.kind {
+                    BuildingKind::GoodsCompany(id) => (
+                        "GoodsCompany".to_string(),
+                        companies.descriptions.get(id).map(|d| d.name.clone()),
+                    ),
+                    k => (format!("{k:?}"), None),
+                };
+                GraphBuilding {
+                    id: key(b.id),
+                    kind,
+                    company,
+                    door_pos: b.door_pos,
+                    footprint: b.obb.corners.iter().map(|c| c.z(b.height)).collect(),
+                    zone: b
+                        .zone
+                        .as_ref()
+                        .map(|z| z.poly.iter().map(|c| c.z(b.height)).collect()),
+                }
+            })
+            .collect();
+
+        let lots = map
+            .lots
+            .values()
+            .map(|l| GraphLot {
+                id: key(l.id),
+                road: key(l.parent),
+                footprint: l.shape.corners.iter().map(|c| c.z(l.height)).collect(),
+            })
+            .collect();
+
+        Self {
+            version: MAP_GRAPH_VERSION,
+            intersections,
+            roads,
+            lanes,
+            turns,
+            buildings,
+            lots,
+        }
+    }
+
+    /// Converts the graph to a GeoJSON `FeatureCollection`
+    pub fn to_geojson(&self) -> Value {
+        fn line(points: &[Vec3]) -> Value {
+            json!({
+                "type": "LineString",
+                "coordinates": points.iter().map(|p| [p.x, p.y, p.z]).collect::<Vec<_>>(),
+            })
+        }
+
+        fn polygon(points: &[Vec3]) -> Value {
+            let mut ring: Vec<_> = points.iter().map(|p| [p.x, p.y, p.z]).collect();
+            if let Some(&first) = ring.first() {
+                ring.push(first);
+            }
+            json!({
+                "type": "Polygon",
+                "coordinates": [ring],
+            })
+        }
+
+        fn feature(geometry: Value, mut properties: Value, layer: &str) -> Value {
+            properties["layer"] = json!(layer);
+            json!({
+                "type": "Feature",
+                "geometry": geometry,
+                "properties": properties,
+            })
+        }
+
+        let mut features = vec![];
+
+        for i in &self.intersections {
+            features.push(feature(
+                json!({
+                    "type": "Point",
+                    "coordinates": [i.pos.x, i.pos.y, i.pos.z],
+                }),
+                json!({
+                    "id": i.id,
+                    "roads": i.roads,
+                    "light_policy": i.light_policy,
+                    "turn_policy": i.turn_policy,
+                }),
+                "intersection",
+            ));
+        }
+
+        for r in &self.roads {
+            features.push(feature(
+                line(&r.points),
+                json!({
+                    "id": r.id,
+                    "src": r.src,
+                    "dst": r.dst,
+                    "width": r.width,
+                    "length": r.length,
+                    "lanes": r.lanes,
+                }),
+                "road",
+            ));
+        }
+
+        for l in &self.lanes {
+            features.push(feature(
+                line(&l.points),
+                json!({
+                    "id": l.id,
+                    "road": l.road,
+                    "src": l.src,
+                    "dst": l.dst,
+                    "kind": l.kind,
+                    "speed_limit": l.speed_limit,
+                    "length": l.length,
+                }),
+                "lane",
+            ));
+        }
+
+        for t in &self.turns {
+            features.push(feature(
+                line(&t.points),
+                json!({
+                    "intersection": t.intersection,
+                    "src_lane": t.src_lane,
+                    "dst_lane": t.dst_lane,
+                    "bidirectional": t.bidirectional,
+                    "kind": t.kind,
+                }),
+                "turn",
+            ));
+        }
+
+        for b in &self.buildings {
+            features.push(feature(
+                polygon(b.zone.as_ref().unwrap_or(&b.footprint)),
+                json!({
+                    "id": b.id,
+                    "kind": b.kind,
+                    "company": b.company,
+                    "door_pos": [b.door_pos.x, b.door_pos.y, b.door_pos.z],
+                }),
+                "building",
+            ));
+        }
+
+        for l in &self.lots {
+            features.push(feature(
+                polygon(&l.footprint),
+                json!({
+                    "id": l.id,
+                    "road": l.road,
+                }),
+                "lot",
+            ));
+        }
+
+        json!({
+            "type": "FeatureCollection",
+            "features": features,
+        })
+    }
+}
+
+/// Exports the map in the given format, as pretty-printed JSON
+pub fn export_map(
+    map: &Map,
+    companies: &GoodsCompanyRegistry,
+    format: MapExportFormat,
+) -> serde_json::Result<String> {
+    let graph = MapGraph::new(map, companies);
+    match format {
+        MapExportFormat::GeoJSON => serde_json::to_string_pretty(&graph.to_geojson()),
+        MapExportFormat::Graph => serde_json::to_string_pretty(&graph),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::procgen::load_testfield;
+    use geom::Vec2;
+
+    #[test]
+    fn export_testfield() {
+        let mut m = Map::empty();
+        load_testfield(&mut m, Vec2::ZERO, 3, 100.0);
+        let registry = GoodsCompanyRegistry::default();
+
+        let graph = MapGraph::new(&m, &registry);
+        assert_eq!(graph.intersections.len(), 9);
+        assert_eq!(graph.roads.len(), 12);
+        assert_eq!(graph.lanes.len(), m.lanes().len());
+
+        let geojson = graph.to_geojson();
+        let n_features = geojson["features"].as_array().unwrap().len();
+        assert_eq!(
+            n_features,
+            graph.intersections.len()
+                + graph.roads.len()
+                + graph.lanes.len()
+                + graph.turns.len()
+                + graph.buildings.len()
+                + graph.lots.len()
+        );
+
+        let s = export_map(&m, &registry, MapExportFormat::Graph).unwrap();
+        let back: MapGraph = serde_json::from_str(&s).unwrap();
+        assert_eq!(back.roads.len(), 12);
+    }
+}
@@ -8,12 +8,18 @@ use crate::map::{
 use crate::utils::time::Tick;
 use common::descriptions::BuildingGen;
 use geom::OBB;
-use geom::{Spline3, Vec2, Vec3};
+use geom::{Spline, Spline3, Vec2, Vec3};
 use ordered_float::OrderedFloat;
 use serde::{Deserialize, Serialize};
 use slotmapd::HopSlotMap;
 use std::collections::BTreeMap;
 
+/// Roads can go through shallow water, deeper water needs a bridge
+pub const ROAD_MAX_WATER_DEPTH: f32 = 1.0;
+
+/// Minimum height of a bridge above the water surface
+pub const BRIDGE_CLEARANCE: f32 = 2.0;
+
 pub type Roads = HopSlotMap<RoadID, Road>;
 pub type Lanes = HopSlotMap<LaneID, Lane>;
 pub type Intersections = HopSlotMap<IntersectionID, Intersection>;
@@ -142,6 +148,23 @@ impl Map {
             return None;
         }
 
+        let real_pos = |proj: MapProject| match proj.kind {
+            ProjectKind::Inter(id) => self.intersections.get(id).map_or(proj.pos, |i| i.pos),
+            _ => proj.pos,
+        };
+        if self.road_needs_bridge(real_pos(from), real_pos(to), interpoint) {
+            info!("cannot build a road through water, it needs to be raised as a bridge");
+            return None;
+        }
+        let max_grade = pattern.max_grade();
+        if Self::road_grade(real_pos(from), real_pos(to), interpoint) > max_grade {
+            info!(
+                "cannot build a road steeper than {:.0}% with these lanes",
+                max_grade * 100.0
+            );
+            return None;
+        }
+
         let connection_segment = match interpoint {
             Some(x) => RoadSegmentKind::from_elbow(from.pos.xy(), to.pos.xy(), x),
             None => RoadSegmentKind::Straight,
@@ -448,8 +471,8 @@ impl Map {
 
         let (r1, r2) = match r.segment {
             RoadSegmentKind::Straight => (
-                self.connect(src_id, id, &pat, RoadSegmentKind::Straight)?,
-                self.connect(id, r.dst, &pat, RoadSegmentKind::Straight)?,
+                self.connect_unchecked(src_id, id, &pat, RoadSegmentKind::Straight)?,
+                self.connect_unchecked(id, r.dst, &pat, RoadSegmentKind::Straight)?,
             ),
             RoadSegmentKind::Curved((from_derivative, to_derivative)) => {
                 let s = Spline3 {
@@ -463,7 +486,7 @@ impl Map {
                 let (s_from, s_to) = s.split_at(t_approx);
 
                 (
-                    self.connect(
+                    self.connect_unchecked(
                         src_id,
                         id,
                         &pat,
@@ -472,7 +495,7 @@ impl Map {
                             s_from.to_derivative.xy(),
                         )),
                     )?,
-                    self.connect(
+                    self.connect_unchecked(
                         id,
                         r.dst,
                         &pat,
@@ -522,13 +545,37 @@ impl Map {
         Some(id)
     }
 
-    /// Returns None if one of the intersections don't exist
+    /// Returns None if one of the intersections don't exist or if the road would be steeper than
+    /// its lanes allow
     pub(crate) fn connect(
         &mut self,
         src_id: IntersectionID,
         dst_id: IntersectionID,
         pattern: &LanePattern,
         segment: RoadSegmentKind,
+    ) -> Option<RoadID> {
+        let from = self.intersections.get(src_id)?.pos;
+        let to = self.intersections.get(dst_id)?.pos;
+        let max_grade = pattern.max_grade();
+        if Self::segment_grade(from, to, segment) > max_grade {
+            info!(
+                "cannot connect {:?} {:?}, steeper than {:.0}%",
+                src_id,
+                dst_id,
+                max_grade * 100.0
+            );
+            return None;
+        }
+        self.connect_unchecked(src_id, dst_id, pattern, segment)
+    }
+
+    /// Same as [`Map::connect`] without the grade limit, for the parts of an existing road
+    fn connect_unchecked(
+        &mut self,
+        src_id: IntersectionID,
+        dst_id: IntersectionID,
+        pattern: &LanePattern,
+        segment: RoadSegmentKind,
     ) -> Option<RoadID> {
         info!(
             "connect {:?} {:?} {:?} {:?}",
@@ -573,6 +620,58 @@ impl Map {
     }
 
     // Public helpers
+
+    /// Returns true if a road between the two points would cross water deeper than
+    /// [`ROAD_MAX_WATER_DEPTH`] without being at least [`BRIDGE_CLEARANCE`] above its surface.
+    /// Such roads need to be raised to be built as bridges.
+    pub fn road_needs_bridge(&self, from: Vec3, to: Vec3, interpoint: Option<Vec2>) -> bool {
+        let segment = interpoint.map(|x| RoadSegmentKind::from_elbow(from.xy(), to.xy(), x));
+        let (from_derivative, to_derivative) = match segment {
+            Some(RoadSegmentKind::Curved(x)) => x,
+            _ => ((to - from).xy(), (to - from).xy()),
+        };
+        let spline = Spline3 {
+            from,
+            to,
+            from_derivative: from_derivative.z0(),
+            to_derivative: to_derivative.z0(),
+        };
+
+        let n = (from.distance(to) / 8.0).ceil().max(1.0) as usize;
+        (0..=n).any(|i| {
+            let p = spline.get(i as f32 / n as f32);
+            let Some(surface) = self.environment.water_surface(p.xy()) else {
+                return false;
+            };
+            self.environment.water_depth(p.xy()) > ROAD_MAX_WATER_DEPTH
+                && p.z < surface + BRIDGE_CLEARANCE
+        })
+    }
+
+    /// Average slope (height over horizontal length) of a road between the two points, following
+    /// the curve through `interpoint` if any. It must not exceed [`LanePattern::max_grade`].
+    pub fn road_grade(from: Vec3, to: Vec3, interpoint: Option<Vec2>) -> f32 {
+        let segment = match interpoint {
+            Some(x) => RoadSegmentKind::from_elbow(from.xy(), to.xy(), x),
+            None => RoadSegmentKind::Straight,
+        };
+        Self::segment_grade(from, to, segment)
+    }
+
+    fn segment_grade(from: Vec3, to: Vec3, segment: RoadSegmentKind) -> f32 {
+        let length = match segment {
+            RoadSegmentKind::Curved((from_derivative, to_derivative)) => Spline {
+                from: from.xy(),
+                to: to.xy(),
+                from_derivative,
+                to_derivative,
+            }
+            .length(1.0),
+            RoadSegmentKind::Straight => from.xy().distance(to.xy()),
+        };
+        (to.z - from.z).abs() / length.max(1.0)
+    }
+
     pub fn project(&self, pos: Vec3, tolerance: f32, filter: ProjectFilter) -> MapProject {
         let mk_proj = move |kind| MapProject { pos, kind };
 
@@ -20,14 +20,19 @@ pub use objects::*;
 
 pub mod procgen {
     mod building;
+    mod city;
     pub mod heightmap;
+    mod osm;
     mod presets;
 
     pub use building::*;
+    pub use city::*;
+    pub use osm::*;
     pub use presets::*;
 }
 
 mod change_detection;
+mod export;
 mod light_policy;
 #[allow(clippy::module_inception)]
 mod map;
@@ -38,10 +43,12 @@ pub mod terrain;
 mod traffic_control;
 mod traversable;
 mod turn_policy;
+mod water;
 
 // Use self or else it would be ambiguous with "pathfinding" crate
 pub use self::pathfinding::*;
 pub use change_detection::*;
+pub use export::*;
 pub use light_policy::*;
 pub use map::*;
 pub use spatial_map::*;
@@ -49,6 +56,7 @@ pub use terrain::*;
 pub use traffic_control::*;
 pub use traversable::*;
 pub use turn_policy::*;
+pub use water::*;
 
 pub use ::pathfinding as pathfinding_crate;
 
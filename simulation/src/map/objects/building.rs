@@ -1,6 +1,7 @@
+use crate::definitions::Definitions;
 use crate::map::procgen::{gen_exterior_farm, gen_exterior_house, ColoredMesh};
 use crate::map::{Buildings, Environment, LanePattern, SpatialMap};
-use crate::souls::goods_company::GoodsCompanyID;
+use crate::souls::goods_company::{GoodsCompanyID, GoodsCompanyRegistry};
 use common::descriptions::BuildingGen;
 use egui_inspect::debug_inspect_impl;
 use geom::{Color, Polygon, Vec2, Vec3, OBB};
@@ -30,6 +31,17 @@ impl BuildingKind {
         }
     }
 
+    /// Electricity drawn from the grid by a building of this kind, in kW
+    pub fn power_consumption(&self, companies: &GoodsCompanyRegistry, defs: &Definitions) -> i32 {
+        match self {
+            BuildingKind::GoodsCompany(id) => companies
+                .descriptions
+                .get(*id)
+                .map_or(0, |descr| descr.power_consumption),
+            _ => defs.building(*self).map_or(0, |b| b.power_consumption),
+        }
+    }
+
     pub fn is_cached_in_bkinds(&self) -> bool {
         matches!(
             self,
@@ -52,6 +52,17 @@ impl LaneKind {
             LaneKind::Rail => 5.3,
         }
     }
+
+    /// Steepest slope (height over horizontal distance) a road with this lane can have
+    #[inline]
+    pub const fn max_grade(self) -> f32 {
+        match self {
+            LaneKind::Driving | LaneKind::Bus | LaneKind::Parking => 0.15,
+            LaneKind::Biking => 0.1,
+            LaneKind::Walking => 0.2,
+            LaneKind::Rail => 0.05,
+        }
+    }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
@@ -101,6 +112,13 @@ impl LanePattern {
     pub fn width(&self) -> f32 {
         self.lanes().map(|(kind, _, _)| kind.width()).sum()
     }
+
+    /// The strictest grade limit of its lanes
+    pub fn max_grade(&self) -> f32 {
+        self.lanes()
+            .map(|(kind, _, _)| kind.max_grade())
+            .fold(LaneKind::Walking.max_grade(), f32::min)
+    }
 }
 
 #[derive(PartialEq, Copy, Clone, Inspect)]
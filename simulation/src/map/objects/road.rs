@@ -447,6 +447,28 @@ impl Road {
         -self.points.last_dir().unwrap_or(Vec3::X).xy().normalize()
     }
 
+    /// Ends and elbow of a road following this one `offset` meters to its right when going
+    /// from src to dst, negative offsets are on the left
+    pub fn parallel(&self, offset: f32) -> (Vec3, Vec3, Option<Vec2>) {
+        let from = self.points.first();
+        let to = self.points.last();
+        match self.segment {
+            RoadSegmentKind::Straight => {
+                let n = (to - from).xy().normalize().perpendicular() * offset;
+                (from + n.z0(), to + n.z0(), None)
+            }
+            RoadSegmentKind::Curved((from_derivative, to_derivative)) => {
+                let a = from.xy() + from_derivative.normalize().perpendicular() * offset;
+                let b = to.xy() + to_derivative.normalize().perpendicular() * offset;
+                // the elbow is where the offset tangents cross
+                let denom = from_derivative.cross(to_derivative);
+                let elbow = (denom.abs() > 1e-3)
+                    .then(|| a + from_derivative * ((b - a).cross(to_derivative) / denom));
+                (a.z(from.z), b.z(to.z), elbow)
+            }
+        }
+    }
+
     pub fn other_end(&self, my_end: IntersectionID) -> Option<IntersectionID> {
         if self.src == my_end {
             return Some(self.dst);
@@ -457,3 +479,84 @@ impl Road {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::map::{LaneKind, LanePatternBuilder, MapProject, RoadSegmentKind};
+    use crate::tests::TestCtx;
+    use geom::{vec2, vec3};
+
+    #[test]
+    fn parallel_roads() {
+        let test = TestCtx::new();
+        let mut map = test.g.map_mut();
+        let pat = LanePatternBuilder::default().build();
+        let mut build = |from, to, elbow| {
+            map.make_connection(
+                MapProject::ground(from),
+                MapProject::ground(to),
+                elbow,
+                &pat,
+            )
+            .unwrap()
+            .1
+        };
+        let straight = build(vec3(0.0, 0.0, 0.0), vec3(100.0, 0.0, 0.0), None);
+        let curved = build(
+            vec3(0.0, 100.0, 0.0),
+            vec3(100.0, 200.0, 0.0),
+            Some(vec2(100.0, 100.0)),
+        );
+
+        let straight = &map.roads()[straight];
+        let (from, to, elbow) = straight.parallel(20.0);
+        assert!(from.xy().is_close(vec2(0.0, -20.0), 0.01));
+        assert!(to.xy().is_close(vec2(100.0, -20.0), 0.01));
+        assert!(elbow.is_none());
+
+        let (from, to, elbow) = map.roads()[curved].parallel(-20.0);
+        assert!(from.xy().is_close(vec2(0.0, 120.0), 0.01));
+        assert!(to.xy().is_close(vec2(80.0, 200.0), 0.01));
+        assert!(elbow.unwrap().is_close(vec2(80.0, 120.0), 0.01));
+
+        let rail = LanePatternBuilder::new().rail(true).build();
+        assert!(rail.max_grade() < pat.max_grade());
+        assert_eq!(pat.max_grade(), LaneKind::Driving.max_grade());
+    }
+
+    #[test]
+    fn steep_roads_are_refused() {
+        let test = TestCtx::new();
+        let mut map = test.g.map_mut();
+        let road = LanePatternBuilder::default().build();
+        let rail = LanePatternBuilder::new().rail(true).build();
+        let mut build = |height, elbow, pat| {
+            map.make_connection(
+                MapProject::ground(vec3(0.0, 300.0, 0.0)),
+                MapProject::ground(vec3(100.0, 300.0, height)),
+                elbow,
+                pat,
+            )
+            .is_some()
+        };
+
+        assert!(!build(20.0, None, &road));
+        assert!(build(10.0, None, &road));
+        assert!(!build(10.0, None, &rail));
+        assert!(build(4.0, None, &rail));
+        // the curve is longer than the straight line, so it is less steep
+        assert!(!build(16.0, None, &road));
+        assert!(build(16.0, Some(vec2(50.0, 400.0)), &road));
+
+        // imports connect intersections directly
+        let low = map.add_intersection(vec3(0.0, 600.0, 0.0));
+        let high = map.add_intersection(vec3(100.0, 600.0, 20.0));
+        let mid = map.add_intersection(vec3(-100.0, 600.0, 10.0));
+        assert!(map
+            .connect(low, high, &road, RoadSegmentKind::Straight)
+            .is_none());
+        assert!(map
+            .connect(low, mid, &road, RoadSegmentKind::Straight)
+            .is_some());
+    }
+}
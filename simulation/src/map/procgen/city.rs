@@ -0,0 +1,608 @@
+//! Procedural city generator.
+//!
+//! The road network is grown from a few highways crossing the city center, arterials branch off the
+//! highways and local streets branch off the arterials. Every new segment is checked against the
+//! terrain (generated from [`heightmap::height`](super::heightmap::height)) so that roads don't go
+//! into water or up steep slopes. Once the roads are built, a balanced set of companies is placed:
+//! stores near the center and the whole production chain needed to supply them on the outskirts.
+//! The remaining lots are zoned as residential and some of them get a house.
+
+use crate::economy::ItemID;
+use crate::map::{
+    BuildingID, BuildingKind, IntersectionID, LanePattern, LanePatternBuilder, Lot, LotKind, Map,
+    MapProject, ProjectFilter, ProjectKind, RoadID, Zone,
+};
+use crate::souls::goods_company::{GoodsCompanyDescription, GoodsCompanyRegistry};
+use common::descriptions::CompanyKind;
+use common::rand::RandGen;
+use geom::{Polygon, Radians, Segment, Vec2, OBB};
+use ordered_float::OrderedFloat;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+/// Terrain lower than this is considered to be water
+const WATER_LEVEL: f32 = -1.0;
+
+/// Maximum grade (height difference / length) of a generated road
+const MAX_GRADE: f32 = 0.08;
+
+/// A new segment ending closer than this to an intersection will connect to it instead
+const SNAP_DIST: f32 = 35.0;
+
+/// Number of houses that are served by one set of stores
+const HOUSES_PER_STORE: usize = 80;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum RoadClass {
+    Highway,
+    Arterial,
+    Local,
+}
+
+impl RoadClass {
+    fn pattern(self) -> LanePattern {
+        match self {
+            RoadClass::Highway => LanePatternBuilder::new()
+                .n_lanes(2)
+                .parking(false)
+                .speed_limit(25.0)
+                .build(),
+            RoadClass::Arterial => LanePatternBuilder::new()
+                .n_lanes(2)
+                .parking(false)
+                .speed_limit(14.0)
+                .build(),
+            RoadClass::Local => LanePatternBuilder::new().build(),
+        }
+    }
+
+    fn segment_length(self) -> f32 {
+        match self {
+            RoadClass::Highway => 200.0,
+            RoadClass::Arterial => 140.0,
+            RoadClass::Local => 90.0,
+        }
+    }
+
+    /// Maximum angle deviation between two consecutive segments, in radians
+    fn wiggle(self) -> f32 {
+        match self {
+            RoadClass::Highway => 0.08,
+            RoadClass::Arterial => 0.15,
+            RoadClass::Local => 0.1,
+        }
+    }
+}
+
+struct Branch {
+    from: IntersectionID,
+    dir: Vec2,
+    class: RoadClass,
+    steps_left: u32,
+}
+
+struct CityGen<'a> {
+    map: &'a mut Map,
+    rng: RandGen,
+    center: Vec2,
+    radius: f32,
+}
+
+/// Generates a city around `center`, returns the buildings that were built
+pub fn gen_city(
+    map: &mut Map,
+    companies: &GoodsCompanyRegistry,
+    seed: u64,
+    center: Vec2,
+    radius: f32,
+) -> Vec<BuildingID> {
+    let time = std::time::Instant::now();
+
+    let mut gen = CityGen {
+        map,
+        rng: common::rand::gen(seed),
+        center,
+        radius: radius.max(100.0),
+    };
+
+    gen.grow_roads();
+
+    let mut built = gen.place_companies(companies);
+    built.extend(gen.zone_lots());
+
+    info!(
+        "generating city took {}ms: {} roads and {} buildings",
+        time.elapsed().as_secs_f32() * 1000.0,
+        gen.map.roads.len(),
+        built.len()
+    );
+
+    gen.map.check_invariants();
+
+    built
+}
+
+/// Tries to build the company on either side of the road at the given position,
+/// without overlapping other roads or buildings
+pub fn build_company_along(
+    map: &mut Map,
+    descr: &GoodsCompanyDescription,
+    rid: RoadID,
+    p: Vec2,
+) -> Option<BuildingID> {
+    let size = descr.size;
+    let road = map.roads.get(rid)?;
+    let (proj, _, dir) = road.points.project_segment_dir(p.z0());
+    let dir = dir.xy();
+    let road_width = road.width;
+
+    for side in [1.0, -1.0] {
+        let side = side * dir.perpendicular();
+        let obb = OBB::new(
+            proj.xy() + side * (size + road_width + 0.5) * 0.5,
+            side,
+            size,
+            size,
+        );
+        let on_land = obb
+            .corners
+            .iter()
+            .all(|&c| map.environment.height(c).is_some_and(|h| h >= WATER_LEVEL));
+        if !on_land {
+            continue;
+        }
+        let overlaps = map
+            .spatial_map
+            .query(
+                obb,
+                ProjectFilter::ROAD | ProjectFilter::INTER | ProjectFilter::BUILDING,
+            )
+            .any(|x| x != ProjectKind::Road(rid));
+        if overlaps {
+            continue;
+        }
+
+        let zone = descr
+            .zone
+            .is_some()
+            .then(|| Zone::new(Polygon::from(obb.corners.as_slice()), Vec2::X));
+
+        if let Some(id) =
+            map.build_special_building(&obb, BuildingKind::GoodsCompany(descr.id), descr.bgen, zone)
+        {
+            return Some(id);
+        }
+    }
+    None
+}
+
+impl<'a> CityGen<'a> {
+    fn rand(&mut self) -> f32 {
+        self.rng.next_f32()
+    }
+
+    fn is_land(&self, p: Vec2) -> bool {
+        self.map
+            .environment
+            .height(p)
+            .is_some_and(|h| h >= WATER_LEVEL)
+    }
+
+    fn in_city(&self, p: Vec2) -> bool {
+        p.distance(self.center) <= self.radius && self.is_land(p)
+    }
+
+    /// Checks that a straight road between a and b stays on land, isn't too steep
+    /// and doesn't go further than max_dist from the center
+    fn terrain_ok(&self, a: Vec2, b: Vec2, max_dist: f32) -> bool {
+        const SAMPLES: usize = 8;
+        let env = &self.map.environment;
+        let mut last = unwrap_ret!(env.height(a), false);
+        for i in 1..=SAMPLES {
+            let p = a + (b - a) * (i as f32 / SAMPLES as f32);
+            if p.distance(self.center) > max_dist || !self.is_land(p) {
+                return false;
+            }
+            let h = unwrap_ret!(env.height(p), false);
+            if (h - last).abs() > MAX_GRADE * a.distance(b) / SAMPLES as f32 {
+                return false;
+            }
+            last = h;
+        }
+        true
+    }
+
+    fn project(&self, p: Vec2, kind: ProjectKind) -> MapProject {
+        let h = self.map.environment.height(p).unwrap_or(0.0);
+        MapProject {
+            pos: p.z(h + 0.3),
+            kind,
+        }
+    }
+
+    /// Finds where the segment from a to b should end: either on an existing intersection,
+    /// on the first road it crosses, or on the ground at b.
+    fn segment_end(&self, from: IntersectionID, a: Vec2, b: Vec2) -> Option<MapProject> {
+        let dir = (b - a).try_normalize()?;
+        let len = a.distance(b);
+        let query = OBB::new((a + b) * 0.5, dir, len + SNAP_DIST * 2.0, SNAP_DIST * 2.0);
+
+        let mut best: Option<(f32, ProjectKind, Vec2)> = None;
+        let mut consider = |t: f32, kind: ProjectKind, pos: Vec2| {
+            if best.is_none_or(|(bt, _, _)| t < bt) {
+                best = Some((t, kind, pos));
+            }
+        };
+
+        let seg = Segment::new(a, b);
+        for kind in self
+            .map
+            .spatial_map
+            .query(query, ProjectFilter::INTER | ProjectFilter::ROAD)
+        {
+            match kind {
+                ProjectKind::Inter(id) if id != from => {
+                    let pos = self.map.intersections[id].pos.xy();
+                    let proj = seg.project(pos);
+                    if proj.distance(pos) < SNAP_DIST && (pos - a).dot(dir) > 0.0 {
+                        consider(a.distance(proj), kind, pos);
+                    }
+                }
+                ProjectKind::Road(id) => {
+                    let road = &self.map.roads[id];
+                    if road.src == from || road.dst == from {
+                        continue;
+                    }
+                    for w in road.points.as_slice().windows(2) {
+                        let rseg = Segment::new(w[0].xy(), w[1].xy());
+                        if let Some(p) = seg.intersection_point(&rseg) {
+                            consider(a.distance(p), kind, p);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let Some((t, kind, pos)) = best else {
+            return Some(self.project(b, ProjectKind::Ground));
+        };
+
+        // Too close to the start to make a decent road
+        if t < SNAP_DIST {
+            return None;
+        }
+
+        if let ProjectKind::Road(id) = kind {
+            // Crossing a road near one of its ends, connect to the intersection instead
+            let road = &self.map.roads[id];
+            for inter in [road.src, road.dst] {
+                let ipos = self.map.intersections[inter].pos.xy();
+                if ipos.distance(pos) < SNAP_DIST {
+                    return Some(self.project(ipos, ProjectKind::Inter(inter)));
+                }
+            }
+        }
+
+        Some(self.project(pos, kind))
+    }
+
+    /// Tries to build one segment of a branch, returns the end intersection if the branch can grow further
+    fn build_segment(&mut self, branch: &Branch) -> Option<(IntersectionID, Vec2)> {
+        let from_pos = self.map.intersections.get(branch.from)?.pos.xy();
+        let len = branch.class.segment_length() * (0.85 + 0.3 * self.rand());
+        let wiggle = (self.rand() - 0.5) * 2.0 * branch.class.wiggle();
+
+        // Try to go around obstacles by turning a bit more each time
+        for turn in [0.0, 0.35, -0.35, 0.7, -0.7] {
+            let dir = branch.dir.rotated_by_angle(Radians(wiggle + turn));
+            let to = from_pos + dir * len;
+            if !self.terrain_ok(from_pos, to, self.radius) {
+                continue;
+            }
+            let Some(end) = self.segment_end(branch.from, from_pos, to) else {
+                continue;
+            };
+            if !self.terrain_ok(from_pos, end.pos.xy(), self.radius) {
+                continue;
+            }
+
+            let from = self.project(from_pos, ProjectKind::Inter(branch.from));
+            let (to, _) = self
+                .map
+                .make_connection(from, end, None, &branch.class.pattern())?;
+
+            if !end.kind.is_ground() {
+                // Joined the existing network, stop growing this branch
+                return None;
+            }
+            return Some((to, dir));
+        }
+        None
+    }
+
+    fn grow_roads(&mut self) {
+        let max_roads = ((self.radius / 40.0).powi(2) as usize).clamp(20, 3000);
+
+        let start_pos = self.center;
+        if !self.in_city(start_pos) {
+            log::warn!("cannot generate city in the water");
+            return;
+        }
+        let start = self.project(start_pos, ProjectKind::Ground);
+        let h = start.pos.z;
+        let start = self.map.add_intersection(start_pos.z(h));
+
+        let mut queue = VecDeque::new();
+
+        let n_highways = 2 + (self.rand() * 2.0) as usize;
+        let base_angle = self.rand() * std::f32::consts::TAU;
+        for i in 0..n_highways {
+            let angle = base_angle + std::f32::consts::TAU * i as f32 / n_highways as f32;
+            queue.push_back(Branch {
+                from: start,
+                dir: Vec2::from_angle(Radians(angle)),
+                class: RoadClass::Highway,
+                steps_left: u32::MAX,
+            });
+        }
+
+        let mut n_roads = 0;
+        let mut n_steps = 0;
+        while let Some(branch) = queue.pop_front() {
+            if n_roads >= max_roads {
+                break;
+            }
+            n_steps += 1;
+            let Some((end, dir)) = self.build_segment(&branch) else {
+                continue;
+            };
+            n_roads += 1;
+
+            let (side_class, side_prob) = match branch.class {
+                RoadClass::Highway => (
+                    RoadClass::Arterial,
+                    if n_steps % 2 == 0 { 0.7 } else { 0.1 },
+                ),
+                RoadClass::Arterial => (RoadClass::Local, 0.8),
+                RoadClass::Local => (RoadClass::Local, 0.35),
+            };
+            for side in [1.0, -1.0] {
+                if self.rand() < side_prob {
+                    let steps_left = match side_class {
+                        RoadClass::Arterial => 4 + (self.rand() * 6.0) as u32,
+                        _ => 2 + (self.rand() * 4.0) as u32,
+                    };
+                    queue.push_back(Branch {
+                        from: end,
+                        dir: side * dir.perpendicular(),
+                        class: side_class,
+                        steps_left,
+                    });
+                }
+            }
+
+            if branch.steps_left > 1 {
+                queue.push_back(Branch {
+                    from: end,
+                    dir,
+                    class: branch.class,
+                    steps_left: branch.steps_left - 1,
+                });
+            }
+        }
+    }
+
+    /// Places stores near the center, then the producers of every consumed item further away,
+    /// until every consumed item has a producer in the city.
+    fn place_companies(&mut self, companies: &GoodsCompanyRegistry) -> Vec<BuildingID> {
+        let mut built = vec![];
+
+        let n_sets = 1 + self.lots().count() / (HOUSES_PER_STORE * 2);
+
+        let mut producers: BTreeMap<ItemID, &GoodsCompanyDescription> = BTreeMap::new();
+        for descr in companies.descriptions.values() {
+            for &(item, _) in &descr.recipe.production {
+                producers.entry(item).or_insert(descr);
+            }
+        }
+
+        let mut demand: VecDeque<ItemID> = VecDeque::new();
+        let mut produced: BTreeSet<ItemID> = BTreeSet::new();
+
+        let mut place =
+            |gen: &mut Self, descr: &GoodsCompanyDescription, demand: &mut VecDeque<ItemID>| {
+                let near_center = matches!(descr.kind, CompanyKind::Store);
+                if let Some(id) = gen.place_company(descr, near_center) {
+                    built.push(id);
+                    demand.extend(descr.recipe.consumption.iter().map(|&(item, _)| item));
+                    true
+                } else {
+                    false
+                }
+            };
+
+        for _ in 0..n_sets {
+            for descr in companies.descriptions.values() {
+                if matches!(descr.kind, CompanyKind::Store) {
+                    place(self, descr, &mut demand);
+                }
+            }
+        }
+
+        while let Some(item) = demand.pop_front() {
+            if !produced.insert(item) {
+                continue;
+            }
+            let Some(&descr) = producers.get(&item) else {
+                continue;
+            };
+            place(self, descr, &mut demand);
+        }
+
+        built
+    }
+
+    /// Builds the company along an existing road, or along a new dead-end road going out of the
+    /// city if there is no room left
+    fn place_company(
+        &mut self,
+        descr: &GoodsCompanyDescription,
+        near_center: bool,
+    ) -> Option<BuildingID> {
+        let mut candidates: Vec<(RoadID, Vec2)> = self
+            .map
+            .roads
+            .values()
+            .filter(|r| r.sidewalks(r.src).incoming.is_some())
+            .flat_map(|r| {
+                [0.25, 0.5, 0.75].map(|t| (r.id, r.points.point_along(r.length() * t).xy()))
+            })
+            .collect();
+        if near_center {
+            candidates.sort_by_key(|(_, p)| OrderedFloat(p.distance(self.center)));
+        } else {
+            candidates.sort_by_key(|(_, p)| OrderedFloat(-p.distance(self.center)));
+        }
+
+        for (rid, p) in candidates {
+            if let Some(id) = build_company_along(self.map, descr, rid, p) {
+                return Some(id);
+            }
+        }
+
+        let size = descr.size;
+        let mut inters: Vec<_> = self
+            .map
+            .intersections
+            .values()
+            .map(|i| (i.id, i.pos.xy()))
+            .collect();
+        inters.sort_by_key(|(_, p)| OrderedFloat(-p.distance(self.center)));
+
+        for (id, pos) in inters.into_iter().take(50) {
+            let Some(dir) = (pos - self.center).try_normalize() else {
+                continue;
+            };
+            let end = pos + dir * (size + 40.0);
+            let max_dist = self.radius + 3.0 * size + 40.0;
+            if !self.terrain_ok(pos, end, max_dist) {
+                continue;
+            }
+            let (_, rid) = unwrap_cont!(self.map.make_connection(
+                self.project(pos, ProjectKind::Inter(id)),
+                self.project(end, ProjectKind::Ground),
+                None,
+                &RoadClass::Local.pattern(),
+            ));
+            let mid = self.map.roads[rid]
+                .points
+                .point_along(size * 0.5 + 20.0)
+                .xy();
+            if let Some(id) = build_company_along(self.map, descr, rid, mid) {
+                return Some(id);
+            }
+            if let Some(road) = self.map.remove_road(rid) {
+                if self
+                    .map
+                    .intersections
+                    .get(road.dst)
+                    .is_some_and(|i| i.roads.is_empty())
+                {
+                    self.map.remove_intersection(road.dst);
+                }
+            }
+        }
+
+        log::warn!("could not find a place for {} in the city", descr.name);
+        None
+    }
+
+    /// The lots of this city, other cities of the map may have their own
+    fn lots(&self) -> impl Iterator<Item = &Lot> + '_ {
+        self.map
+            .lots
+            .values()
+            .filter(|l| l.shape.center().distance(self.center) < self.radius)
+    }
+
+    /// Zones every lot of the city as residential and builds houses on some of them,
+    /// the density decreasing away from the center
+    fn zone_lots(&mut self) -> Vec<BuildingID> {
+        let lots: Vec<_> = self.lots().map(|l| (l.id, l.shape.center())).collect();
+
+        let mut built = vec![];
+        for (id, pos) in lots {
+            let density = 0.85 - 0.6 * pos.distance(self.center) / self.radius;
+            if self.rand() < density {
+                if let Some(b) = self.map.build_house(id) {
+                    built.push(b);
+                }
+            } else {
+                self.map.set_lot_kind(id, LotKind::Residential);
+            }
+        }
+        built
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::economy::ItemRegistry;
+    use crate::map::procgen::heightmap::{Biome, WorldGenOptions};
+    use crate::map::Environment;
+
+    #[test]
+    fn city_valid() {
+        let mut items = ItemRegistry::default();
+        items
+            .load_item_definitions(&common::saveload::load_string("../assets/items.json").unwrap());
+        let mut companies = GoodsCompanyRegistry::default();
+        companies.load(
+            &common::saveload::load_string("../assets/companies.json").unwrap(),
+            &items,
+        );
+
+        let mut m = Map::empty();
+        let opts = WorldGenOptions {
+            seed: 1,
+            ..WorldGenOptions::preset(Biome::Temperate)
+        };
+        m.environment = Environment::generate(8, 8, &opts);
+        let center = m.environment.bounds().center();
+        // this seed has land at the center of the map
+        assert!(m
+            .environment
+            .height(center)
+            .is_some_and(|h| h >= WATER_LEVEL));
+
+        let built = gen_city(&mut m, &companies, 1, center, 1000.0);
+        m.check_invariants();
+
+        assert!(m.roads().len() > 10);
+        assert!(!built.is_empty());
+
+        let descrs: Vec<_> = m
+            .buildings()
+            .values()
+            .filter_map(|b| match b.kind {
+                BuildingKind::GoodsCompany(id) => Some(&companies.descriptions[id]),
+                _ => None,
+            })
+            .collect();
+        assert!(descrs.iter().any(|d| matches!(d.kind, CompanyKind::Store)));
+
+        // Every consumed item is produced somewhere in the city
+        for d in &descrs {
+            for (item, _) in &d.recipe.consumption {
+                assert!(
+                    descrs
+                        .iter()
+                        .any(|p| p.recipe.production.iter().any(|(x, _)| x == item)),
+                    "{} has no supplier",
+                    d.name
+                );
+            }
+        }
+    }
+}
@@ -1,12 +1,120 @@
-use geom::{fnoise, simplex_noise, vec2, Vec2};
+use geom::{fnoise, simplex_noise, vec2, Vec2, AABB};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
 
-pub(crate) fn height(p: Vec2) -> (f32, Vec2) {
-    let (noise, mut grad) = fnoise::<4>(Vec2::splat(70.69) + 0.00006 * p);
+/// Overall shape of the generated land
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Biome {
+    /// Land split by a sea strait running east-west through the middle of the map
+    #[default]
+    Temperate,
+    /// Islands around the center of the map, surrounded by sea
+    Islands,
+    /// Mostly land with a few lakes
+    Continent,
+    /// Like temperate but with hills and mountains
+    Highlands,
+    /// Dry continent with almost no trees
+    Arid,
+}
+
+impl Biome {
+    pub const ALL: [Biome; 5] = [
+        Biome::Temperate,
+        Biome::Islands,
+        Biome::Continent,
+        Biome::Highlands,
+        Biome::Arid,
+    ];
+}
+
+impl FromStr for Biome {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|b| format!("{b:?}").eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("unknown biome {s}, expected one of {:?}", Self::ALL))
+    }
+}
+
+/// Parameters of the world generation, stored in the simulation options so that the same world
+/// can be generated again from a replay
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorldGenOptions {
+    /// Seed of the terrain noise and of the simulation random number generator
+    pub seed: u64,
+    /// Noise level under which the terrain is under water, higher means more water
+    pub sea_level: f32,
+    /// Height scale of the land, 0 gives a flat land
+    pub mountains: f32,
+    /// Carve rivers from the inland to the sea
+    pub rivers: bool,
+    /// Multiplier of the tree density
+    pub tree_density: f32,
+    pub biome: Biome,
+}
+
+impl Default for WorldGenOptions {
+    fn default() -> Self {
+        Self::preset(Biome::Temperate)
+    }
+}
+
+impl WorldGenOptions {
+    /// Default options for the given biome
+    pub fn preset(biome: Biome) -> Self {
+        let (sea_level, mountains, rivers, tree_density) = match biome {
+            Biome::Temperate => (0.12, 0.0, false, 1.0),
+            Biome::Islands => (0.16, 0.15, false, 1.2),
+            Biome::Continent => (0.12, 0.1, true, 1.0),
+            Biome::Highlands => (0.1, 0.5, true, 0.8),
+            Biome::Arid => (0.1, 0.2, false, 0.1),
+        };
+        Self {
+            seed: crate::RNG_SEED,
+            sea_level,
+            mountains,
+            rivers,
+            tree_density,
+            biome,
+        }
+    }
+
+    /// Offset in noise space so that different seeds give different worlds,
+    /// the default seed gives the original world
+    fn noise_offset(&self) -> Vec2 {
+        let k = self.seed ^ crate::RNG_SEED;
+        vec2((k % 1009) as f32, ((k / 1009) % 1009) as f32) * 1.37
+    }
+}
+
+/// Raw terrain noise and its gradient, the terrain is under water where the noise is under the sea level.
+/// `center` is the center of the map, where the islands are
+pub(crate) fn height(p: Vec2, center: Vec2, opts: &WorldGenOptions) -> (f32, Vec2) {
+    let (noise, mut grad) = fnoise::<4>(Vec2::splat(70.69) + opts.noise_offset() + 0.00006 * p);
     grad *= 0.00006;
 
-    let ratio = 0.00005;
-    let mut noise = noise - 0.1 + (p.y * 2.0 - 25000.0).abs() * ratio;
-    grad += vec2(0.0, (p.y * 2.0 - 25000.0).signum() * ratio);
+    let mut noise = noise - 0.1;
+    match opts.biome {
+        Biome::Temperate | Biome::Highlands => {
+            let ratio = 0.00005;
+            noise += (p.y * 2.0 - 25000.0).abs() * ratio;
+            grad += vec2(0.0, (p.y * 2.0 - 25000.0).signum() * ratio);
+        }
+        Biome::Islands => {
+            let ratio = 0.00004;
+            let d = p - center;
+            noise += 0.45 - d.mag() * ratio;
+            grad -= d.try_normalize().unwrap_or(Vec2::ZERO) * ratio;
+        }
+        Biome::Continent | Biome::Arid => {
+            noise += 0.3;
+        }
+    }
+
     if noise < -0.0 {
         noise = noise * noise;
         grad = 2.0 * noise * grad;
@@ -17,8 +125,126 @@ pub(crate) fn height(p: Vec2) -> (f32, Vec2) {
     (noise, grad)
 }
 
-pub(crate) fn tree_density(mut p: Vec2) -> f32 {
+/// Height of the terrain in meters before rivers are carved
+pub(crate) fn elevation(p: Vec2, center: Vec2, opts: &WorldGenOptions) -> f32 {
+    let rh = height(p, center, opts).0 - opts.sea_level;
+    if rh > 0.0 {
+        return 1000.0 * rh * opts.mountains;
+    }
+    1000.0 * rh
+}
+
+pub(crate) fn tree_density(mut p: Vec2, opts: &WorldGenOptions) -> f32 {
     p -= vec2(-20000.0, 20000.0);
-    let major = simplex_noise((p - vec2(-1000.0, 10000.0)) * 0.0006).0 * 0.5 + 0.5;
-    (-major * 1.0 + simplex_noise(p * 0.0006).0 * 1.5 + 0.5).max(0.0) + -0.1
+    let off = opts.noise_offset();
+    let major = simplex_noise(off + (p - vec2(-1000.0, 10000.0)) * 0.0006).0 * 0.5 + 0.5;
+    let dens = (-major * 1.0 + simplex_noise(off + p * 0.0006).0 * 1.5 + 0.5).max(0.0) + -0.1;
+    dens * opts.tree_density
+}
+
+/// Paths of the rivers, from their source inland to the sea or to the edge of the map.
+/// Rivers follow the slope of the terrain noise with enough momentum to cross small basins,
+/// and are dropped if they go nowhere.
+pub(crate) fn river_paths(opts: &WorldGenOptions, bounds: AABB) -> Vec<Vec<Vec2>> {
+    const STEP: f32 = 60.0;
+    const MAX_STEPS: usize = 1000;
+
+    if !opts.rivers {
+        return vec![];
+    }
+
+    let mut rng = common::rand::gen(opts.seed);
+    let size = bounds.size();
+    let n_rivers = ((size.x * size.y) / (6000.0 * 6000.0)).ceil() as usize;
+
+    let mut rivers = vec![];
+    for _ in 0..n_rivers {
+        for _ in 0..20 {
+            let start = bounds.ll + vec2(rng.next_f32() * size.x, rng.next_f32() * size.y);
+            if height(start, bounds.center(), opts).0 < opts.sea_level + 0.15 {
+                continue;
+            }
+
+            let mut path = vec![start];
+            let mut p = start;
+            let mut dir = Vec2::ZERO;
+            let mut finished = false;
+            for i in 0..MAX_STEPS {
+                let (h, grad) = height(p, bounds.center(), opts);
+                if h < opts.sea_level {
+                    finished = true;
+                    break;
+                }
+                let meander = simplex_noise(p * 0.002 + Vec2::splat(i as f32 * 0.01)).0 * 0.6;
+                let downhill = (-grad).try_normalize().unwrap_or(dir);
+                dir = (dir * 2.0 + downhill)
+                    .try_normalize()
+                    .unwrap_or(Vec2::X)
+                    .rotated_by_angle(geom::Radians(meander));
+                p += dir * STEP;
+                if !bounds.contains(p) {
+                    finished = true;
+                    break;
+                }
+                path.push(p);
+            }
+
+            if finished && path.len() > 10 {
+                rivers.push(path);
+                break;
+            }
+        }
+    }
+    rivers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::Environment;
+
+    #[test]
+    fn default_is_original_terrain() {
+        let opts = WorldGenOptions::default();
+        for i in 0..100 {
+            let p = vec2(i as f32 * 251.0, i as f32 * 123.0);
+
+            let (noise, _) = fnoise::<4>(Vec2::splat(70.69) + 0.00006 * p);
+            let mut noise = noise - 0.1 + (p.y * 2.0 - 25000.0).abs() * 0.00005;
+            if noise < 0.0 {
+                noise = noise * noise;
+            } else if noise > 1.0 {
+                noise = 1.0;
+            }
+            let expected = 1000.0 * (noise - 0.12).min(0.0);
+
+            assert_eq!(elevation(p, Vec2::splat(12500.0), &opts), expected);
+        }
+    }
+
+    #[test]
+    fn islands_are_centered() {
+        let opts = WorldGenOptions::preset(Biome::Islands);
+        for size in [8, 16] {
+            let env = Environment::generate(size, size, &opts);
+            assert!(env.height(env.bounds().center()).unwrap() > 0.0);
+        }
+    }
+
+    #[test]
+    fn rivers_are_carved() {
+        let opts = WorldGenOptions {
+            seed: 7,
+            ..WorldGenOptions::preset(Biome::Continent)
+        };
+        let env = Environment::generate(16, 16, &opts);
+        let rivers = river_paths(&opts, env.bounds());
+        assert!(!rivers.is_empty());
+
+        for river in rivers {
+            for &p in &river[1..] {
+                assert!(env.height(p).unwrap() < elevation(p, env.bounds().center(), &opts));
+            }
+        }
+    }
 }
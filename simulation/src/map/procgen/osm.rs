@@ -0,0 +1,908 @@
+//! Importer for OpenStreetMap extracts, either in the XML (`.osm`) or PBF (`.osm.pbf`) format.
+//!
+//! The extract is first parsed into an [`OsmData`], which only keeps the ways and tags that are
+//! relevant to Egregoria (highways, railways and buildings). This is what is sent inside
+//! [`WorldCommand::MapLoadOSM`](crate::world_command::WorldCommand::MapLoadOSM) so that every
+//! peer builds the exact same map without needing the file.
+
+use crate::map::{
+    BuildingID, BuildingKind, IntersectionID, LanePattern, LanePatternBuilder, LotID, Map,
+    ProjectFilter, ProjectKind, RoadSegmentKind,
+};
+use common::descriptions::BuildingGen;
+use common::{FastMap, FastSet};
+use flat_spatial::Grid;
+use geom::{vec2, Vec2, OBB};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+
+/// Tags that are kept when filtering an extract, everything else is thrown away
+const KEPT_TAGS: &[&str] = &[
+    "highway", "railway", "service", "lanes", "oneway", "junction", "maxspeed", "building",
+];
+
+/// Intersections closer than this (in meters) are merged together
+const MERGE_DIST: f32 = 15.0;
+
+/// Shape nodes of a way closer than this (in meters) to the previous kept node are skipped
+const SHAPE_MIN_DIST: f32 = 40.0;
+
+const EARTH_RADIUS: f64 = 6_371_000.0;
+
+#[derive(Debug)]
+pub enum OsmError {
+    Io(std::io::Error),
+    Xml(String),
+    Pbf(&'static str),
+}
+
+impl Display for OsmError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OsmError::Io(e) => write!(f, "io error: {}", e),
+            OsmError::Xml(e) => write!(f, "invalid osm xml: {}", e),
+            OsmError::Pbf(e) => write!(f, "invalid osm pbf: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for OsmError {}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OsmWay {
+    pub nodes: Vec<i64>,
+    pub tags: BTreeMap<String, String>,
+}
+
+impl OsmWay {
+    pub fn tag(&self, k: &str) -> Option<&str> {
+        self.tags.get(k).map(String::as_str)
+    }
+
+    fn is_closed(&self) -> bool {
+        self.nodes.len() >= 4 && self.nodes.first() == self.nodes.last()
+    }
+}
+
+/// The subset of an OSM extract that is used to build a map.
+/// Node positions are stored as (lat, lon) in degrees.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OsmData {
+    pub nodes: BTreeMap<i64, (f64, f64)>,
+    pub ways: Vec<OsmWay>,
+}
+
+impl OsmData {
+    /// Reads an extract from disk, the format is deduced from the extension
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, OsmError> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).map_err(OsmError::Io)?;
+        let is_pbf = path
+            .file_name()
+            .and_then(|x| x.to_str())
+            .is_some_and(|x| x.ends_with(".pbf"));
+        if is_pbf {
+            Self::from_pbf(&bytes)
+        } else {
+            Self::from_xml(&bytes)
+        }
+    }
+
+    pub fn from_xml(bytes: &[u8]) -> Result<Self, OsmError> {
+        let text = std::str::from_utf8(bytes).map_err(|e| OsmError::Xml(e.to_string()))?;
+        let doc = roxmltree::Document::parse(text).map_err(|e| OsmError::Xml(e.to_string()))?;
+
+        let mut data = OsmData::default();
+
+        for elem in doc.root_element().children().filter(|x| x.is_element()) {
+            match elem.tag_name().name() {
+                "node" => {
+                    let attr = |name| {
+                        elem.attribute(name)
+                            .ok_or_else(|| OsmError::Xml(format!("node without {}", name)))
+                    };
+                    let parse_err = |e: std::num::ParseFloatError| OsmError::Xml(e.to_string());
+                    let id = attr("id")?
+                        .parse::<i64>()
+                        .map_err(|e| OsmError::Xml(e.to_string()))?;
+                    let lat = attr("lat")?.parse::<f64>().map_err(parse_err)?;
+                    let lon = attr("lon")?.parse::<f64>().map_err(parse_err)?;
+                    data.nodes.insert(id, (lat, lon));
+                }
+                "way" => {
+                    let mut way = OsmWay::default();
+                    for child in elem.children().filter(|x| x.is_element()) {
+                        match child.tag_name().name() {
+                            "nd" => {
+                                let Some(r) = child.attribute("ref").and_then(|x| x.parse().ok())
+                                else {
+                                    continue;
+                                };
+                                way.nodes.push(r);
+                            }
+                            "tag" => {
+                                let (Some(k), Some(v)) =
+                                    (child.attribute("k"), child.attribute("v"))
+                                else {
+                                    continue;
+                                };
+                                way.tags.insert(k.to_string(), v.to_string());
+                            }
+                            _ => {}
+                        }
+                    }
+                    data.ways.push(way);
+                }
+                _ => {}
+            }
+        }
+
+        Ok(data.filtered())
+    }
+
+    pub fn from_pbf(bytes: &[u8]) -> Result<Self, OsmError> {
+        let mut data = OsmData::default();
+        pbf::read_file(bytes, &mut data)?;
+        Ok(data.filtered())
+    }
+
+    /// Only keeps the ways that can be imported, the tags that are used and the nodes that are referenced
+    pub fn filtered(mut self) -> Self {
+        self.ways.retain(|way| {
+            way.nodes.len() >= 2
+                && (way_pattern(way).is_some()
+                    || (way.is_closed() && way.tag("building").is_some()))
+        });
+
+        let mut used = FastSet::default();
+        for way in &mut self.ways {
+            way.tags.retain(|k, _| KEPT_TAGS.contains(&&**k));
+            used.extend(way.nodes.iter().copied());
+        }
+        self.nodes.retain(|id, _| used.contains(id));
+        self.ways
+            .retain(|way| way.nodes.iter().all(|n| self.nodes.contains_key(n)));
+
+        self
+    }
+
+    /// Projects all nodes to map space using an equirectangular projection
+    /// around the center of the extract, which is placed at `center`
+    pub fn project(&self, center: Vec2) -> BTreeMap<i64, Vec2> {
+        if self.nodes.is_empty() {
+            return BTreeMap::new();
+        }
+
+        let n = self.nodes.len() as f64;
+        let lat0 = self.nodes.values().map(|x| x.0).sum::<f64>() / n;
+        let lon0 = self.nodes.values().map(|x| x.1).sum::<f64>() / n;
+        let coslat = lat0.to_radians().cos();
+
+        self.nodes
+            .iter()
+            .map(|(&id, &(lat, lon))| {
+                let x = (lon - lon0).to_radians() * coslat * EARTH_RADIUS;
+                let y = (lat - lat0).to_radians() * EARTH_RADIUS;
+                (id, center + vec2(x as f32, y as f32))
+            })
+            .collect()
+    }
+}
+
+/// Infers the lane pattern of a way from its tags.
+/// Returns None if the way shouldn't be imported as a road.
+pub fn way_pattern(way: &OsmWay) -> Option<LanePattern> {
+    if let Some(railway) = way.tag("railway") {
+        if railway != "rail" || way.tag("service").is_some() {
+            return None;
+        }
+        return Some(
+            LanePatternBuilder::new()
+                .rail(true)
+                .one_way(is_one_way(way))
+                .speed_limit(maxspeed(way).unwrap_or(30.0))
+                .build(),
+        );
+    }
+
+    let highway = way.tag("highway")?;
+    let highway = highway.strip_suffix("_link").unwrap_or(highway);
+
+    // (default lanes per direction, speed limit in m/s, parking, sidewalks)
+    let (def_lanes, speed, parking, sidewalks) = match highway {
+        "motorway" => (2, 30.0, false, false),
+        "trunk" => (2, 25.0, false, false),
+        "primary" => (2, 15.0, false, true),
+        "secondary" => (1, 14.0, false, true),
+        "tertiary" => (1, 13.0, true, true),
+        "unclassified" | "residential" => (1, 9.0, true, true),
+        "living_street" => (1, 5.0, true, true),
+        "service" => (1, 6.0, false, true),
+        _ => return None,
+    };
+
+    let one_way = is_one_way(way) || highway == "motorway";
+    let n_lanes = match way.tag("lanes").and_then(|x| x.parse::<u32>().ok()) {
+        Some(n) if one_way => n,
+        Some(n) => n.div_ceil(2),
+        None => def_lanes,
+    }
+    .max(1);
+
+    Some(
+        LanePatternBuilder::new()
+            .n_lanes(n_lanes)
+            .one_way(one_way)
+            .parking(parking)
+            .sidewalks(sidewalks)
+            .speed_limit(maxspeed(way).unwrap_or(speed))
+            .build(),
+    )
+}
+
+fn is_one_way(way: &OsmWay) -> bool {
+    matches!(way.tag("oneway"), Some("yes" | "1" | "true" | "-1"))
+        || way.tag("junction") == Some("roundabout")
+}
+
+/// Speed limit in m/s, OSM uses km/h unless "mph" is specified
+fn maxspeed(way: &OsmWay) -> Option<f32> {
+    let v = way.tag("maxspeed")?;
+    if let Some(mph) = v.strip_suffix("mph") {
+        return Some(mph.trim().parse::<f32>().ok()? * 0.44704);
+    }
+    Some(v.trim().parse::<f32>().ok()? / 3.6)
+}
+
+/// Builds the roads and buildings of an OSM extract into the map, with the extract centered at `center`.
+/// Residential buildings are built as houses on the closest lot and train stations as special buildings.
+/// Returns the buildings that were built.
+pub fn load_osm(map: &mut Map, data: &OsmData, center: Vec2) -> Vec<BuildingID> {
+    let time = std::time::Instant::now();
+
+    let positions = data.project(center);
+
+    let mut roads = vec![];
+    for way in &data.ways {
+        if let Some(pat) = way_pattern(way) {
+            let mut nodes = way.nodes.clone();
+            if way.tag("oneway") == Some("-1") {
+                nodes.reverse();
+            }
+            roads.push((nodes, pat));
+        }
+    }
+
+    // Nodes shared by multiple roads (or road extremities) must become intersections
+    let mut uses: FastMap<i64, u32> = FastMap::default();
+    for (nodes, _) in &roads {
+        for n in nodes {
+            *uses.entry(*n).or_default() += 1;
+        }
+        *uses.entry(nodes[0]).or_default() += 1;
+        *uses.entry(*nodes.last().unwrap()).or_default() += 1;
+    }
+
+    let mut g: Grid<IntersectionID, Vec2> = Grid::new(50);
+    let mut inters = vec![];
+    let mut inter_of = |map: &mut Map, pos: Vec2| -> IntersectionID {
+        if let Some((h, _)) = g.query_around(pos, MERGE_DIST).next() {
+            return *g.get(h).unwrap().1;
+        }
+        let h = map.environment.height(pos).unwrap_or(0.0);
+        let id = map.add_intersection(pos.z(h + 0.3));
+        g.insert(pos, id);
+        g.maintain();
+        inters.push(id);
+        id
+    };
+
+    let mut done = FastSet::default();
+    for (nodes, pat) in &roads {
+        let mut last_pos = positions[&nodes[0]];
+        let mut last = inter_of(map, last_pos);
+
+        for (i, n) in nodes.iter().enumerate().skip(1) {
+            let pos = positions[n];
+            let is_end = i == nodes.len() - 1;
+            if uses[n] <= 1 && !is_end && pos.distance(last_pos) < SHAPE_MIN_DIST {
+                continue;
+            }
+
+            let id = inter_of(map, pos);
+            if id == last {
+                continue;
+            }
+
+            // segments shared by multiple ways are only built once
+            if done.insert((last.min(id), last.max(id)))
+                && map
+                    .connect(last, id, pat, RoadSegmentKind::Straight)
+                    .is_none()
+            {
+                log::warn!("could not connect osm way between {:?} and {:?}", last, id);
+            }
+            last = id;
+            last_pos = pos;
+        }
+    }
+
+    // roads too steep for their lanes are not built, like in game
+    for id in inters {
+        if map
+            .intersections
+            .get(id)
+            .is_some_and(|i| i.roads.is_empty())
+        {
+            map.remove_intersection(id);
+        }
+    }
+
+    let mut built = vec![];
+    let mut used_lots: FastSet<LotID> = FastSet::default();
+    for way in &data.ways {
+        if !way.is_closed() {
+            continue;
+        }
+        let Some(building) = way.tag("building") else {
+            continue;
+        };
+        let footprint: Vec<Vec2> = way.nodes[1..].iter().map(|n| positions[n]).collect();
+        let centroid = footprint.iter().copied().sum::<Vec2>() / footprint.len() as f32;
+
+        match building {
+            "train_station" => {
+                let Some(obb) = fit_obb(&footprint) else {
+                    continue;
+                };
+                if let Some(id) = map.build_special_building(
+                    &obb,
+                    BuildingKind::TrainStation,
+                    BuildingGen::NoWalkway {
+                        door_pos: Vec2::ZERO,
+                    },
+                    None,
+                ) {
+                    built.push(id);
+                }
+            }
+            "yes" | "house" | "residential" | "apartments" | "detached" | "terrace"
+            | "semidetached_house" => {
+                let lot = map
+                    .spatial_map
+                    .query(centroid, ProjectFilter::LOT)
+                    .find_map(ProjectKind::to_lot);
+                let Some(lot) = lot else {
+                    continue;
+                };
+                if !used_lots.insert(lot) {
+                    continue;
+                }
+                if let Some(id) = map.build_house(lot) {
+                    built.push(id);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    info!(
+        "loading osm extract took {}ms",
+        time.elapsed().as_secs_f32() * 1000.0
+    );
+
+    map.check_invariants();
+
+    built
+}
+
+/// Fits an oriented bounding box to a footprint, aligned with its longest edge
+fn fit_obb(footprint: &[Vec2]) -> Option<OBB> {
+    let mut axis = None;
+    let mut best = 0.0;
+    for (i, &a) in footprint.iter().enumerate() {
+        let b = footprint[(i + 1) % footprint.len()];
+        let d = b.distance2(a);
+        if d > best {
+            best = d;
+            axis = (b - a).try_normalize();
+        }
+    }
+    let axis = axis?;
+    let perp = axis.perpendicular();
+
+    let (mut minx, mut maxx, mut miny, mut maxy) = (f32::MAX, f32::MIN, f32::MAX, f32::MIN);
+    for p in footprint {
+        let x = p.dot(axis);
+        let y = p.dot(perp);
+        minx = minx.min(x);
+        maxx = maxx.max(x);
+        miny = miny.min(y);
+        maxy = maxy.max(y);
+    }
+    let center = axis * (minx + maxx) * 0.5 + perp * (miny + maxy) * 0.5;
+    Some(OBB::new(center, axis, maxx - minx, maxy - miny))
+}
+
+/// Minimal protobuf decoding of the PBF format, only reading what is needed for [`OsmData`].
+/// See https://wiki.openstreetmap.org/wiki/PBF_Format
+mod pbf {
+    use super::{OsmData, OsmError, OsmWay};
+
+    enum Value<'a> {
+        Varint(u64),
+        Bytes(&'a [u8]),
+        Fixed,
+    }
+
+    struct Reader<'a> {
+        buf: &'a [u8],
+    }
+
+    impl<'a> Reader<'a> {
+        fn varint(&mut self) -> Result<u64, OsmError> {
+            let mut v = 0;
+            for shift in (0..64).step_by(7) {
+                let (&b, rest) = self
+                    .buf
+                    .split_first()
+                    .ok_or(OsmError::Pbf("truncated varint"))?;
+                self.buf = rest;
+                v |= ((b & 0x7F) as u64) << shift;
+                if b & 0x80 == 0 {
+                    return Ok(v);
+                }
+            }
+            Err(OsmError::Pbf("varint too long"))
+        }
+
+        fn take(&mut self, n: usize) -> Result<&'a [u8], OsmError> {
+            if n > self.buf.len() {
+                return Err(OsmError::Pbf("truncated field"));
+            }
+            let (a, b) = self.buf.split_at(n);
+            self.buf = b;
+            Ok(a)
+        }
+
+        fn field(&mut self) -> Result<Option<(u64, Value<'a>)>, OsmError> {
+            if self.buf.is_empty() {
+                return Ok(None);
+            }
+            let key = self.varint()?;
+            let v = match key & 7 {
+                0 => Value::Varint(self.varint()?),
+                1 => {
+                    self.take(8)?;
+                    Value::Fixed
+                }
+                2 => {
+                    let n = self.varint()? as usize;
+                    Value::Bytes(self.take(n)?)
+                }
+                5 => {
+                    self.take(4)?;
+                    Value::Fixed
+                }
+                _ => return Err(OsmError::Pbf("unsupported wire type")),
+            };
+            Ok(Some((key >> 3, v)))
+        }
+
+        /// Calls f for each field of the message
+        fn for_each(
+            buf: &'a [u8],
+            mut f: impl FnMut(u64, Value<'a>) -> Result<(), OsmError>,
+        ) -> Result<(), OsmError> {
+            let mut r = Reader { buf };
+            while let Some((id, v)) = r.field()? {
+                f(id, v)?;
+            }
+            Ok(())
+        }
+    }
+
+    fn zigzag(v: u64) -> i64 {
+        (v >> 1) as i64 ^ -((v & 1) as i64)
+    }
+
+    fn packed(buf: &[u8]) -> Result<Vec<u64>, OsmError> {
+        let mut r = Reader { buf };
+        let mut v = vec![];
+        while !r.buf.is_empty() {
+            v.push(r.varint()?);
+        }
+        Ok(v)
+    }
+
+    /// Packed delta-coded sint64, as used by dense nodes and way refs
+    fn packed_delta(buf: &[u8]) -> Result<Vec<i64>, OsmError> {
+        let mut acc = 0;
+        Ok(packed(buf)?
+            .into_iter()
+            .map(|x| {
+                acc += zigzag(x);
+                acc
+            })
+            .collect())
+    }
+
+    pub(super) fn read_file(mut bytes: &[u8], data: &mut OsmData) -> Result<(), OsmError> {
+        while !bytes.is_empty() {
+            if bytes.len() < 4 {
+                return Err(OsmError::Pbf("truncated blob header size"));
+            }
+            let header_len = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize;
+            let mut r = Reader { buf: &bytes[4..] };
+            let header = r.take(header_len)?;
+
+            let mut kind: &[u8] = &[];
+            let mut datasize = 0;
+            Reader::for_each(header, |id, v| {
+                match (id, v) {
+                    (1, Value::Bytes(b)) => kind = b,
+                    (3, Value::Varint(n)) => datasize = n as usize,
+                    _ => {}
+                }
+                Ok(())
+            })?;
+
+            let blob = r.take(datasize)?;
+            bytes = r.buf;
+
+            if kind != b"OSMData" {
+                continue;
+            }
+
+            let mut raw = None;
+            Reader::for_each(blob, |id, v| {
+                match (id, v) {
+                    (1, Value::Bytes(b)) => raw = Some(b.to_vec()),
+                    (3, Value::Bytes(b)) => {
+                        raw = Some(
+                            miniz_oxide::inflate::decompress_to_vec_zlib(b)
+                                .map_err(|_| OsmError::Pbf("invalid zlib data"))?,
+                        )
+                    }
+                    (4..=7, Value::Bytes(_)) => {
+                        return Err(OsmError::Pbf("unsupported blob compression"))
+                    }
+                    _ => {}
+                }
+                Ok(())
+            })?;
+
+            let raw = raw.ok_or(OsmError::Pbf("empty blob"))?;
+            read_block(&raw, data)?;
+        }
+        Ok(())
+    }
+
+    fn read_block(block: &[u8], data: &mut OsmData) -> Result<(), OsmError> {
+        let mut strings: Vec<String> = vec![];
+        let mut groups = vec![];
+        let mut granularity = 100i64;
+        let mut lat_offset = 0i64;
+        let mut lon_offset = 0i64;
+
+        Reader::for_each(block, |id, v| {
+            match (id, v) {
+                (1, Value::Bytes(b)) => Reader::for_each(b, |id, v| {
+                    if let (1, Value::Bytes(s)) = (id, v) {
+                        strings.push(String::from_utf8_lossy(s).into_owned());
+                    }
+                    Ok(())
+                })?,
+                (2, Value::Bytes(b)) => groups.push(b),
+                (17, Value::Varint(n)) => granularity = n as i64,
+                (19, Value::Varint(n)) => lat_offset = n as i64,
+                (20, Value::Varint(n)) => lon_offset = n as i64,
+                _ => {}
+            }
+            Ok(())
+        })?;
+
+        let coord = |offset: i64, v: i64| 1e-9 * (offset + granularity * v) as f64;
+        let string = |i: u64| strings.get(i as usize).cloned().unwrap_or_default();
+
+        for group in groups {
+            Reader::for_each(group, |id, v| {
+                let Value::Bytes(b) = v else {
+                    return Ok(());
+                };
+                match id {
+                    // Node
+                    1 => {
+                        let (mut nid, mut lat, mut lon) = (0, 0, 0);
+                        Reader::for_each(b, |id, v| {
+                            match (id, v) {
+                                (1, Value::Varint(n)) => nid = zigzag(n),
+                                (8, Value::Varint(n)) => lat = zigzag(n),
+                                (9, Value::Varint(n)) => lon = zigzag(n),
+                                _ => {}
+                            }
+                            Ok(())
+                        })?;
+                        data.nodes
+                            .insert(nid, (coord(lat_offset, lat), coord(lon_offset, lon)));
+                    }
+                    // DenseNodes
+                    2 => {
+                        let (mut ids, mut lats, mut lons) = (vec![], vec![], vec![]);
+                        Reader::for_each(b, |id, v| {
+                            match (id, v) {
+                                (1, Value::Bytes(b)) => ids = packed_delta(b)?,
+                                (8, Value::Bytes(b)) => lats = packed_delta(b)?,
+                                (9, Value::Bytes(b)) => lons = packed_delta(b)?,
+                                _ => {}
+                            }
+                            Ok(())
+                        })?;
+                        for ((nid, lat), lon) in ids.into_iter().zip(lats).zip(lons) {
+                            data.nodes
+                                .insert(nid, (coord(lat_offset, lat), coord(lon_offset, lon)));
+                        }
+                    }
+                    // Way
+                    3 => {
+                        let (mut keys, mut vals, mut refs) = (vec![], vec![], vec![]);
+                        Reader::for_each(b, |id, v| {
+                            match (id, v) {
+                                (2, Value::Bytes(b)) => keys = packed(b)?,
+                                (3, Value::Bytes(b)) => vals = packed(b)?,
+                                (8, Value::Bytes(b)) => refs = packed_delta(b)?,
+                                _ => {}
+                            }
+                            Ok(())
+                        })?;
+                        data.ways.push(OsmWay {
+                            nodes: refs,
+                            tags: keys
+                                .into_iter()
+                                .zip(vals)
+                                .map(|(k, v)| (string(k), string(v)))
+                                .collect(),
+                        });
+                    }
+                    _ => {}
+                }
+                Ok(())
+            })?;
+        }
+
+        Ok(())
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::{packed_delta, read_file, zigzag, Reader};
+        use crate::map::procgen::osm::{OsmData, OsmError};
+
+        fn varint(mut v: u64, out: &mut Vec<u8>) {
+            while v >= 0x80 {
+                out.push((v as u8 & 0x7F) | 0x80);
+                v >>= 7;
+            }
+            out.push(v as u8);
+        }
+
+        fn sint(v: i64) -> u64 {
+            ((v << 1) ^ (v >> 63)) as u64
+        }
+
+        fn field_varint(id: u64, v: u64, out: &mut Vec<u8>) {
+            varint(id << 3, out);
+            varint(v, out);
+        }
+
+        fn field_bytes(id: u64, b: &[u8], out: &mut Vec<u8>) {
+            varint(id << 3 | 2, out);
+            varint(b.len() as u64, out);
+            out.extend_from_slice(b);
+        }
+
+        fn packed_deltas(values: &[i64]) -> Vec<u8> {
+            let mut out = vec![];
+            let mut prev = 0;
+            for &v in values {
+                varint(sint(v - prev), &mut out);
+                prev = v;
+            }
+            out
+        }
+
+        /// A primitive block with two dense nodes and a residential way between them
+        fn block() -> Vec<u8> {
+            let mut strings = vec![];
+            for s in ["", "highway", "residential"] {
+                field_bytes(1, s.as_bytes(), &mut strings);
+            }
+
+            let mut dense = vec![];
+            field_bytes(1, &packed_deltas(&[1, 2]), &mut dense);
+            field_bytes(8, &packed_deltas(&[488_500_000, 488_510_000]), &mut dense);
+            field_bytes(9, &packed_deltas(&[23_000_000, 23_000_000]), &mut dense);
+
+            let mut way = vec![];
+            field_varint(1, 10, &mut way);
+            field_bytes(2, &[1], &mut way);
+            field_bytes(3, &[2], &mut way);
+            field_bytes(8, &packed_deltas(&[1, 2]), &mut way);
+
+            let mut group = vec![];
+            field_bytes(2, &dense, &mut group);
+            field_bytes(3, &way, &mut group);
+
+            let mut block = vec![];
+            field_bytes(1, &strings, &mut block);
+            field_bytes(2, &group, &mut block);
+            block
+        }
+
+        fn blob(kind: &str, blob: &[u8], out: &mut Vec<u8>) {
+            let mut header = vec![];
+            field_bytes(1, kind.as_bytes(), &mut header);
+            field_varint(3, blob.len() as u64, &mut header);
+
+            out.extend_from_slice(&(header.len() as u32).to_be_bytes());
+            out.extend_from_slice(&header);
+            out.extend_from_slice(blob);
+        }
+
+        fn raw_blob(block: &[u8]) -> Vec<u8> {
+            let mut raw = vec![];
+            field_bytes(1, block, &mut raw);
+            raw
+        }
+
+        #[test]
+        fn varints() {
+            let mut r = Reader {
+                buf: &[0xAC, 0x02, 0x01],
+            };
+            assert_eq!(r.varint().unwrap(), 300);
+            assert_eq!(r.varint().unwrap(), 1);
+            assert!(matches!(r.varint(), Err(OsmError::Pbf(_))));
+
+            let mut r = Reader { buf: &[0xFF; 11] };
+            assert!(matches!(r.varint(), Err(OsmError::Pbf("varint too long"))));
+
+            assert_eq!(zigzag(sint(-3)), -3);
+            assert_eq!(zigzag(sint(1 << 40)), 1 << 40);
+            assert_eq!(
+                packed_delta(&packed_deltas(&[5, 3, 10])).unwrap(),
+                vec![5, 3, 10]
+            );
+        }
+
+        #[test]
+        fn raw_and_zlib_blobs() {
+            let mut header = vec![];
+            field_bytes(4, b"OsmSchema-V0.6", &mut header);
+
+            let mut file = vec![];
+            blob("OSMHeader", &raw_blob(&header), &mut file);
+            blob("OSMData", &raw_blob(&block()), &mut file);
+
+            let mut data = OsmData::default();
+            read_file(&file, &mut data).unwrap();
+            assert_eq!(data.nodes.len(), 2);
+            let (lat, lon) = data.nodes[&2];
+            assert!((lat - 48.851).abs() < 1e-9 && (lon - 2.3).abs() < 1e-9);
+            assert_eq!(data.ways.len(), 1);
+            assert_eq!(data.ways[0].nodes, vec![1, 2]);
+            assert_eq!(data.ways[0].tag("highway"), Some("residential"));
+
+            let mut zlib = vec![];
+            field_bytes(
+                3,
+                &miniz_oxide::deflate::compress_to_vec_zlib(&block(), 6),
+                &mut zlib,
+            );
+            let mut file = vec![];
+            blob("OSMData", &zlib, &mut file);
+
+            let mut data2 = OsmData::default();
+            read_file(&file, &mut data2).unwrap();
+            assert_eq!(data2.nodes, data.nodes);
+            assert_eq!(data2.ways[0].nodes, data.ways[0].nodes);
+        }
+
+        #[test]
+        fn malformed_files() {
+            let mut file = vec![];
+            blob("OSMData", &raw_blob(&block()), &mut file);
+
+            let mut data = OsmData::default();
+            for len in [2, 10, file.len() - 1] {
+                assert!(
+                    matches!(read_file(&file[..len], &mut data), Err(OsmError::Pbf(_))),
+                    "truncated to {len}"
+                );
+            }
+
+            let mut lzma = vec![];
+            field_bytes(4, b"xx", &mut lzma);
+            let mut file = vec![];
+            blob("OSMData", &lzma, &mut file);
+            assert!(matches!(
+                read_file(&file, &mut data),
+                Err(OsmError::Pbf("unsupported blob compression"))
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::LaneKind;
+
+    const SAMPLE: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<osm version="0.6">
+  <node id="1" lat="48.8500" lon="2.3000"/>
+  <node id="2" lat="48.8510" lon="2.3000"/>
+  <node id="3" lat="48.8520" lon="2.3000"/>
+  <node id="4" lat="48.8510" lon="2.3015"/>
+  <node id="5" lat="48.8510" lon="2.2985"/>
+  <node id="6" lat="48.9000" lon="2.2985"/>
+  <way id="10">
+    <nd ref="1"/><nd ref="2"/><nd ref="3"/>
+    <tag k="highway" v="primary"/>
+    <tag k="lanes" v="4"/>
+    <tag k="name" v="Rue de Test"/>
+  </way>
+  <way id="11">
+    <nd ref="5"/><nd ref="2"/><nd ref="4"/>
+    <tag k="highway" v="residential"/>
+    <tag k="oneway" v="yes"/>
+  </way>
+  <way id="12">
+    <nd ref="1"/><nd ref="6"/>
+    <tag k="highway" v="footway"/>
+  </way>
+</osm>"#;
+
+    #[test]
+    fn parse_xml() {
+        let data = OsmData::from_xml(SAMPLE.as_bytes()).unwrap();
+        assert_eq!(data.ways.len(), 2);
+        assert_eq!(data.nodes.len(), 5);
+        assert!(!data.ways[0].tags.contains_key("name"));
+
+        let pat = way_pattern(&data.ways[0]).unwrap();
+        assert_eq!(pat.lanes_forward.len(), pat.lanes_backward.len());
+        let pat = way_pattern(&data.ways[1]).unwrap();
+        assert!(pat
+            .lanes_backward
+            .iter()
+            .all(|(kind, _)| *kind == LaneKind::Walking));
+    }
+
+    #[test]
+    fn shared_segments_are_built_once() {
+        let mut data = OsmData::from_xml(SAMPLE.as_bytes()).unwrap();
+        // follows way 10 on its first segment then turns east
+        let mut way = data.ways[0].clone();
+        way.nodes = vec![1, 2, 4];
+        data.ways.push(way);
+
+        let mut m = Map::empty();
+        load_osm(&mut m, &data, Vec2::ZERO);
+        m.check_invariants();
+        assert_eq!(m.roads().len(), 4);
+        assert!(m.intersections().values().all(|i| i.roads.len() != 2));
+    }
+
+    #[test]
+    fn osm_valid() {
+        let data = OsmData::from_xml(SAMPLE.as_bytes()).unwrap();
+        let mut m = Map::empty();
+        load_osm(&mut m, &data, Vec2::ZERO);
+        m.check_invariants();
+        assert_eq!(m.intersections().len(), 5);
+        assert_eq!(m.roads().len(), 4);
+    }
+}
@@ -1,9 +1,10 @@
 use crate::map::procgen::heightmap;
-use crate::map::procgen::heightmap::tree_density;
+use crate::map::procgen::heightmap::{tree_density, WorldGenOptions};
+use crate::map::{Water, WaterSource};
 use crate::utils::time::Tick;
 use common::timestep::UP_DT;
 use flat_spatial::Grid;
-use geom::{lerp, vec2, Intersect, Radians, Ray3, Vec2, Vec3, AABB};
+use geom::{lerp, vec2, Intersect, Radians, Ray3, Segment, Vec2, Vec3, AABB, OBB};
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
@@ -17,6 +18,9 @@ const CELL_SIZE: f32 = TerrainChunkID::SIZE_F32 / TERRAIN_CHUNK_RESOLUTION as f3
 
 const TREE_GRID_SIZE: usize = 256;
 
+/// Flow of the spring at the start of generated rivers, in m³/s
+const RIVER_FLOW: f32 = 10.0;
+
 pub type Chunk = geom::HeightmapChunk<TERRAIN_CHUNK_RESOLUTION, { TerrainChunkID::SIZE }>;
 pub type Heightmap = geom::Heightmap<TERRAIN_CHUNK_RESOLUTION, { TerrainChunkID::SIZE }>;
 
@@ -32,6 +36,7 @@ pub struct Tree {
 pub struct Environment {
     heightmap: Heightmap,
     pub trees: Grid<Tree, Vec2>,
+    pub water: Water,
 }
 
 #[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -53,14 +58,19 @@ impl Default for Environment {
 
 impl Environment {
     pub fn new(w: u16, h: u16) -> Self {
+        Self::generate(w, h, &WorldGenOptions::default())
+    }
+
+    pub fn generate(w: u16, h: u16, opts: &WorldGenOptions) -> Self {
         let mut me = Self {
             heightmap: Heightmap::new(w, h),
             trees: Grid::new(TREE_GRID_SIZE as i32),
+            water: Water::new(w, h),
         };
         for y in 0..h {
             let chunks: Vec<_> = (0..w)
                 .into_par_iter()
-                .map(|x| me.generate_chunk((x, y)))
+                .map(|x| me.generate_chunk((x, y), opts))
                 .collect();
             for (x, chunk) in (0..w).zip(chunks) {
                 if let Some((v, trees)) = chunk {
@@ -71,13 +81,78 @@ impl Environment {
                 }
             }
         }
+        for river in heightmap::river_paths(opts, me.bounds()) {
+            me.carve_river(&river, opts);
+
+            // Rivers starting above the sea level are fed by a spring
+            if me.height(river[0]).is_some_and(|h| h > 0.0) {
+                me.water.add_source(WaterSource {
+                    pos: river[0],
+                    flow: RIVER_FLOW,
+                });
+            }
+        }
         me
     }
 
+    /// Digs a river bed along the path and removes the trees that were in it
+    fn carve_river(&mut self, path: &[Vec2], opts: &WorldGenOptions) {
+        const WIDTH: f32 = 25.0;
+        const DEPTH: f32 = 8.0;
+
+        let center = self.bounds().center();
+        for w in path.windows(2) {
+            let seg = Segment::new(w[0], w[1]);
+            let bbox = AABB::new(w[0].min(w[1]), w[0].max(w[1])).expand(WIDTH);
+            self.terrain_apply(bbox, |pos| {
+                let d = seg.project(pos.xy()).distance(pos.xy()) / WIDTH;
+                if d >= 1.0 {
+                    return pos.z;
+                }
+                pos.z
+                    .min(heightmap::elevation(pos.xy(), center, opts) - DEPTH * (1.0 - d * d))
+            });
+
+            let Some(dir) = (w[1] - w[0]).try_normalize() else {
+                continue;
+            };
+            let obb = OBB::new(
+                (w[0] + w[1]) * 0.5,
+                dir,
+                w[0].distance(w[1]) + WIDTH,
+                WIDTH * 2.0,
+            );
+            self.remove_trees_near(obb, |_| {});
+        }
+    }
+
     pub fn height(&self, pos: Vec2) -> Option<f32> {
         self.heightmap.height(pos)
     }
 
+    /// Height of the water surface, the sea level where the terrain is under 0
+    pub fn water_surface(&self, pos: Vec2) -> Option<f32> {
+        let h = self.height(pos)?;
+        if h < 0.0 {
+            return Some(0.0);
+        }
+        Some(h + self.water.depth(pos))
+    }
+
+    /// Depth of water (sea or fresh water) at the given position
+    pub fn water_depth(&self, pos: Vec2) -> f32 {
+        match self.height(pos) {
+            Some(h) if h < 0.0 => -h,
+            Some(_) => self.water.depth(pos),
+            None => 0.0,
+        }
+    }
+
+    /// Simulates one step of the water flow, returns the chunks where the water changed
+    pub fn step_water(&mut self) -> Vec<TerrainChunkID> {
+        self.water.step(&self.heightmap)
+    }
+
     pub fn remove_trees_near(
         &mut self,
         obj: impl Intersect<Vec2>,
@@ -153,7 +228,7 @@ impl Environment {
         slope: Option<(Vec3, Vec3)>,
     ) -> Vec<TerrainChunkID> {
         let bbox = AABB::centered(center, Vec2::splat(radius * 2.0));
-        match kind {
+        let modified = match kind {
             TerraformKind::Elevation => self.terrain_apply(bbox, |pos| {
                 let dist = pos.xy().distance(center) / radius;
                 if dist >= 1.0 {
@@ -223,23 +298,24 @@ impl Environment {
                     .map(|(x, y)| TerrainChunkID::new_i16(x as i16, y as i16))
                     .collect()
             }
-        }
+        };
+        self.water.activate(modified.iter().copied());
+        modified
     }
 
-    fn generate_chunk(&self, (x, y): (u16, u16)) -> Option<(Chunk, Vec<Tree>)> {
+    fn generate_chunk(
+        &self,
+        (x, y): (u16, u16),
+        opts: &WorldGenOptions,
+    ) -> Option<(Chunk, Vec<Tree>)> {
         let mut heights = [[0.0; TERRAIN_CHUNK_RESOLUTION]; TERRAIN_CHUNK_RESOLUTION];
 
+        let center = self.bounds().center();
         let offchunk = vec2(x as f32, y as f32) * TerrainChunkID::SIZE_F32;
         for (y, l) in heights.iter_mut().enumerate() {
             for (x, h) in l.iter_mut().enumerate() {
                 let offcell = vec2(x as f32, y as f32) * CELL_SIZE;
-                let mut rh = heightmap::height(offchunk + offcell).0 - 0.12;
-
-                if rh > 0.0 {
-                    rh = 0.0;
-                }
-
-                *h = 1000.0 * rh;
+                *h = heightmap::elevation(offchunk + offcell, center, opts);
             }
         }
 
@@ -264,7 +340,7 @@ impl Environment {
 
                 let sample = cellpos + vec2(jitterx, jittery) * TCELLW;
 
-                let tdens = tree_density(pchunk + sample);
+                let tdens = tree_density(pchunk + sample, opts);
 
                 if dens_test < tdens && chunk.height_unchecked(sample) >= 0.0 {
                     trees.push(Tree::new(pchunk + sample));
@@ -318,12 +394,14 @@ pub fn to_pos(encoded: SmolTree, chunk: (u32, u32)) -> Vec2 {
 struct SerializedEnvironment {
     h: Heightmap,
     trees: Vec<((u32, u32), Vec<SmolTree>)>,
+    water: Water,
 }
 
 impl From<SerializedEnvironment> for Environment {
     fn from(ser: SerializedEnvironment) -> Self {
         let mut terrain = Environment {
             heightmap: ser.h,
+            water: ser.water,
             ..Self::default()
         };
 
@@ -342,6 +420,7 @@ impl From<&Environment> for SerializedEnvironment {
         let mut t = SerializedEnvironment {
             h: ter.heightmap.clone(),
             trees: Vec::new(),
+            water: ter.water.clone(),
         };
 
         for (cell_id, chunk) in ter.trees.storage().cells.iter() {
@@ -1,6 +1,9 @@
-use crate::map::{Intersection, IntersectionID, LaneID, LaneKind, Lanes, Roads, TurnID, TurnKind};
+use crate::map::{
+    Intersection, IntersectionID, LaneID, LaneKind, Lanes, Road, Roads, TurnID, TurnKind,
+};
 use egui_inspect::{Inspect, OptionDefault};
 use geom::{vec2, Vec2};
+use ordered_float::OrderedFloat;
 use serde::{Deserialize, Serialize};
 use std::iter::{Extend, Iterator};
 
@@ -160,6 +163,11 @@ impl TurnPolicy {
             _ => {}
         }
 
+        if let Some(merge) = Self::merge_turns(inter, roads) {
+            turns.extend(merge);
+            return;
+        }
+
         let n_roads = inter.roads.len();
 
         for (i1, road1) in inter.roads.iter().enumerate() {
@@ -197,6 +205,98 @@ impl TurnPolicy {
         }
     }
 
+    /// Turns of a ramp merging into (or diverging from) a road: three one-way roads without
+    /// sidewalks, two of them continuing the third one. The lanes follow each other instead of
+    /// crossing, the ramp joins or leaves the main road by its outermost lanes.
+    fn merge_turns(inter: &Intersection, roads: &Roads) -> Option<Vec<(TurnID, TurnKind)>> {
+        let &[r1, r2, r3] = inter.roads.as_slice() else {
+            return None;
+        };
+        let rs = [roads.get(r1)?, roads.get(r2)?, roads.get(r3)?];
+        if rs
+            .iter()
+            .any(|r| !r.is_one_way() || r.lanes_iter().any(|(_, kind)| !kind.vehicles()))
+        {
+            return None;
+        }
+
+        let is_incoming = |r: &Road| !r.incoming_lanes_to(inter.id).is_empty();
+        let merging = match rs.iter().filter(|r| is_incoming(r)).count() {
+            2 => true,
+            1 => false,
+            _ => return None,
+        };
+        // the road flowing the other way: where the traffic merges to or diverges from
+        let lone_i = rs.iter().position(|r| is_incoming(r) != merging)?;
+        let lone = rs[lone_i];
+        let (a, b) = (rs[(lone_i + 1) % 3], rs[(lone_i + 2) % 3]);
+
+        let lone_dir = lone.dir_from(inter.id);
+        let alignment = |r: &Road| -r.dir_from(inter.id).dot(lone_dir);
+        if alignment(a) < 0.5 || alignment(b) < 0.5 {
+            return None;
+        }
+
+        // lanes are ordered from left to right in the direction of travel
+        let lanes_of = |r: &Road| {
+            if is_incoming(r) {
+                filter_vehicles(r.incoming_lanes_to(inter.id))
+            } else {
+                filter_vehicles(r.outgoing_lanes_from(inter.id))
+            }
+        };
+
+        // the main road has the most lanes, or is the straightest
+        let key = |r: &Road| (lanes_of(r).len(), OrderedFloat(alignment(r)));
+        let (main, ramp) = if key(a) >= key(b) { (a, b) } else { (b, a) };
+
+        let flow = if merging { lone_dir } else { -lone_dir };
+        let right = flow.perpendicular();
+        let lateral = |r: &Road| {
+            let d = f32::min(20.0, r.points.length() * 0.5);
+            let p = if r.src == inter.id {
+                r.points.point_along(d)
+            } else {
+                r.points.point_along(r.points.length() - d)
+            };
+            (p.xy() - inter.pos.xy()).dot(right)
+        };
+
+        let (mut main_l, mut ramp_l, mut lone_l) = (lanes_of(main), lanes_of(ramp), lanes_of(lone));
+        if main_l.is_empty() || ramp_l.is_empty() || lone_l.is_empty() {
+            return None;
+        }
+        // mirror a ramp on the left so that it is always on the side of the last lanes
+        if lateral(ramp) < lateral(main) {
+            main_l.reverse();
+            ramp_l.reverse();
+            lone_l.reverse();
+        }
+
+        let turn =
+            |src: LaneID, dst: LaneID| (TurnID::new(inter.id, src, dst, false), TurnKind::Driving);
+        let mut turns = vec![];
+        if merging {
+            let last = lone_l.len() - 1;
+            for (i, &l) in main_l.iter().chain(&ramp_l).enumerate() {
+                turns.push(turn(l, lone_l[i.min(last)]));
+            }
+        } else {
+            for (i, &l) in lone_l.iter().enumerate() {
+                match main_l.get(i) {
+                    Some(&dst) => turns.push(turn(l, dst)),
+                    None => turns.push(turn(l, ramp_l[(i - main_l.len()).min(ramp_l.len() - 1)])),
+                }
+            }
+            // no lane dedicated to the exit, it is taken from the outermost one
+            if lone_l.len() <= main_l.len() {
+                let outermost = lone_l[lone_l.len() - 1];
+                turns.extend(ramp_l.iter().map(|&r| turn(outermost, r)));
+            }
+        }
+        Some(turns)
+    }
+
     pub fn generate_walking_turns(
         self,
         inter: &Intersection,
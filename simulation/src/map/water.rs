@@ -0,0 +1,329 @@
+//! Fresh water flowing over the terrain.
+//!
+//! The sea is everything under the height 0 and is not simulated. On top of the land, every cell of
+//! the heightmap holds a depth of fresh water that flows to its lower neighbours at each step.
+//! Water comes from sources (placed at the start of rivers by the world generation), pools in
+//! basins to form lakes and is lost when it reaches the sea or evaporates.
+//!
+//! Only chunks where the water moved recently are simulated, terraforming wakes up the chunks
+//! it modifies.
+
+use crate::map::{Heightmap, TerrainChunkID, TERRAIN_CHUNK_RESOLUTION};
+use geom::Vec2;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+const RES: usize = TERRAIN_CHUNK_RESOLUTION;
+const CELL_SIZE: f32 = Heightmap::CELL_SIZE;
+
+/// Fraction of the surface difference that flows to a neighbour at each step
+const FLOW_RATE: f32 = 0.2;
+
+/// Depth of water lost at each step, in meters
+const EVAPORATION: f32 = 0.0001;
+
+/// Chunks where no cell changed more than this during a step go to sleep
+const ACTIVITY_THRESHOLD: f32 = 0.0005;
+
+/// Simulated time of one step, in seconds
+pub const WATER_STEP_DT: f32 = 0.5;
+
+/// Depth of fresh water in meters, indexed with [y][x] like the heightmap chunks
+pub type WaterChunk = [[f32; RES]; RES];
+
+type ChunkPos = (u16, u16);
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct WaterSource {
+    pub pos: Vec2,
+    /// In m³/s
+    pub flow: f32,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Water {
+    w: u16,
+    h: u16,
+    chunks: BTreeMap<ChunkPos, Box<WaterChunk>>,
+    active: BTreeSet<ChunkPos>,
+    sources: Vec<WaterSource>,
+}
+
+impl Water {
+    /// Creates a dry world of w * h chunks
+    pub fn new(w: u16, h: u16) -> Self {
+        Self {
+            w,
+            h,
+            ..Default::default()
+        }
+    }
+
+    pub fn add_source(&mut self, source: WaterSource) {
+        if let Some(chunk) = self.chunk_of(source.pos) {
+            self.active.insert(chunk);
+        }
+        self.sources.push(source);
+    }
+
+    pub fn sources(&self) -> &[WaterSource] {
+        &self.sources
+    }
+
+    /// Depth of fresh water at the given position, 0 when dry
+    pub fn depth(&self, p: Vec2) -> f32 {
+        if p.x < 0.0 || p.y < 0.0 {
+            return 0.0;
+        }
+        self.depth_idx((p.x / CELL_SIZE) as usize, (p.y / CELL_SIZE) as usize)
+    }
+
+    /// Depth of fresh water of the cell at the given global index
+    pub fn depth_idx(&self, x: usize, y: usize) -> f32 {
+        let Some(chunk) = self.chunks.get(&((x / RES) as u16, (y / RES) as u16)) else {
+            return 0.0;
+        };
+        chunk[y % RES][x % RES]
+    }
+
+    pub fn chunk(&self, id: TerrainChunkID) -> Option<&WaterChunk> {
+        self.chunks.get(&(id.0 as u16, id.1 as u16)).map(|x| &**x)
+    }
+
+    /// Iterates over the wet chunks
+    pub fn chunks(&self) -> impl Iterator<Item = (TerrainChunkID, &WaterChunk)> {
+        self.chunks
+            .iter()
+            .map(|(&(x, y), c)| (TerrainChunkID::new_i16(x as i16, y as i16), &**c))
+    }
+
+    pub fn n_active_chunks(&self) -> usize {
+        self.active.len()
+    }
+
+    /// Wakes up the given chunks and their neighbours, for example after they were terraformed
+    pub fn activate(&mut self, ids: impl IntoIterator<Item = TerrainChunkID>) {
+        for id in ids {
+            for dy in -1..=1 {
+                for dx in -1..=1 {
+                    let (x, y) = (id.0 as i32 + dx, id.1 as i32 + dy);
+                    if x < 0 || y < 0 || x >= self.w as i32 || y >= self.h as i32 {
+                        continue;
+                    }
+                    self.active.insert((x as u16, y as u16));
+                }
+            }
+        }
+    }
+
+    fn chunk_of(&self, p: Vec2) -> Option<ChunkPos> {
+        if p.x < 0.0 || p.y < 0.0 {
+            return None;
+        }
+        let size = TerrainChunkID::SIZE_F32;
+        let c = ((p.x / size) as u16, (p.y / size) as u16);
+        (c.0 < self.w && c.1 < self.h).then_some(c)
+    }
+
+    /// Simulates one step of [`WATER_STEP_DT`] seconds, returns the chunks that changed
+    pub fn step(&mut self, heightmap: &Heightmap) -> Vec<TerrainChunkID> {
+        if self.active.is_empty() {
+            return vec![];
+        }
+
+        for source in &self.sources {
+            let Some(c) = self.chunk_of(source.pos) else {
+                continue;
+            };
+            let x = (source.pos.x / CELL_SIZE) as usize % RES;
+            let y = (source.pos.y / CELL_SIZE) as usize % RES;
+            let chunk = self.chunks.entry(c).or_default();
+            chunk[y][x] += source.flow * WATER_STEP_DT / (CELL_SIZE * CELL_SIZE);
+            self.active.insert(c);
+        }
+
+        let (gw, gh) = (self.w as usize * RES, self.h as usize * RES);
+        let terrain = |x: usize, y: usize| heightmap.height_idx(x, y).unwrap_or(0.0);
+
+        let mut deltas: BTreeMap<ChunkPos, Box<WaterChunk>> = BTreeMap::new();
+        let mut add_delta = |x: usize, y: usize, v: f32| {
+            let d = deltas
+                .entry(((x / RES) as u16, (y / RES) as u16))
+                .or_default();
+            d[y % RES][x % RES] += v;
+        };
+
+        for &(cx, cy) in &self.active {
+            let Some(chunk) = self.chunks.get(&(cx, cy)) else {
+                continue;
+            };
+            for (ly, row) in chunk.iter().enumerate() {
+                for (lx, &depth) in row.iter().enumerate() {
+                    if depth <= 0.0 {
+                        continue;
+                    }
+                    let x = cx as usize * RES + lx;
+                    let y = cy as usize * RES + ly;
+                    let t = terrain(x, y);
+                    if t < 0.0 {
+                        // The terrain was dug under the sea level
+                        add_delta(x, y, -depth);
+                        continue;
+                    }
+                    let surface = t + depth;
+
+                    let mut flows = [(0, 0, 0.0, false); 4];
+                    let mut total = 0.0;
+                    for (i, (nx, ny)) in [
+                        (x.wrapping_sub(1), y),
+                        (x + 1, y),
+                        (x, y.wrapping_sub(1)),
+                        (x, y + 1),
+                    ]
+                    .into_iter()
+                    .enumerate()
+                    {
+                        if nx >= gw || ny >= gh {
+                            continue;
+                        }
+                        let nt = terrain(nx, ny);
+                        let to_sea = nt < 0.0;
+                        let nsurface = if to_sea {
+                            0.0
+                        } else {
+                            nt + self.depth_idx(nx, ny)
+                        };
+                        let diff = surface - nsurface;
+                        if diff > 0.0 {
+                            flows[i] = (nx, ny, diff * FLOW_RATE, to_sea);
+                            total += diff * FLOW_RATE;
+                        }
+                    }
+                    if total <= 0.0 {
+                        continue;
+                    }
+
+                    let scale = if total > depth { depth / total } else { 1.0 };
+                    for (nx, ny, flow, to_sea) in flows {
+                        if flow <= 0.0 {
+                            continue;
+                        }
+                        add_delta(x, y, -flow * scale);
+                        // Fresh water reaching the sea is lost
+                        if !to_sea {
+                            add_delta(nx, ny, flow * scale);
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut changed = BTreeSet::new();
+        let mut still_active = BTreeSet::new();
+        for &c in self.active.iter().chain(deltas.keys()) {
+            if !changed.insert(c) {
+                continue;
+            }
+
+            let delta = deltas.get(&c);
+            if delta.is_none() && !self.chunks.contains_key(&c) {
+                continue;
+            }
+            let chunk = self.chunks.entry(c).or_default();
+
+            let mut maxchange: f32 = 0.0;
+            let mut wet = false;
+            for (y, row) in chunk.iter_mut().enumerate() {
+                for (x, v) in row.iter_mut().enumerate() {
+                    let d = delta.map_or(0.0, |d| d[y][x]);
+                    let old = *v;
+                    *v = (*v + d - EVAPORATION).max(0.0);
+                    maxchange = maxchange.max((*v - old).abs());
+                    wet |= *v > 0.0;
+                }
+            }
+
+            if !wet {
+                self.chunks.remove(&c);
+                continue;
+            }
+            if maxchange > ACTIVITY_THRESHOLD {
+                still_active.insert(c);
+            }
+        }
+
+        for source in &self.sources {
+            if let Some(c) = self.chunk_of(source.pos) {
+                still_active.insert(c);
+            }
+        }
+        self.active = still_active;
+
+        changed
+            .into_iter()
+            .map(|(x, y)| TerrainChunkID::new_i16(x as i16, y as i16))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::{Environment, Map};
+    use geom::{vec2, AABB};
+
+    fn total_volume(water: &Water) -> f32 {
+        water
+            .chunks()
+            .flat_map(|(_, c)| c.iter().flatten())
+            .sum::<f32>()
+            * CELL_SIZE
+            * CELL_SIZE
+    }
+
+    #[test]
+    fn water_spreads_and_drains() {
+        let mut env = Environment::new(2, 2);
+        env.terrain_apply(env.bounds(), |_| 1.0);
+        let source = vec2(300.0, 300.0);
+        env.water.add_source(WaterSource {
+            pos: source,
+            flow: 10.0,
+        });
+
+        for _ in 0..200 {
+            env.step_water();
+        }
+        assert!(env.water.depth(source) > 0.0);
+        assert!(env.water.depth(source + vec2(50.0, 0.0)) > 0.0);
+        let volume = total_volume(&env.water);
+        assert!(volume > 0.0 && volume <= 10.0 * WATER_STEP_DT * 200.0);
+
+        // dig a channel to the sea, the water drains away
+        env.terrain_apply(AABB::new(vec2(0.0, 290.0), vec2(1024.0, 310.0)), |_| -5.0);
+        env.water.sources.clear();
+        for _ in 0..2000 {
+            env.step_water();
+        }
+        assert!(total_volume(&env.water) < volume * 0.5);
+    }
+
+    #[test]
+    fn roads_across_water_need_bridges() {
+        let mut m = Map::empty();
+        m.environment = Environment::new(2, 2);
+        m.environment.terrain_apply(m.environment.bounds(), |p| {
+            if (p.x - 500.0).abs() < 50.0 {
+                -10.0
+            } else {
+                0.0
+            }
+        });
+
+        let a = vec2(300.0, 500.0);
+        let b = vec2(700.0, 500.0);
+        assert!(m.road_needs_bridge(a.z(0.0), b.z(0.0), None));
+        assert!(!m.road_needs_bridge(a.z(5.0), b.z(5.0), None));
+        assert!(!m.road_needs_bridge(a.z(0.0), vec2(300.0, 900.0).z(0.0), None));
+    }
+}
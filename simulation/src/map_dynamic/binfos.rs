@@ -8,6 +8,9 @@ use std::collections::BTreeMap;
 pub struct BuildingInfo {
     pub owner: Option<SoulID>,
     pub inside: Vec<SoulID>,
+    /// The building stands in water and cannot be used
+    #[serde(default)]
+    pub flooded: bool,
 }
 
 #[derive(Clone, Default, Serialize, Deserialize, Debug)]
@@ -40,6 +43,10 @@ impl BuildingInfos {
         self.owners.insert(soul, building);
     }
 
+    pub fn is_flooded(&self, building: BuildingID) -> bool {
+        self.assignment.get(building).is_some_and(|x| x.flooded)
+    }
+
     pub fn owner(&self, building: BuildingID) -> Option<SoulID> {
         self.assignment.get(building).and_then(|x| x.owner)
     }
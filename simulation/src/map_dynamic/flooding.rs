@@ -0,0 +1,41 @@
+use crate::map::{Map, TerrainChunkID, WATER_STEP_DT};
+use crate::map_dynamic::BuildingInfos;
+use crate::utils::resources::Resources;
+use crate::utils::time::{Tick, TICKS_PER_SECOND};
+use crate::World;
+use common::FastSet;
+
+/// Depth of fresh water above which a building is flooded, in meters
+pub const FLOOD_DEPTH: f32 = 0.3;
+
+/// Steps the water simulation and marks the buildings standing in water as flooded
+pub fn water_update(_: &mut World, resources: &mut Resources) {
+    profiling::scope!("map_dynamic::water_update");
+    let tick = resources.read::<Tick>().0;
+    let step_ticks = (WATER_STEP_DT * TICKS_PER_SECOND as f32) as u64;
+    if !tick.is_multiple_of(step_ticks) {
+        return;
+    }
+
+    let mut map = resources.write::<Map>();
+    let changed: FastSet<TerrainChunkID> = map.environment.step_water().into_iter().collect();
+    if changed.is_empty() {
+        return;
+    }
+
+    let mut binfos = resources.write::<BuildingInfos>();
+    for b in map.buildings().values() {
+        let center = b.obb.center();
+        if !changed.contains(&TerrainChunkID::new(center)) {
+            continue;
+        }
+        let flooded = map.environment.water.depth(center) > FLOOD_DEPTH;
+        let Some(info) = binfos.get_mut(b.id) else {
+            continue;
+        };
+        if info.flooded != flooded {
+            log::info!("building {:?} flooded: {}", b.id, flooded);
+            info.flooded = flooded;
+        }
+    }
+}
@@ -1,11 +1,13 @@
 mod binfos;
 mod dispatch;
+mod flooding;
 mod itinerary;
 mod parking;
 mod router;
 
 pub use binfos::*;
 pub use dispatch::*;
+pub use flooding::*;
 pub use itinerary::*;
 pub use parking::*;
 pub use router::*;
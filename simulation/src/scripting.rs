@@ -0,0 +1,427 @@
+//! Embedded scripting with [Rhai](https://rhai.rs).
+//!
+//! Scripts can read the simulation (map queries, entity positions, market state) and emit
+//! [`WorldCommand`]s. They run in two ways:
+//! - As macros, run once from the UI with [`run_macro`]. The emitted commands are pushed
+//!   into the player's commands like any other action.
+//! - As simulation scripts, installed with [`WorldCommand::SetScript`] and stored in the save.
+//!   Their `on_tick` function is called every tick by [`run_scripts`] and the emitted commands are
+//!   applied right away. Since every client runs the same scripts at the same tick, they must be
+//!   deterministic: the engine has no access to the clock and no randomness.
+//!
+//! ```rhai
+//! fn on_tick() {
+//!     if tick() % 500 == 0 && n_vehicles() < 100 {
+//!         spawn_random_cars(10);
+//!     }
+//! }
+//! ```
+
+use crate::economy::{Government, ItemRegistry, Market};
+use crate::map::{BuildingID, LanePatternBuilder, MapProject, ProjectFilter, ProjectKind};
+use crate::multiplayer::chat::{Message, MessageKind};
+use crate::utils::time::{GameTime, Tick};
+use crate::world_command::WorldCommand;
+use crate::{Replay, Simulation};
+use geom::{Color, Vec2};
+use rhai::{Array, CallFnOptions, Dynamic, Engine, EvalAltResult, Scope, AST, FLOAT, INT};
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+
+/// Maximum number of operations a script can do in one run, so that an infinite loop
+/// doesn't freeze the game
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct Scripts {
+    scripts: BTreeMap<String, Script>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Script {
+    pub source: String,
+    pub enabled: bool,
+    /// Why the script was disabled, if it failed
+    pub error: Option<String>,
+    #[serde(skip)]
+    ast: Option<AST>,
+}
+
+impl Scripts {
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Script)> {
+        self.scripts.iter()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Script> {
+        self.scripts.get(name)
+    }
+
+    /// Installs or replaces a script, it is disabled if it doesn't compile
+    pub fn set(&mut self, name: String, source: String) {
+        let mut script = Script {
+            source,
+            enabled: true,
+            error: None,
+            ast: None,
+        };
+        if let Err(e) = script.compiled() {
+            log::warn!("script {} doesn't compile: {}", name, e);
+            script.enabled = false;
+            script.error = Some(e);
+        }
+        self.scripts.insert(name, script);
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        self.scripts.remove(name);
+    }
+}
+
+impl Script {
+    fn compiled(&mut self) -> Result<&AST, String> {
+        if self.ast.is_none() {
+            self.ast = Some(ENGINE.compile(&self.source).map_err(|e| e.to_string())?);
+        }
+        Ok(self.ast.as_ref().unwrap())
+    }
+}
+
+/// Runs the `on_tick` function of every enabled script and applies the commands they emitted.
+/// A script that fails is disabled.
+pub fn run_scripts(sim: &mut Simulation) {
+    profiling::scope!("scripting::run_scripts");
+    let mut commands = vec![];
+    {
+        let mut scripts = sim.write::<Scripts>();
+        for (name, script) in scripts.scripts.iter_mut().filter(|(_, s)| s.enabled) {
+            let result = script.compiled().and_then(|ast| {
+                let (result, emitted) = with_context(sim, || {
+                    ENGINE.call_fn_with_options::<Dynamic>(
+                        CallFnOptions::new().eval_ast(false),
+                        &mut Scope::new(),
+                        ast,
+                        "on_tick",
+                        (),
+                    )
+                });
+                result.map(|_| emitted).map_err(|e| e.to_string())
+            });
+
+            match result {
+                Ok(emitted) => commands.extend(emitted),
+                Err(e) => {
+                    log::warn!("script {} failed and was disabled: {}", name, e);
+                    script.enabled = false;
+                    script.error = Some(e);
+                }
+            }
+        }
+    }
+
+    if commands.is_empty() {
+        return;
+    }
+
+    // The scripts emit the same commands again when the replay is played,
+    // so they must not be recorded
+    let replay_enabled = std::mem::replace(&mut sim.write::<Replay>().enabled, false);
+    for command in &commands {
+        command.apply(sim);
+    }
+    sim.write::<Replay>().enabled = replay_enabled;
+}
+
+/// Runs a script once against the simulation and returns the commands it emitted
+pub fn run_macro(sim: &Simulation, source: &str) -> Result<Vec<WorldCommand>, String> {
+    let ast = ENGINE.compile(source).map_err(|e| e.to_string())?;
+    let (result, commands) = with_context(sim, || ENGINE.run_ast(&ast));
+    result.map_err(|e| e.to_string())?;
+    Ok(commands)
+}
+
+thread_local! {
+    static SIM: Cell<*const Simulation> = const { Cell::new(std::ptr::null()) };
+    static COMMANDS: RefCell<Vec<WorldCommand>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Gives the script functions access to the simulation while `f` runs,
+/// returns the result of `f` and the commands emitted meanwhile
+fn with_context<R>(sim: &Simulation, f: impl FnOnce() -> R) -> (R, Vec<WorldCommand>) {
+    struct Reset(*const Simulation);
+    impl Drop for Reset {
+        fn drop(&mut self) {
+            SIM.with(|s| s.set(self.0));
+        }
+    }
+
+    let _reset = Reset(SIM.with(|s| s.replace(sim)));
+    let result = f();
+    (
+        result,
+        COMMANDS.with(|c| std::mem::take(&mut *c.borrow_mut())),
+    )
+}
+
+fn with_sim<R>(f: impl FnOnce(&Simulation) -> R) -> R {
+    SIM.with(|s| {
+        let sim = s.get();
+        assert!(!sim.is_null(), "script function called outside of a script");
+        // Safety: the pointer is only set by with_context, while the simulation is borrowed
+        f(unsafe { &*sim })
+    })
+}
+
+fn emit(command: WorldCommand) {
+    COMMANDS.with(|c| c.borrow_mut().push(command));
+}
+
+type FnResult<T> = Result<T, Box<EvalAltResult>>;
+
+/// Scripts can use integers or floats for coordinates
+fn num(v: &Dynamic) -> FnResult<f32> {
+    if let Ok(f) = v.as_float() {
+        return Ok(f as f32);
+    }
+    if let Ok(i) = v.as_int() {
+        return Ok(i as f32);
+    }
+    Err(format!("expected a number, got {}", v.type_name()).into())
+}
+
+fn pos(x: &Dynamic, y: &Dynamic) -> FnResult<Vec2> {
+    Ok(Vec2::new(num(x)?, num(y)?))
+}
+
+fn project(p: Vec2) -> MapProject {
+    with_sim(|sim| {
+        let map = sim.map();
+        let z = map.environment.height(p).unwrap_or(0.0);
+        map.project(p.z(z), 0.0, ProjectFilter::ALL)
+    })
+}
+
+fn map_of(entries: impl IntoIterator<Item = (&'static str, Dynamic)>) -> rhai::Map {
+    entries.into_iter().map(|(k, v)| (k.into(), v)).collect()
+}
+
+lazy_static::lazy_static! {
+    static ref ENGINE: Engine = make_engine();
+}
+
+fn make_engine() -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+    engine.on_print(|s| log::info!("script: {}", s));
+    engine.on_debug(|s, _, p| log::debug!("script {:?}: {}", p, s));
+
+    engine.register_type_with_name::<BuildingID>("BuildingID");
+    engine.register_fn("to_string", |id: &mut BuildingID| format!("{id:?}"));
+    engine.register_fn("to_debug", |id: &mut BuildingID| format!("{id:?}"));
+
+    // Reading the simulation
+    engine.register_fn("tick", || with_sim(|sim| sim.read::<Tick>().0 as INT));
+    engine.register_fn("money", || {
+        with_sim(|sim| sim.read::<Government>().money.bucks() as INT)
+    });
+    engine.register_fn("n_vehicles", || {
+        with_sim(|sim| sim.world().vehicles.len() as INT)
+    });
+    engine.register_fn("n_humans", || {
+        with_sim(|sim| sim.world().humans.len() as INT)
+    });
+    engine.register_fn("n_trains", || {
+        with_sim(|sim| sim.world().trains.len() as INT)
+    });
+    engine.register_fn("n_companies", || {
+        with_sim(|sim| sim.world().companies.len() as INT)
+    });
+    engine.register_fn("vehicles", || -> Array {
+        with_sim(|sim| {
+            sim.world()
+                .vehicles
+                .values()
+                .map(|v| {
+                    let p = v.trans.position;
+                    Dynamic::from_map(map_of([
+                        ("x", Dynamic::from_float(p.x as FLOAT)),
+                        ("y", Dynamic::from_float(p.y as FLOAT)),
+                        ("z", Dynamic::from_float(p.z as FLOAT)),
+                        ("speed", Dynamic::from_float(v.speed.0 as FLOAT)),
+                    ]))
+                })
+                .collect()
+        })
+    });
+    engine.register_fn("buildings", || -> Array {
+        with_sim(|sim| {
+            sim.map()
+                .buildings()
+                .values()
+                .map(|b| {
+                    let p = b.obb.center();
+                    Dynamic::from_map(map_of([
+                        ("id", Dynamic::from(b.id)),
+                        ("kind", format!("{:?}", b.kind).into()),
+                        ("x", Dynamic::from_float(p.x as FLOAT)),
+                        ("y", Dynamic::from_float(p.y as FLOAT)),
+                    ]))
+                })
+                .collect()
+        })
+    });
+    engine.register_fn("n_roads", || with_sim(|sim| sim.map().roads().len() as INT));
+    engine.register_fn("height", |x: Dynamic, y: Dynamic| -> FnResult<Dynamic> {
+        let p = pos(&x, &y)?;
+        Ok(with_sim(|sim| {
+            sim.map()
+                .environment
+                .height(p)
+                .map_or(Dynamic::UNIT, |h| Dynamic::from_float(h as FLOAT))
+        }))
+    });
+    engine.register_fn("project", |x: Dynamic, y: Dynamic| -> FnResult<rhai::Map> {
+        let proj = project(pos(&x, &y)?);
+        let kind = match proj.kind {
+            ProjectKind::Inter(_) => "intersection",
+            ProjectKind::Road(_) => "road",
+            ProjectKind::Building(_) => "building",
+            ProjectKind::Lot(_) => "lot",
+            ProjectKind::Ground => "ground",
+        };
+        let mut m = map_of([
+            ("kind", kind.into()),
+            ("x", Dynamic::from_float(proj.pos.x as FLOAT)),
+            ("y", Dynamic::from_float(proj.pos.y as FLOAT)),
+            ("z", Dynamic::from_float(proj.pos.z as FLOAT)),
+        ]);
+        if let ProjectKind::Building(id) = proj.kind {
+            m.insert("id".into(), Dynamic::from(id));
+        }
+        Ok(m)
+    });
+    engine.register_fn("market", |item: &str| -> FnResult<rhai::Map> {
+        with_sim(|sim| {
+            let id = sim
+                .read::<ItemRegistry>()
+                .try_id(item)
+                .ok_or_else(|| format!("unknown item {item}"))?;
+            let market = sim.read::<Market>();
+            let (_, m) = market
+                .iter()
+                .find(|(&k, _)| k == id)
+                .ok_or_else(|| format!("no market for {item}"))?;
+            let buy_qty: u32 = m.buy_orders().values().map(|o| o.qty).sum();
+            let sell_qty: u32 = m.sell_orders().values().map(|o| o.qty).sum();
+            Ok(map_of([
+                ("n_buy_orders", (m.buy_orders().len() as INT).into()),
+                ("n_sell_orders", (m.sell_orders().len() as INT).into()),
+                ("buy_qty", (buy_qty as INT).into()),
+                ("sell_qty", (sell_qty as INT).into()),
+                ("ext_value", (m.ext_value.bucks() as INT).into()),
+            ]))
+        })
+    });
+
+    // Emitting commands
+    engine.register_fn("command", |command: Dynamic| -> FnResult<()> {
+        emit(rhai::serde::from_dynamic::<WorldCommand>(&command)?);
+        Ok(())
+    });
+    engine.register_fn(
+        "make_road",
+        |x1: Dynamic, y1: Dynamic, x2: Dynamic, y2: Dynamic| -> FnResult<()> {
+            let from = project(pos(&x1, &y1)?);
+            let to = project(pos(&x2, &y2)?);
+            emit(WorldCommand::MapMakeConnection {
+                from,
+                to,
+                inter: None,
+                pat: LanePatternBuilder::new().build(),
+            });
+            Ok(())
+        },
+    );
+    engine.register_fn("remove_building", |id: BuildingID| {
+        emit(WorldCommand::MapRemoveBuilding(id))
+    });
+    engine.register_fn("spawn_random_cars", |n: INT| {
+        emit(WorldCommand::SpawnRandomCars {
+            n_cars: n.max(0) as usize,
+        })
+    });
+    engine.register_fn("send_message", |text: &str| {
+        let sent_at = with_sim(|sim| sim.read::<GameTime>().instant());
+        emit(WorldCommand::SendMessage {
+            message: Message {
+                name: "script".to_string(),
+                text: text.to_string(),
+                sent_at,
+                color: Color::WHITE,
+                kind: MessageKind::Info,
+            },
+        })
+    });
+
+    engine
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::TestCtx;
+    use geom::vec3;
+
+    #[test]
+    fn macro_emits_commands() {
+        let ctx = TestCtx::new();
+        ctx.build_roads(&[vec3(50.0, 50.0, 0.0), vec3(250.0, 50.0, 0.0)]);
+
+        let commands = run_macro(
+            &ctx.g,
+            r#"
+            if n_roads() > 0 && project(150, 50).kind == "road" {
+                make_road(150, 50, 150, 250);
+                command(#{ SpawnRandomCars: #{ n_cars: 3 } });
+            }
+            "#,
+        )
+        .unwrap();
+        assert!(matches!(
+            commands[0],
+            WorldCommand::MapMakeConnection { .. }
+        ));
+        assert!(matches!(
+            commands[1],
+            WorldCommand::SpawnRandomCars { n_cars: 3 }
+        ));
+
+        assert!(run_macro(&ctx.g, "loop {}").is_err());
+    }
+
+    #[test]
+    fn scripts_run_every_tick() {
+        let mut ctx = TestCtx::new();
+        ctx.build_roads(&[vec3(50.0, 50.0, 0.0), vec3(450.0, 50.0, 0.0)]);
+        ctx.apply(&[
+            WorldCommand::SetScript {
+                name: "cars".to_string(),
+                source: "fn on_tick() { if tick() == 2 { spawn_random_cars(5); } }".to_string(),
+            },
+            WorldCommand::SetScript {
+                name: "broken".to_string(),
+                source: "fn on_tick() { unknown_function(); }".to_string(),
+            },
+        ]);
+
+        for _ in 0..5 {
+            ctx.tick();
+        }
+
+        assert_eq!(ctx.g.world().vehicles.len(), 5);
+        let scripts = ctx.g.read::<Scripts>();
+        assert!(scripts.get("cars").unwrap().enabled);
+        let broken = scripts.get("broken").unwrap();
+        assert!(!broken.enabled && broken.error.is_some());
+    }
+}
@@ -1,3 +1,4 @@
+use crate::events::{EventKind, Events};
 use crate::map::{BuildingID, BuildingKind, Map, PathKind};
 use crate::map_dynamic::{
     BuildingInfos, DispatchID, DispatchKind, DispatchQueryTarget, Dispatcher, Itinerary,
@@ -71,6 +72,7 @@ pub fn freight_station_system(world: &mut World, resources: &mut Resources) {
     let map = resources.read::<Map>();
     let time = resources.read::<GameTime>();
     let tick = *resources.read::<Tick>();
+    let mut events = resources.write::<Events>();
 
     for (me, f) in world.freight_stations.iter_mut() {
         let pos = f.trans;
@@ -141,6 +143,13 @@ pub fn freight_station_system(world: &mut World, resources: &mut Resources) {
             DispatchKind::FreightTrain,
             DispatchQueryTarget::Pos(destination),
         ) else {
+            if station.trains.is_empty() {
+                events.emit(
+                    EventKind::NoFreightTrain(station.building),
+                    Some(pos.position),
+                    &time,
+                );
+            }
             continue;
         };
 
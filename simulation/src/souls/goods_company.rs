@@ -1,5 +1,6 @@
 use super::desire::Work;
-use crate::economy::{find_trade_place, ItemID, ItemRegistry, Market};
+use crate::economy::{find_trade_place, ItemID, ItemRegistry, Market, PowerGrid};
+use crate::events::{EventKind, Events};
 use crate::map::{Building, BuildingID, Map, Zone, MAX_ZONE_AREA};
 use crate::map_dynamic::BuildingInfos;
 use crate::souls::desire::WorkKind;
@@ -31,6 +32,9 @@ pub struct Recipe {
     pub storage_multiplier: i32,
 }
 
+/// Energy of one unit of the electricity item
+const KWH_PER_ELECTRICITY: i32 = 1;
+
 new_key_type! {
     pub struct GoodsCompanyID;
 }
@@ -49,6 +53,10 @@ pub struct GoodsCompanyDescription {
     pub asset_location: String,
     pub price: i64,
     pub zone: Option<Box<ZoneDescription>>,
+    /// Electricity drawn from the grid, in kW
+    pub power_consumption: i32,
+    /// Electricity delivered to the grid at full productivity, in kW
+    pub power_production: i32,
 }
 
 #[derive(Default)]
@@ -67,7 +75,23 @@ impl GoodsCompanyRegistry {
                 }
             };
 
+        // without the item, no company can produce electricity
+        let electricity = registry.try_id("electricity");
+
         for descr in descriptions {
+            // Electricity is not stored nor traded, it goes straight to the grid
+            let mut power_production = 0;
+            let mut production = vec![];
+            for (item, qty) in descr.recipe.production {
+                let item_id = registry.id(&item);
+                if Some(item_id) == electricity {
+                    power_production +=
+                        qty * KWH_PER_ELECTRICITY * 3600 / descr.recipe.complexity.max(1);
+                    continue;
+                }
+                production.push((item_id, qty));
+            }
+
             let recipe = Recipe {
                 consumption: descr
                     .recipe
@@ -78,15 +102,7 @@ impl GoodsCompanyRegistry {
                         (item_id, qty)
                     })
                     .collect(),
-                production: descr
-                    .recipe
-                    .production
-                    .into_iter()
-                    .map(|(item, qty)| {
-                        let item_id = registry.id(&item);
-                        (item_id, qty)
-                    })
-                    .collect(),
+                production,
                 complexity: descr.recipe.complexity,
                 storage_multiplier: descr.recipe.storage_multiplier,
             };
@@ -105,6 +121,8 @@ impl GoodsCompanyRegistry {
                     asset_location: descr.asset_location,
                     price: descr.price,
                     zone: descr.zone,
+                    power_consumption: descr.power_consumption,
+                    power_production,
                 });
 
             #[cfg(not(test))]
@@ -123,11 +141,15 @@ impl Recipe {
         }
     }
 
-    pub fn should_produce(&self, soul: SoulID, market: &Market) -> bool {
-        // Has enough resources
+    pub fn has_inputs(&self, soul: SoulID, market: &Market) -> bool {
         self.consumption
             .iter()
             .all(move |&(kind, qty)| market.capital(soul, kind) >= qty)
+    }
+
+    pub fn should_produce(&self, soul: SoulID, market: &Market) -> bool {
+        // Has enough resources
+        self.has_inputs(soul, market)
             &&
             // Has enough storage
             self.production.iter().all(move |&(kind, qty)| {
@@ -157,12 +179,30 @@ pub struct GoodsCompany {
     pub progress: f32,
     pub driver: Option<HumanID>,
     pub trucks: Vec<VehicleID>,
+    /// Electricity drawn from the grid, in kW
+    pub power_consumption: i32,
+    /// Electricity delivered to the grid at full productivity, in kW
+    pub power_production: i32,
 }
 
 impl GoodsCompany {
-    pub fn productivity(&self, workers: usize, zone: Option<&Zone>) -> f32 {
+    /// Productivity when the grid is balanced
+    pub fn capacity(&self, workers: usize, zone: Option<&Zone>) -> f32 {
         workers as f32 / self.max_workers as f32 * zone.map_or(1.0, |z| z.area / MAX_ZONE_AREA)
     }
+
+    /// Power plants only run as much as the grid needs, and the companies drawing electricity
+    /// slow down during a brownout
+    pub fn productivity(&self, workers: usize, zone: Option<&Zone>, grid: &PowerGrid) -> f32 {
+        let capacity = self.capacity(workers, zone);
+        if self.power_production > 0 {
+            return capacity * grid.load_factor();
+        }
+        if self.power_consumption > 0 {
+            return capacity * grid.brownout_factor();
+        }
+        capacity
+    }
 }
 
 pub fn company_soul(sim: &mut Simulation, company: GoodsCompany) -> Option<SoulID> {
@@ -203,12 +243,16 @@ pub fn company_soul(sim: &mut Simulation, company: GoodsCompany) -> Option<SoulI
 
 pub fn company_system(world: &mut World, res: &mut Resources) {
     profiling::scope!("souls::company_system");
-    let delta = res.read::<GameTime>().realdelta;
+    let time: &GameTime = &res.read();
+    let delta = time.realdelta;
+    let check_inputs = time.tick(10);
+    let mut events = res.write::<Events>();
     let cbuf: &ParCommandBuffer<CompanyEnt> = &res.read();
     let cbuf_human: &ParCommandBuffer<HumanEnt> = &res.read();
     let binfos: &BuildingInfos = &res.read();
     let market: &Market = &res.read();
     let map: &Map = &res.read();
+    let grid: &PowerGrid = &res.read();
 
     world.companies.iter_mut().for_each(|(me, c)| {
         let n_workers = c.workers.0.len();
@@ -218,8 +262,23 @@ pub fn company_system(world: &mut World, res: &mut Resources) {
             return;
         });
 
-        if c.comp.recipe.should_produce(soul, market) {
-            c.comp.progress += c.comp.productivity(n_workers, b.zone.as_ref())
+        if check_inputs {
+            for &(item, qty) in &c.comp.recipe.consumption {
+                events.condition(
+                    EventKind::MissingInput {
+                        company: me,
+                        building: c.comp.building,
+                        item,
+                    },
+                    n_workers > 0 && market.capital(soul, item) < qty,
+                    Some(b.door_pos),
+                    time,
+                );
+            }
+        }
+
+        if c.comp.recipe.should_produce(soul, market) && !binfos.is_flooded(c.comp.building) {
+            c.comp.progress += c.comp.productivity(n_workers, b.zone.as_ref(), grid)
                 / c.comp.recipe.complexity as f32
                 * delta;
         }
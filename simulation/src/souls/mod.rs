@@ -86,6 +86,8 @@ pub(crate) fn add_souls_to_empty_buildings(sim: &mut Simulation) {
             max_workers: des.n_workers,
             progress: 0.0,
             driver: None,
+            power_consumption: des.power_consumption,
+            power_production: des.power_production,
             trucks: {
                 drop(registry);
                 unwrap_or!(mk_trucks(sim), continue)
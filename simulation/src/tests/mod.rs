@@ -10,7 +10,10 @@ use crate::{Simulation, SimulationOptions};
 use common::logger::MyLog;
 use common::saveload::Encoder;
 use geom::{Vec2, Vec3};
+use std::sync::Once;
 
+mod power;
+mod scenario;
 mod test_iso;
 mod vehicles;
 
@@ -21,14 +24,22 @@ pub(crate) struct TestCtx {
 
 impl TestCtx {
     pub(crate) fn new() -> Self {
-        MyLog::init();
-        crate::init::init();
-
-        let g = Simulation::new_with_options(SimulationOptions {
+        Self::with_options(SimulationOptions {
             terrain_size: 1,
             save_replay: false,
             ..Default::default()
+        })
+    }
+
+    pub(crate) fn with_options(opts: SimulationOptions) -> Self {
+        // the systems and resources are registered in globals, only once for all the tests
+        static INIT: Once = Once::new();
+        INIT.call_once(|| {
+            MyLog::init();
+            crate::init::init();
         });
+
+        let g = Simulation::new_with_options(opts);
         let sched = Simulation::schedule();
 
         Self { g, sched }
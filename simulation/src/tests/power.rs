@@ -0,0 +1,174 @@
+use super::scenario::Scenario;
+use super::TestCtx;
+use crate::economy::{ItemRegistry, PowerGrid, BROWNOUT_MIN_PRODUCTIVITY};
+use crate::souls::goods_company::GoodsCompanyRegistry;
+use crate::world_command::WorldCommand;
+use crate::CompanyEnt;
+
+/// Five houses and a bakery along a street, with solar panels at the end of it if `panels`
+fn town(panels: bool) -> TestCtx {
+    let panels = if panels {
+        r#", ["Solar Panels", [600, 180]]"#
+    } else {
+        ""
+    };
+    let source = format!(
+        r#"{{
+            "terrain_size": 2,
+            "roads": [[[50, 100, 0], [700, 100, 0]]],
+            "houses": [[150, 80], [200, 80], [250, 80], [150, 120], [200, 120]],
+            "companies": [["Bakery", [300, 100]]{panels}],
+            "ticks": 0,
+            "expect": []
+        }}"#
+    );
+    Scenario::parse(&source).unwrap().setup().unwrap()
+}
+
+/// Ticks until `done` holds, panics after `max` ticks
+fn tick_until(ctx: &mut TestCtx, max: u64, what: &str, done: impl Fn(&TestCtx) -> bool) {
+    for _ in 0..max {
+        ctx.g.tick(&mut ctx.sched, &[]);
+        if done(ctx) {
+            return;
+        }
+    }
+    panic!("{what} did not happen within {max} ticks");
+}
+
+/// Whether every company has at least one worker
+fn staffed(ctx: &TestCtx) -> bool {
+    let world = ctx.g.world();
+    !world.companies.is_empty() && world.companies.values().all(|c| !c.workers.0.is_empty())
+}
+
+fn company(ctx: &TestCtx, f: impl Fn(&CompanyEnt) -> bool) -> (f32, f32) {
+    let world = ctx.g.world();
+    let map = ctx.g.map();
+    let grid = ctx.g.read::<PowerGrid>();
+    let c = world.companies.values().find(|c| f(c)).unwrap();
+    let zone = map.buildings()[c.comp.building].zone.as_ref();
+    let workers = c.workers.0.len();
+    (
+        c.comp.capacity(workers, zone),
+        c.comp.productivity(workers, zone, &grid),
+    )
+}
+
+#[test]
+fn town_without_power_plant_browns_out() {
+    let mut ctx = town(false);
+    tick_until(&mut ctx, 3000, "a worker at the bakery", staffed);
+
+    let grid = ctx.g.read::<PowerGrid>();
+    // 20 kW per house and 150 kW for the bakery
+    assert_eq!(grid.consumption, 250);
+    assert_eq!(grid.capacity, 0);
+    assert!(grid.is_brownout());
+    drop(grid);
+
+    let (capacity, productivity) = company(&ctx, |c| c.comp.power_consumption > 0);
+    assert!(capacity > 0.0);
+    assert_eq!(productivity, capacity * BROWNOUT_MIN_PRODUCTIVITY);
+}
+
+#[test]
+fn solar_panels_supply_the_town() {
+    let mut ctx = town(true);
+    tick_until(
+        &mut ctx,
+        3000,
+        "workers at the bakery and the panels",
+        staffed,
+    );
+    // the grid is balanced before the new workers start
+    ctx.g.tick(&mut ctx.sched, &[]);
+
+    let grid = ctx.g.read::<PowerGrid>();
+    assert_eq!(grid.consumption, 250);
+    assert!(!grid.is_brownout(), "{} < 250 kW", grid.capacity);
+    assert_eq!(grid.satisfaction(), 1.0);
+    let load_factor = grid.load_factor();
+    assert_eq!(load_factor, 250.0 / grid.capacity as f32);
+    drop(grid);
+
+    // the panels only run as much as the town draws
+    let (capacity, productivity) = company(&ctx, |c| c.comp.power_production > 0);
+    assert_eq!(productivity, capacity * load_factor);
+
+    // the power is gone with the panels
+    let panels = ctx
+        .g
+        .world()
+        .companies
+        .values()
+        .find(|c| c.comp.power_production > 0)
+        .unwrap()
+        .comp
+        .building;
+    WorldCommand::MapRemoveBuilding(panels).apply(&mut ctx.g);
+    ctx.g.tick(&mut ctx.sched, &[]);
+    assert_eq!(ctx.g.read::<PowerGrid>().capacity, 0);
+}
+
+#[test]
+fn electricity_goes_to_the_grid() {
+    let mut items = ItemRegistry::default();
+    items.load_item_definitions(
+        r#"[{"name": "coal", "label": "Coal"}, {"name": "electricity", "label": "Electricity"}]"#,
+    );
+    let mut companies = GoodsCompanyRegistry::default();
+    companies.load(
+        r#"[{
+            "name": "Coal power plant",
+            "bgen": {"kind": "centered_door", "vertical_factor": 1.0},
+            "kind": "network",
+            "recipe": {
+                "consumption": [["coal", 1]],
+                "production": [["electricity", 10], ["coal", 1]],
+                "complexity": 100,
+                "storage_multiplier": 5
+            },
+            "n_workers": 10,
+            "size": 165.0,
+            "asset_location": "coal_power_plant.glb",
+            "price": 1000
+        }]"#,
+        &items,
+    );
+
+    let plant = companies.descriptions.values().next().unwrap();
+    // 10 kWh every 100 seconds
+    assert_eq!(plant.power_production, 360);
+    assert_eq!(plant.recipe.production, [(items.id("coal"), 1)]);
+}
+
+#[test]
+fn no_power_without_electricity_item() {
+    let mut items = ItemRegistry::default();
+    items.load_item_definitions(r#"[{"name": "bread", "label": "Bread"}]"#);
+    let mut companies = GoodsCompanyRegistry::default();
+    companies.load(
+        r#"[{
+            "name": "Bakery",
+            "bgen": {"kind": "centered_door", "vertical_factor": 1.0},
+            "kind": "store",
+            "recipe": {
+                "consumption": [],
+                "production": [["bread", 1]],
+                "complexity": 100,
+                "storage_multiplier": 5
+            },
+            "n_workers": 3,
+            "size": 10.0,
+            "asset_location": "bakery.glb",
+            "price": 1000,
+            "power_consumption": 150
+        }]"#,
+        &items,
+    );
+
+    let bakery = companies.descriptions.values().next().unwrap();
+    assert_eq!(bakery.power_production, 0);
+    assert_eq!(bakery.power_consumption, 150);
+}
@@ -0,0 +1,339 @@
+//! Declarative scenarios to catch traffic and economy regressions.
+//!
+//! Every JSON file in `src/tests/scenarios` describes a small map, a script of commands and a list
+//! of expectations, positions being written as arrays of coordinates.
+//!
+//! The scenario is run headlessly for a number of ticks and fails as soon as an expectation can
+//! no longer be met.
+//!
+//! ```json
+//! {
+//!   "roads": [[[0, 0, 0], [200, 0, 0]]],
+//!   "houses": [[50, 20]],
+//!   "companies": [["Supermarket", [150, 20]]],
+//!   "commands": [[100, {"SpawnRandomCars": {"n_cars": 10}}]],
+//!   "ticks": 5000,
+//!   "expect": [
+//!     {"Trade": {"item": "job-opening", "within": 100}},
+//!     {"NoGridlock": {"max_stopped": 3000}}
+//!   ]
+//! }
+//! ```
+
+use super::TestCtx;
+use crate::economy::{ItemRegistry, Market, PowerGrid};
+use crate::map::procgen::build_company_along;
+use crate::map::{BuildingID, Map};
+use crate::map_dynamic::BuildingInfos;
+use crate::souls::goods_company::GoodsCompanyRegistry;
+use crate::transportation::VehicleState;
+use crate::world_command::WorldCommand;
+use crate::{SimulationOptions, VehicleID};
+use common::saveload::{Encoder, JSON};
+use geom::{Vec2, Vec3};
+use ordered_float::OrderedFloat;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+/// A vehicle parking closer than this to the door of a building has reached it
+const PARK_RADIUS: f32 = 60.0;
+
+/// Vehicles slower than this are considered stopped, in m/s
+const STOPPED_SPEED: f32 = 0.1;
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Scenario {
+    /// Size of the terrain in chunks
+    #[serde(default = "default_terrain_size")]
+    terrain_size: u16,
+    /// Roads to build before the first tick, as polylines
+    #[serde(default)]
+    roads: Vec<Vec<Vec3>>,
+    /// Houses to build on the lot nearest to each position
+    #[serde(default)]
+    houses: Vec<[f32; 2]>,
+    /// Companies to build along the road nearest to each position, by name
+    #[serde(default)]
+    companies: Vec<(String, [f32; 2])>,
+    /// Commands to apply at the given tick, in the same format as the start commands
+    #[serde(default)]
+    commands: Vec<(u64, WorldCommand)>,
+    /// Number of ticks to run
+    ticks: u64,
+    expect: Vec<Expectation>,
+}
+
+fn default_terrain_size() -> u16 {
+    1
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) enum Expectation {
+    /// A vehicle parks next to the building nearest to `near` before the tick `within`
+    VehicleReaches { near: [f32; 2], within: u64 },
+    /// Some of the item is traded on the market before the tick `within`
+    Trade { item: String, within: u64 },
+    /// The power grid covers a nonzero consumption before the tick `within`
+    Powered { within: u64 },
+    /// No driving vehicle stays stopped for more than `max_stopped` ticks in a row
+    NoGridlock { max_stopped: u64 },
+}
+
+enum Status {
+    Pending,
+    Met,
+    Failed(String),
+}
+
+struct Check {
+    expectation: Expectation,
+    status: Status,
+    /// Number of ticks each vehicle has been stopped for, used by [`Expectation::NoGridlock`]
+    stopped: BTreeMap<VehicleID, u64>,
+}
+
+impl Check {
+    fn update(&mut self, ctx: &TestCtx, tick: u64) {
+        if !matches!(self.status, Status::Pending) {
+            return;
+        }
+
+        match self.expectation {
+            Expectation::VehicleReaches { near, within } => {
+                let map = ctx.g.map();
+                let near = Vec2::from(near);
+                let Some(building) = nearest_building(&map, near) else {
+                    self.status = Status::Failed(format!("no building near {near:?}"));
+                    return;
+                };
+                let door = map.buildings()[building].door_pos;
+                let reached = ctx.g.world().vehicles.values().any(|v| {
+                    matches!(&v.vehicle.state, VehicleState::RoadToPark(spline, _, _)
+                        if spline.to.distance(door) < PARK_RADIUS)
+                });
+                if reached {
+                    self.status = Status::Met;
+                } else if tick >= within {
+                    self.status = Status::Failed(format!(
+                        "no vehicle reached {building:?} within {within} ticks"
+                    ));
+                }
+            }
+            Expectation::Trade { ref item, within } => {
+                let Some(id) = ctx.g.read::<ItemRegistry>().try_id(item) else {
+                    self.status = Status::Failed(format!("unknown item {item}"));
+                    return;
+                };
+                if ctx
+                    .g
+                    .read::<Market>()
+                    .last_trades()
+                    .iter()
+                    .any(|t| t.kind == id && t.qty > 0)
+                {
+                    self.status = Status::Met;
+                } else if tick >= within {
+                    self.status =
+                        Status::Failed(format!("no {item} was traded within {within} ticks"));
+                }
+            }
+            Expectation::Powered { within } => {
+                let grid = ctx.g.read::<PowerGrid>();
+                if grid.consumption > 0 && !grid.is_brownout() {
+                    self.status = Status::Met;
+                } else if tick >= within {
+                    self.status = Status::Failed(format!(
+                        "the grid was still short of power after {within} ticks: {} < {} kW",
+                        grid.capacity, grid.consumption
+                    ));
+                }
+            }
+            Expectation::NoGridlock { max_stopped } => {
+                let vehicles = &ctx.g.world().vehicles;
+                self.stopped.retain(|id, _| vehicles.contains_key(*id));
+                for (id, v) in vehicles.iter() {
+                    let driving = matches!(
+                        v.vehicle.state,
+                        VehicleState::Driving | VehicleState::Panicking(_)
+                    );
+                    if !driving || v.speed.0 > STOPPED_SPEED {
+                        self.stopped.remove(&id);
+                        continue;
+                    }
+                    let stopped = self.stopped.entry(id).or_default();
+                    *stopped += 1;
+                    if *stopped > max_stopped {
+                        self.status = Status::Failed(format!(
+                            "{id:?} is stuck at {:?} since {max_stopped} ticks",
+                            v.trans.position
+                        ));
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Called once the scenario is over
+    fn finish(&mut self, ticks: u64) {
+        if !matches!(self.status, Status::Pending) {
+            return;
+        }
+        self.status = match self.expectation {
+            Expectation::NoGridlock { .. } => Status::Met,
+            ref e => Status::Failed(format!("{e:?} still pending after {ticks} ticks")),
+        };
+    }
+}
+
+fn nearest_building(map: &Map, p: Vec2) -> Option<BuildingID> {
+    map.buildings()
+        .values()
+        .min_by_key(|b| OrderedFloat(b.door_pos.xy().distance2(p)))
+        .map(|b| b.id)
+}
+
+impl Scenario {
+    pub(crate) fn parse(source: &str) -> Result<Self, String> {
+        JSON::decode(source.as_bytes()).map_err(|e| e.to_string())
+    }
+
+    pub(super) fn setup(&self) -> Result<TestCtx, String> {
+        let ctx = TestCtx::with_options(SimulationOptions {
+            terrain_size: self.terrain_size,
+            save_replay: false,
+            ..Default::default()
+        });
+
+        for polyline in &self.roads {
+            ctx.build_roads(polyline);
+        }
+        for &p in &self.houses {
+            let p = Vec2::from(p);
+            if ctx.g.map().lots().is_empty() {
+                return Err(format!("no lot to build the house near {p:?}"));
+            }
+            ctx.build_house_near(p);
+        }
+
+        for (name, p) in &self.companies {
+            let p = Vec2::from(*p);
+            let registry = ctx.g.read::<GoodsCompanyRegistry>();
+            let descr = registry
+                .descriptions
+                .values()
+                .find(|d| &d.name == name)
+                .ok_or_else(|| format!("unknown company {name}"))?;
+
+            let mut map = ctx.g.map_mut();
+            let road = map
+                .roads()
+                .values()
+                .min_by_key(|r| OrderedFloat(r.points.project(p.z0()).xy().distance2(p)))
+                .map(|r| r.id)
+                .ok_or_else(|| format!("no road to build {name} along"))?;
+            let id = build_company_along(&mut map, descr, road, p)
+                .ok_or_else(|| format!("no room for {name} near {p:?}"))?;
+            drop(map);
+            ctx.g.write::<BuildingInfos>().insert(id);
+        }
+
+        Ok(ctx)
+    }
+
+    /// Runs the scenario, returns the reasons of the failed expectations
+    pub(crate) fn run(&self) -> Result<(), Vec<String>> {
+        let mut ctx = self.setup().map_err(|e| vec![e])?;
+
+        let mut checks: Vec<Check> = self
+            .expect
+            .iter()
+            .map(|e| Check {
+                expectation: e.clone(),
+                status: Status::Pending,
+                stopped: BTreeMap::new(),
+            })
+            .collect();
+
+        for tick in 0..self.ticks {
+            let commands = self
+                .commands
+                .iter()
+                .filter(|(t, _)| *t == tick)
+                .map(|(_, c)| c);
+            ctx.g.tick(&mut ctx.sched, commands);
+
+            for check in &mut checks {
+                check.update(&ctx, tick);
+            }
+            if checks.iter().any(|c| matches!(c.status, Status::Failed(_))) {
+                break;
+            }
+        }
+
+        let failures: Vec<String> = checks
+            .into_iter()
+            .filter_map(|mut c| {
+                c.finish(self.ticks);
+                match c.status {
+                    Status::Failed(reason) => Some(reason),
+                    _ => None,
+                }
+            })
+            .collect();
+
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(failures)
+        }
+    }
+}
+
+#[test]
+fn scenarios() {
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/scenarios");
+    let mut paths: Vec<_> = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty());
+
+    let mut failures = vec![];
+    for path in paths {
+        let name = path.file_stem().unwrap().to_string_lossy().to_string();
+        let source = common::saveload::load_string(&path).unwrap();
+        let scenario = match Scenario::parse(&source) {
+            Ok(s) => s,
+            Err(e) => {
+                failures.push(format!("{name}: cannot parse: {e}"));
+                continue;
+            }
+        };
+        if let Err(reasons) = scenario.run() {
+            failures.extend(reasons.into_iter().map(|r| format!("{name}: {r}")));
+        }
+    }
+
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[test]
+fn unmet_expectation_fails() {
+    let scenario = Scenario::parse(
+        r#"{
+            "roads": [[[50, 50, 0], [250, 50, 0]]],
+            "ticks": 20,
+            "expect": [{"Trade": {"item": "bread", "within": 10}}]
+        }"#,
+    )
+    .unwrap();
+
+    let failures = scenario.run().unwrap_err();
+    assert_eq!(failures.len(), 1);
+    assert!(failures[0].contains("bread"));
+}
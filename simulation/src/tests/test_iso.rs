@@ -292,3 +292,16 @@ fn test_world_survives_serde() {
 
     sim.save_to_disk("world2");
 }
+
+#[test]
+fn parts_roundtrip() {
+    let mut ctx = super::TestCtx::new();
+    ctx.build_roads(&[vec3(0.0, 0.0, 0.0), vec3(200.0, 0.0, 0.0)]);
+    for _ in 0..10 {
+        ctx.tick();
+    }
+
+    let sim2 = Simulation::from_parts(ctx.g.parts()).unwrap();
+    assert_eq!(ctx.g.hashes(), sim2.hashes());
+    assert_eq!(ctx.g.get_tick(), sim2.get_tick());
+}
@@ -1,4 +1,6 @@
-use crate::map::{Map, TrafficBehavior, Traversable, TraverseKind};
+use crate::definitions::Definitions;
+use crate::events::{EventKind, Events};
+use crate::map::{Map, TrafficBehavior, Traversable, TraverseDirection, TraverseKind};
 use crate::map_dynamic::{Itinerary, OBJECTIVE_OK_DIST};
 use crate::physics::Speed;
 use crate::physics::{Collider, CollisionWorld, PhysicsGroup, PhysicsObject};
@@ -8,6 +10,7 @@ use crate::utils::time::GameTime;
 use crate::world::{VehicleEnt, VehicleID};
 use crate::ParCommandBuffer;
 use crate::World;
+use common::descriptions::VehicleDescription;
 use geom::{angle_lerpxy, Ray, Transform, Vec2, Vec3};
 use slotmapd::Key;
 
@@ -16,16 +19,20 @@ pub fn vehicle_decision_system(world: &mut World, resources: &mut Resources) {
     let ra = &*resources.read();
     let rb = &*resources.read();
     let rc = &*resources.read();
+    let rd = &*resources.read();
+    let mut events = resources.write::<Events>();
 
     world.vehicles.iter_mut().for_each(|(ent, v)| {
         let Some(ref coll) = v.collider else {
             return;
         };
+        let was_panicking = matches!(v.vehicle.state, VehicleState::Panicking(_));
 
         vehicle_decision(
             ra,
             rb,
             rc,
+            rd,
             ent,
             &mut v.it,
             &mut v.trans,
@@ -33,6 +40,22 @@ pub fn vehicle_decision_system(world: &mut World, resources: &mut Resources) {
             &mut v.vehicle,
             coll,
         );
+
+        if !was_panicking && matches!(v.vehicle.state, VehicleState::Panicking(_)) {
+            if let Some(inter) = v.it.get_travers().and_then(|t| match t.kind {
+                TraverseKind::Turn(id) => Some(id.parent),
+                TraverseKind::Lane(id) => {
+                    let lane = ra.lanes().get(id)?;
+                    Some(match t.dir {
+                        TraverseDirection::Forward => lane.dst,
+                        TraverseDirection::Backward => lane.src,
+                    })
+                }
+            }) {
+                let pos = ra.intersections().get(inter).map(|i| i.pos);
+                events.emit(EventKind::Gridlock(inter), pos, rb);
+            }
+        }
     });
 }
 
@@ -40,6 +63,7 @@ pub fn vehicle_decision(
     map: &Map,
     time: &GameTime,
     cow: &CollisionWorld,
+    defs: &Definitions,
     me: VehicleID,
     it: &mut Itinerary,
     trans: &mut Transform,
@@ -48,6 +72,7 @@ pub fn vehicle_decision(
     collider: &Collider,
 ) {
     let (_, self_obj) = cow.get(collider.0).expect("Handle not in collision world");
+    let descr = defs.vehicle(vehicle.kind);
 
     let mut desired_speed = 0.0;
     let mut desired_dir = Vec3::ZERO;
@@ -55,13 +80,12 @@ pub fn vehicle_decision(
         vehicle.state,
         VehicleState::Driving | VehicleState::Panicking(_)
     ) {
-        let danger_length =
-            (self_obj.speed.powi(2) / (2.0 * vehicle.kind.deceleration())).min(100.0);
+        let danger_length = (self_obj.speed.powi(2) / (2.0 * descr.deceleration)).min(100.0);
         let neighbors = cow.query_around(trans.position.xy(), 12.0 + danger_length);
         let objs =
             neighbors.map(|(id, pos)| (pos, cow.get(id).expect("Handle not in collision world").1));
 
-        let (s, d) = calc_decision(me, vehicle, map, time, trans, self_obj, it, objs);
+        let (s, d) = calc_decision(me, vehicle, descr, map, time, trans, self_obj, it, objs);
         desired_speed = s;
         desired_dir = d;
     }
@@ -70,6 +94,7 @@ pub fn vehicle_decision(
         trans,
         kin,
         vehicle,
+        descr,
         time,
         self_obj,
         map,
@@ -145,6 +170,7 @@ fn physics(
     trans: &mut Transform,
     kin: &mut Speed,
     vehicle: &mut Vehicle,
+    descr: &VehicleDescription,
     time: &GameTime,
     obj: &PhysicsObject,
     map: &Map,
@@ -166,19 +192,18 @@ fn physics(
     }
 
     let speed = obj.speed;
-    let kind = vehicle.kind;
 
     let speed = speed
         + (desired_speed - speed).clamp(
-            -time.realdelta * kind.deceleration(),
-            time.realdelta * kind.acceleration(),
+            -time.realdelta * descr.deceleration,
+            time.realdelta * descr.acceleration,
         );
 
-    let max_ang_vel = (speed.abs() / kind.min_turning_radius()).clamp(0.0, 3.0);
+    let max_ang_vel = (speed.abs() / descr.min_turning_radius).clamp(0.0, 3.0);
 
     let approx_angle = trans.dir.distance(desired_dir);
 
-    vehicle.ang_velocity += time.realdelta * kind.ang_acc();
+    vehicle.ang_velocity += time.realdelta * descr.ang_acc;
     vehicle.ang_velocity = vehicle
         .ang_velocity
         .min(4.0 * approx_angle)
@@ -197,6 +222,7 @@ fn physics(
 pub fn calc_decision<'a>(
     me: VehicleID,
     vehicle: &mut Vehicle,
+    descr: &VehicleDescription,
     map: &Map,
     time: &GameTime,
     trans: &Transform,
@@ -212,12 +238,12 @@ pub fn calc_decision<'a>(
     let objective: Vec3 = unwrap_or!(it.get_point(), return default_return);
 
     let speed = self_obj.speed;
-    let time_to_stop = speed / vehicle.kind.deceleration();
+    let time_to_stop = speed / descr.deceleration;
     let stop_dist = time_to_stop * speed * 0.5;
 
     let cutoff = (0.8 + stop_dist).min(1.5);
 
-    let (front_dist, flag) = calc_front_dist(vehicle, trans, self_obj, it, neighs, cutoff);
+    let (front_dist, flag) = calc_front_dist(descr, trans, self_obj, it, neighs, cutoff);
 
     let position = trans.position;
     let dir_to_pos = unwrap_or!(
@@ -276,7 +302,7 @@ pub fn calc_decision<'a>(
                         OBJECTIVE_OK_DIST * 1.05
                             + 2.0
                             + stop_dist
-                            + (vehicle.kind.width() * 0.5 - OBJECTIVE_OK_DIST).max(0.0),
+                            + (descr.width * 0.5 - OBJECTIVE_OK_DIST).max(0.0),
                     ) {
                         return (0.0, dir_to_pos);
                     }
@@ -302,7 +328,7 @@ pub fn calc_decision<'a>(
     }
 
     (
-        vehicle.kind.speed_factor() * vehicle.max_speed_multiplier * speed,
+        descr.speed_factor * vehicle.max_speed_multiplier * speed,
         dir_to_pos,
     )
 }
@@ -311,7 +337,7 @@ pub fn calc_decision<'a>(
 /// It can be another car or a pedestrian, or it can be a potential collision point from a
 /// car coming perpendicularly.
 fn calc_front_dist<'a>(
-    vehicle: &mut Vehicle,
+    descr: &VehicleDescription,
     trans: &Transform,
     self_obj: &PhysicsObject,
     it: &Itinerary,
@@ -326,7 +352,7 @@ fn calc_front_dist<'a>(
     let mut min_front_dist: f32 = 50.0;
 
     let my_ray = Ray {
-        from: position.xy() - direction.xy() * vehicle.kind.width() * 0.5,
+        from: position.xy() - direction.xy() * descr.width * 0.5,
         dir: direction.xy(),
     };
 
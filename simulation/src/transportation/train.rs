@@ -1,3 +1,4 @@
+use crate::definitions::Definitions;
 use crate::map::{IntersectionID, LaneID, Map, TraverseKind};
 use crate::map_dynamic::ItineraryFollower;
 use crate::utils::resources::Resources;
@@ -92,6 +93,7 @@ pub fn spawn_train(
 
     let map = res.read::<Map>();
     let lane = map.lanes().get(lane)?;
+    let loco_descr = &res.read::<Definitions>().locomotive;
 
     let (locopos, locodir) = lane.points.point_dir_along(dist);
 
@@ -112,9 +114,9 @@ pub fn spawn_train(
         speed: Default::default(),
         it: Itinerary::NONE,
         locomotive: Locomotive {
-            max_speed: 50.0,
-            acc_force: 1.0,
-            dec_force: 2.5,
+            max_speed: loco_descr.max_speed,
+            acc_force: loco_descr.acc_force,
+            dec_force: loco_descr.dec_force,
             length: trainlength,
         },
         res: LocomotiveReservation {
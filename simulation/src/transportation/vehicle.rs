@@ -1,3 +1,4 @@
+use crate::definitions::Definitions;
 use crate::map_dynamic::{Itinerary, ParkingManagement, SpotReservation};
 use crate::physics::{Collider, CollisionWorld, PhysicsGroup, PhysicsObject};
 use crate::utils::rand_provider::RandProvider;
@@ -57,56 +58,10 @@ pub fn put_vehicle_in_coworld(sim: &Simulation, w: f32, trans: Transform) -> Col
     ))
 }
 
-impl VehicleKind {
-    pub fn width(self) -> f32 {
-        match self {
-            VehicleKind::Car => 4.5,
-            VehicleKind::Truck => 6.0,
-            VehicleKind::Bus => 9.0,
-        }
-    }
-
-    pub fn acceleration(self) -> f32 {
-        match self {
-            VehicleKind::Car => 3.0,
-            VehicleKind::Truck => 2.5,
-            VehicleKind::Bus => 2.0,
-        }
-    }
-
-    pub fn deceleration(self) -> f32 {
-        match self {
-            VehicleKind::Car | VehicleKind::Bus | VehicleKind::Truck => 6.0,
-        }
-    }
-
-    pub fn min_turning_radius(self) -> f32 {
-        match self {
-            VehicleKind::Car => 0.5,
-            VehicleKind::Truck => 3.0,
-            VehicleKind::Bus => 4.0,
-        }
-    }
-
-    pub fn speed_factor(self) -> f32 {
-        match self {
-            VehicleKind::Car => 1.0,
-            VehicleKind::Truck | VehicleKind::Bus => 0.8,
-        }
-    }
-
-    pub fn ang_acc(self) -> f32 {
-        match self {
-            VehicleKind::Car => 1.0,
-            VehicleKind::Truck => 0.9,
-            VehicleKind::Bus => 0.8,
-        }
-    }
-}
-
 pub fn unpark(sim: &mut Simulation, vehicle: VehicleID) {
+    let kind = unwrap_ret!(sim.world.vehicles.get(vehicle)).vehicle.kind;
+    let w = sim.read::<Definitions>().vehicle(kind).width;
     let v = unwrap_ret!(sim.world.vehicles.get_mut(vehicle));
-    let w = v.vehicle.kind.width();
     let trans = v.trans;
 
     if let VehicleState::Parked(spot) =
@@ -163,7 +118,7 @@ pub fn make_vehicle_entity(
     it: Itinerary,
     mk_collider: bool,
 ) -> VehicleID {
-    let w = vehicle.kind.width();
+    let w = sim.read::<Definitions>().vehicle(vehicle.kind).width;
 
     let mut collider = None;
     if mk_collider {
@@ -4,6 +4,7 @@ use egui_inspect::Inspect;
 use geom::Color;
 use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
+use std::path::Path;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 
@@ -50,7 +51,17 @@ fn load_config_start() -> Config {
         })
         .unwrap_or_default();
     save_config(&c);
-    c
+
+    // mods only change the loaded config, the saved one stays the base one
+    if common::mods::resolve("assets/config.json") == Path::new("assets/config.json") {
+        return c;
+    }
+    common::mods::load_json("assets/config.json")
+        .and_then(|x| common::saveload::JSON::decode(x.as_bytes()).map_err(|e| e.to_string()))
+        .map_err(|x| {
+            log::error!("couldn't read modded config: {}", x);
+        })
+        .unwrap_or(c)
 }
 
 fn save_config(config: &Config) {
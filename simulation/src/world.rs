@@ -1,4 +1,4 @@
-use crate::economy::{Bought, Market, Sold, Workers};
+use crate::economy::{Bought, Market, Sold, TradeFlows, Workers};
 use crate::map_dynamic::{
     DispatchID, Dispatcher, Itinerary, ItineraryFollower, ItineraryLeader, ParkingManagement,
     Router,
@@ -174,6 +174,7 @@ pub struct CompanyEnt {
 impl SimDrop for CompanyEnt {
     fn sim_drop(self, id: CompanyID, res: &mut Resources) {
         res.write::<Market>().remove(SoulID::GoodsCompany(id));
+        res.write::<TradeFlows>().remove(SoulID::GoodsCompany(id));
     }
 }
 
@@ -2,13 +2,15 @@ use std::collections::BTreeMap;
 use std::time::Instant;
 
 use common::descriptions::BuildingGen;
+use common::Role;
 use serde::{Deserialize, Serialize};
 
 use geom::{vec3, Vec2, Vec3, OBB};
 use WorldCommand::*;
 
 use crate::economy::Government;
-use crate::map::procgen::{load_parismap, load_testfield};
+use crate::map::procgen::heightmap::WorldGenOptions;
+use crate::map::procgen::{gen_city, load_osm, load_parismap, load_testfield, OsmData};
 use crate::map::{
     BuildingID, BuildingKind, Environment, IntersectionID, LaneID, LanePattern, LanePatternBuilder,
     LightPolicy, LotID, Map, MapProject, ProjectKind, RoadID, TerraformKind, TurnPolicy, Zone,
@@ -16,6 +18,8 @@ use crate::map::{
 use crate::map_dynamic::{BuildingInfos, ParkingManagement};
 use crate::multiplayer::chat::Message;
 use crate::multiplayer::MultiplayerState;
+use crate::scripting::Scripts;
+use crate::souls::goods_company::GoodsCompanyRegistry;
 use crate::transportation::testing_vehicles::RandomVehicles;
 use crate::transportation::train::{spawn_train, RailWagonKind};
 use crate::transportation::{spawn_parked_vehicle_with_spot, unpark, VehicleKind};
@@ -30,6 +34,26 @@ pub struct WorldCommands {
 
 defer_serialize!(WorldCommands, Vec<WorldCommand>);
 
+/// What a player needs to be allowed to issue a command in multiplayer
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Permission {
+    Chat,
+    Build,
+    /// Commands that replace or distort the whole world
+    Admin,
+}
+
+impl Permission {
+    /// Whether a player with this role may issue the commands needing this permission
+    pub fn granted_to(self, role: Role) -> bool {
+        match self {
+            Permission::Chat => true,
+            Permission::Build => role >= Role::Builder,
+            Permission::Admin => role == Role::Admin,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum WorldCommand {
     Init(Box<SimulationOptions>),
@@ -79,6 +103,15 @@ pub enum WorldCommand {
         zone: Option<Zone>,
     },
     MapLoadParis,
+    MapLoadOSM {
+        data: Box<OsmData>,
+        center: Vec2,
+    },
+    MapGenerateCity {
+        seed: u64,
+        center: Vec2,
+        radius: f32,
+    },
     MapLoadTestField {
         pos: Vec2,
         size: u32,
@@ -89,6 +122,12 @@ pub enum WorldCommand {
         zone: Zone,
     },
     SetGameTime(GameTime),
+    /// Installs or replaces a simulation script, see [`crate::scripting`]
+    SetScript {
+        name: String,
+        source: String,
+    },
+    RemoveScript(String),
 }
 
 impl AsRef<[WorldCommand]> for WorldCommands {
@@ -118,10 +157,44 @@ impl WorldCommands {
         self.commands.is_empty()
     }
 
+    /// Removes the commands the role is not allowed to issue, returns why they were removed.
+    /// This is the input filter of the multiplayer server
+    pub fn retain_permitted(role: Role, inputs: &mut WorldCommands) -> Vec<String> {
+        let mut rejected = vec![];
+        inputs.commands.retain(|cmd| {
+            let permission = cmd.permission();
+            if permission.granted_to(role) {
+                return true;
+            }
+            rejected.push(format!(
+                "{} requires the {:?} permission",
+                cmd.name(),
+                permission
+            ));
+            false
+        });
+        rejected
+    }
+
     pub fn map_load_paris(&mut self) {
         self.commands.push(MapLoadParis)
     }
 
+    pub fn map_load_osm(&mut self, data: OsmData, center: Vec2) {
+        self.commands.push(MapLoadOSM {
+            data: Box::new(data),
+            center,
+        })
+    }
+
+    pub fn map_generate_city(&mut self, seed: u64, center: Vec2, radius: f32) {
+        self.commands.push(MapGenerateCity {
+            seed,
+            center,
+            radius,
+        })
+    }
+
     pub fn map_load_testfield(&mut self, pos: Vec2, size: u32, spacing: f32) {
         self.commands.push(MapLoadTestField { pos, size, spacing })
     }
@@ -130,6 +203,14 @@ impl WorldCommands {
         self.commands.push(SetGameTime(gt))
     }
 
+    pub fn set_script(&mut self, name: String, source: String) {
+        self.commands.push(SetScript { name, source })
+    }
+
+    pub fn remove_script(&mut self, name: String) {
+        self.commands.push(RemoveScript(name))
+    }
+
     pub fn add_train(&mut self, dist: f32, n_wagons: u32, laneid: LaneID) {
         self.commands.push(AddTrain {
             dist,
@@ -199,6 +280,59 @@ impl WorldCommands {
 }
 
 impl WorldCommand {
+    pub fn permission(&self) -> Permission {
+        match self {
+            SendMessage { .. } => Permission::Chat,
+            MapRemoveIntersection(_)
+            | MapRemoveRoad(_)
+            | MapRemoveBuilding(_)
+            | MapBuildHouse(_)
+            | Terraform { .. }
+            | AddTrain { .. }
+            | MapMakeConnection { .. }
+            | MapMakeMultipleConnections(..)
+            | MapUpdateIntersectionPolicy { .. }
+            | MapBuildSpecialBuilding { .. }
+            | UpdateZone { .. } => Permission::Build,
+            Init(_)
+            | SpawnRandomCars { .. }
+            | MapLoadParis
+            | MapLoadOSM { .. }
+            | MapGenerateCity { .. }
+            | MapLoadTestField { .. }
+            | SetGameTime(_)
+            | SetScript { .. }
+            | RemoveScript(_) => Permission::Admin,
+        }
+    }
+
+    /// Name of the variant, without the data which can be large
+    pub fn name(&self) -> &'static str {
+        match self {
+            Init(_) => "Init",
+            MapRemoveIntersection(_) => "MapRemoveIntersection",
+            MapRemoveRoad(_) => "MapRemoveRoad",
+            MapRemoveBuilding(_) => "MapRemoveBuilding",
+            MapBuildHouse(_) => "MapBuildHouse",
+            Terraform { .. } => "Terraform",
+            SendMessage { .. } => "SendMessage",
+            SpawnRandomCars { .. } => "SpawnRandomCars",
+            AddTrain { .. } => "AddTrain",
+            MapMakeConnection { .. } => "MapMakeConnection",
+            MapMakeMultipleConnections(..) => "MapMakeMultipleConnections",
+            MapUpdateIntersectionPolicy { .. } => "MapUpdateIntersectionPolicy",
+            MapBuildSpecialBuilding { .. } => "MapBuildSpecialBuilding",
+            MapLoadParis => "MapLoadParis",
+            MapLoadOSM { .. } => "MapLoadOSM",
+            MapGenerateCity { .. } => "MapGenerateCity",
+            MapLoadTestField { .. } => "MapLoadTestField",
+            UpdateZone { .. } => "UpdateZone",
+            SetGameTime(_) => "SetGameTime",
+            SetScript { .. } => "SetScript",
+            RemoveScript(_) => "RemoveScript",
+        }
+    }
+
     /// Returns true if the command can be applied without any systems needed to be run afterward
     pub fn is_instant(&self) -> bool {
         matches!(
@@ -242,6 +376,10 @@ impl WorldCommand {
             MapMakeMultipleConnections(ref projects, ref links) => {
                 let mut map = sim.map_mut();
                 let mut inters = BTreeMap::new();
+                // ground and road projects create a new intersection, the next links using the
+                // same project have to connect to it
+                let creates_inter =
+                    |kind: ProjectKind| matches!(kind, ProjectKind::Ground | ProjectKind::Road(_));
                 for (from, to, interpoint, pat) in links {
                     let mut fromproj = projects[*from];
                     let mut toproj = projects[*to];
@@ -254,10 +392,10 @@ impl WorldCommand {
                     }
 
                     if let Some((_, r)) = map.make_connection(fromproj, toproj, *interpoint, pat) {
-                        if fromproj.kind.is_ground() {
+                        if creates_inter(fromproj.kind) {
                             inters.insert(*from, map.roads[r].src);
                         }
-                        if toproj.kind.is_ground() {
+                        if creates_inter(toproj.kind) {
                             inters.insert(*to, map.roads[r].dst);
                         }
                     }
@@ -285,6 +423,11 @@ impl WorldCommand {
                 }
             }
             SetGameTime(gt) => *sim.write::<GameTime>() = gt,
+            SetScript {
+                ref name,
+                ref source,
+            } => sim.write::<Scripts>().set(name.clone(), source.clone()),
+            RemoveScript(ref name) => sim.write::<Scripts>().remove(name),
             AddTrain {
                 dist,
                 n_wagons,
@@ -293,6 +436,30 @@ impl WorldCommand {
                 spawn_train(sim, dist, n_wagons, lane, RailWagonKind::Freight);
             }
             MapLoadParis => load_parismap(&mut sim.map_mut()),
+            MapLoadOSM { ref data, center } => {
+                let built = load_osm(&mut sim.map_mut(), data, center);
+                let mut infos = sim.write::<BuildingInfos>();
+                for id in built {
+                    infos.insert(id);
+                }
+            }
+            MapGenerateCity {
+                seed,
+                center,
+                radius,
+            } => {
+                let built = gen_city(
+                    &mut sim.map_mut(),
+                    &sim.read::<GoodsCompanyRegistry>(),
+                    seed,
+                    center,
+                    radius,
+                );
+                let mut infos = sim.write::<BuildingInfos>();
+                for id in built {
+                    infos.insert(id);
+                }
+            }
             MapLoadTestField { pos, size, spacing } => {
                 load_testfield(&mut sim.map_mut(), pos, size, spacing)
             }
@@ -304,8 +471,10 @@ impl WorldCommand {
                     rep.commands.push((*tick, Init(opts.clone())));
                 }
 
+                *sim.write::<RandProvider>() = RandProvider::new(opts.worldgen.seed);
+
                 if opts.terrain_size > 0 {
-                    generate_terrain(sim, opts.terrain_size);
+                    generate_terrain(sim, opts.terrain_size, &opts.worldgen);
                 }
 
                 sim.resources
@@ -359,11 +528,11 @@ impl WorldCommand {
     }
 }
 
-fn generate_terrain(sim: &mut Simulation, size: u16) {
+fn generate_terrain(sim: &mut Simulation, size: u16, opts: &WorldGenOptions) {
     info!("generating terrain..");
     let t = Instant::now();
 
-    sim.map_mut().environment = Environment::new(size, size);
+    sim.map_mut().environment = Environment::generate(size, size, opts);
     info!("took {}s", t.elapsed().as_secs_f32());
 
     let c = vec3(3000.0 + 72.2 / 2.0, 200.0 / 2.0 + 1.0, 0.3);
@@ -418,3 +587,65 @@ impl From<&WorldCommands> for Vec<WorldCommand> {
         x.commands.clone()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{WorldCommand, WorldCommands};
+    use crate::map::{LotID, RoadID};
+    use crate::multiplayer::chat::{Message, MessageKind};
+    use crate::utils::time::GameInstant;
+    use common::Role;
+    use geom::Color;
+
+    fn commands() -> WorldCommands {
+        let mut cmds = WorldCommands::default();
+        cmds.push(WorldCommand::SendMessage {
+            message: Message {
+                name: "bob".to_string(),
+                text: "hi".to_string(),
+                sent_at: GameInstant { timestamp: 0.0 },
+                color: Color::WHITE,
+                kind: MessageKind::PlayerChat,
+            },
+        });
+        cmds.push(WorldCommand::MapRemoveRoad(RoadID::default()));
+        cmds.push(WorldCommand::MapBuildHouse(LotID::default()));
+        cmds.map_load_paris();
+        cmds
+    }
+
+    fn names(cmds: &WorldCommands) -> Vec<&'static str> {
+        cmds.iter().map(WorldCommand::name).collect()
+    }
+
+    #[test]
+    fn viewer_can_only_chat() {
+        let mut cmds = commands();
+        let rejected = WorldCommands::retain_permitted(Role::Viewer, &mut cmds);
+
+        assert_eq!(names(&cmds), ["SendMessage"]);
+        assert_eq!(rejected.len(), 3);
+        assert_eq!(rejected[0], "MapRemoveRoad requires the Build permission");
+    }
+
+    #[test]
+    fn builder_cannot_replace_the_map() {
+        let mut cmds = commands();
+        let rejected = WorldCommands::retain_permitted(Role::Builder, &mut cmds);
+
+        assert_eq!(
+            names(&cmds),
+            ["SendMessage", "MapRemoveRoad", "MapBuildHouse"]
+        );
+        assert_eq!(rejected, ["MapLoadParis requires the Admin permission"]);
+    }
+
+    #[test]
+    fn admin_keeps_everything() {
+        let mut cmds = commands();
+        let rejected = WorldCommands::retain_permitted(Role::Admin, &mut cmds);
+
+        assert!(rejected.is_empty());
+        assert_eq!(names(&cmds).len(), 4);
+    }
+}